@@ -0,0 +1,94 @@
+//! Compile time and code size of a fixed corpus at each `--opt-level`.
+//!
+//! Run with `cargo bench --bench opt_levels`. Besides Criterion's timings,
+//! the total code size of each module is printed per level, and the bench
+//! fails if optimizing makes a module's code more than
+//! `SIZE_REGRESSION_PERCENT` larger than at `none`.
+
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::settings::{self, Configurable};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fs;
+use wabt;
+use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+
+/// The modules compiled, from `filetests`.
+const CORPUS: &[&str] = &[
+    "arith",
+    "br_table",
+    "call",
+    "call_indirect",
+    "fibonacci",
+    "globals",
+    "many_functions",
+    "memory",
+];
+
+/// The `--opt-level` names and the Cranelift `opt_level` they set.
+const OPT_LEVELS: &[(&str, &str)] = &[
+    ("none", "fastest"),
+    ("speed", "default"),
+    ("speed_and_size", "best"),
+];
+
+/// How much larger, in percent, an optimized module's code may be than
+/// its code at `none`.
+const SIZE_REGRESSION_PERCENT: usize = 10;
+
+fn isa(opt_level: &str) -> Box<dyn TargetIsa> {
+    let mut flag_builder = settings::builder();
+    flag_builder.set("opt_level", opt_level).unwrap();
+    let isa_builder = cranelift_native::builder().unwrap_or_else(|_| {
+        panic!("host machine is not a supported target");
+    });
+    isa_builder.finish(settings::Flags::new(flag_builder))
+}
+
+/// Compiles `data` and returns the total size of its code.
+fn compile(data: &[u8], isa: &dyn TargetIsa) -> usize {
+    let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(data)
+        .expect("translation");
+    let (compilation, _, _, _, _) = cranelift::compile_module(
+        &translation.module,
+        translation.function_body_inputs,
+        isa,
+        false,
+    )
+    .expect("compilation");
+    compilation.functions.values().map(Vec::len).sum()
+}
+
+fn opt_levels(c: &mut Criterion) {
+    for name in CORPUS {
+        let wat = fs::read(format!("filetests/{}.wat", name)).expect("corpus module");
+        let data = wabt::wat2wasm(wat).expect("expecting valid wat");
+
+        let mut unoptimized_size = None;
+        for &(level, opt_level) in OPT_LEVELS {
+            let isa = isa(opt_level);
+            let size = compile(&data, &*isa);
+            println!("{} at {}: {} bytes of code", name, level, size);
+            match unoptimized_size {
+                None => unoptimized_size = Some(size),
+                Some(unoptimized_size) => assert!(
+                    size * 100 <= unoptimized_size * (100 + SIZE_REGRESSION_PERCENT),
+                    "{} has {} bytes of code at {}, more than {}% over the {} bytes at none",
+                    name,
+                    size,
+                    level,
+                    SIZE_REGRESSION_PERCENT,
+                    unoptimized_size
+                ),
+            }
+
+            let data = data.clone();
+            c.bench_function(&format!("{}/{}", name, level), move |b| {
+                b.iter(|| compile(&data, &*isa))
+            });
+        }
+    }
+}
+
+criterion_group!(benches, opt_levels);
+criterion_main!(benches);
@@ -0,0 +1,79 @@
+(module
+  ;; Returns 100 plus the index for indices below 17, and 0 otherwise. The
+  ;; br_table has enough targets for Cranelift to lower it to a jump table.
+  (func (export "select") (param i32) (result i32)
+    block $default
+    block $16
+    block $15
+    block $14
+    block $13
+    block $12
+    block $11
+    block $10
+    block $9
+    block $8
+    block $7
+    block $6
+    block $5
+    block $4
+    block $3
+    block $2
+    block $1
+    block $0
+    get_local 0
+    br_table $0 $1 $2 $3 $4 $5 $6 $7 $8 $9 $10 $11 $12 $13 $14 $15 $16 $default
+    end
+    i32.const 100
+    return
+    end
+    i32.const 101
+    return
+    end
+    i32.const 102
+    return
+    end
+    i32.const 103
+    return
+    end
+    i32.const 104
+    return
+    end
+    i32.const 105
+    return
+    end
+    i32.const 106
+    return
+    end
+    i32.const 107
+    return
+    end
+    i32.const 108
+    return
+    end
+    i32.const 109
+    return
+    end
+    i32.const 110
+    return
+    end
+    i32.const 111
+    return
+    end
+    i32.const 112
+    return
+    end
+    i32.const 113
+    return
+    end
+    i32.const 114
+    return
+    end
+    i32.const 115
+    return
+    end
+    i32.const 116
+    return
+    end
+    i32.const 0
+  )
+)
@@ -0,0 +1,15 @@
+(module
+  (import "env" "counter" (global $counter (mut i32)))
+  (import "env" "limit" (global $limit i64))
+
+  (func (export "increment") (result i32)
+    get_global $counter
+    i32.const 1
+    i32.add
+    set_global $counter
+    get_global $counter
+  )
+  (func (export "limit") (result i64)
+    get_global $limit
+  )
+)
@@ -0,0 +1,53 @@
+(module
+  ;; Function k returns 1000 + k, so that each body is distinguishable.
+  (func (export "f0") (result i32) (i32.const 1000))
+  (func (export "f1") (result i32) (i32.const 1001))
+  (func (export "f2") (result i32) (i32.const 1002))
+  (func (export "f3") (result i32) (i32.const 1003))
+  (func (export "f4") (result i32) (i32.const 1004))
+  (func (export "f5") (result i32) (i32.const 1005))
+  (func (export "f6") (result i32) (i32.const 1006))
+  (func (export "f7") (result i32) (i32.const 1007))
+  (func (export "f8") (result i32) (i32.const 1008))
+  (func (export "f9") (result i32) (i32.const 1009))
+  (func (export "f10") (result i32) (i32.const 1010))
+  (func (export "f11") (result i32) (i32.const 1011))
+  (func (export "f12") (result i32) (i32.const 1012))
+  (func (export "f13") (result i32) (i32.const 1013))
+  (func (export "f14") (result i32) (i32.const 1014))
+  (func (export "f15") (result i32) (i32.const 1015))
+  (func (export "f16") (result i32) (i32.const 1016))
+  (func (export "f17") (result i32) (i32.const 1017))
+  (func (export "f18") (result i32) (i32.const 1018))
+  (func (export "f19") (result i32) (i32.const 1019))
+  (func (export "f20") (result i32) (i32.const 1020))
+  (func (export "f21") (result i32) (i32.const 1021))
+  (func (export "f22") (result i32) (i32.const 1022))
+  (func (export "f23") (result i32) (i32.const 1023))
+  (func (export "f24") (result i32) (i32.const 1024))
+  (func (export "f25") (result i32) (i32.const 1025))
+  (func (export "f26") (result i32) (i32.const 1026))
+  (func (export "f27") (result i32) (i32.const 1027))
+  (func (export "f28") (result i32) (i32.const 1028))
+  (func (export "f29") (result i32) (i32.const 1029))
+  (func (export "f30") (result i32) (i32.const 1030))
+  (func (export "f31") (result i32) (i32.const 1031))
+  (func (export "f32") (result i32) (i32.const 1032))
+  (func (export "f33") (result i32) (i32.const 1033))
+  (func (export "f34") (result i32) (i32.const 1034))
+  (func (export "f35") (result i32) (i32.const 1035))
+  (func (export "f36") (result i32) (i32.const 1036))
+  (func (export "f37") (result i32) (i32.const 1037))
+  (func (export "f38") (result i32) (i32.const 1038))
+  (func (export "f39") (result i32) (i32.const 1039))
+  (func (export "f40") (result i32) (i32.const 1040))
+  (func (export "f41") (result i32) (i32.const 1041))
+  (func (export "f42") (result i32) (i32.const 1042))
+  (func (export "f43") (result i32) (i32.const 1043))
+  (func (export "f44") (result i32) (i32.const 1044))
+  (func (export "f45") (result i32) (i32.const 1045))
+  (func (export "f46") (result i32) (i32.const 1046))
+  (func (export "f47") (result i32) (i32.const 1047))
+  (func (export "f48") (result i32) (i32.const 1048))
+  (func (export "f49") (result i32) (i32.const 1049))
+)
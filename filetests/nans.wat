@@ -0,0 +1,10 @@
+(module
+  (func (export "f32_add") (param f32 f32) (result f32)
+    (f32.add (get_local 0) (get_local 1)))
+  (func (export "f32_mul") (param f32 f32) (result f32)
+    (f32.mul (get_local 0) (get_local 1)))
+  (func (export "f64_add") (param f64 f64) (result f64)
+    (f64.add (get_local 0) (get_local 1)))
+  (func (export "f64_div") (param f64 f64) (result f64)
+    (f64.div (get_local 0) (get_local 1)))
+)
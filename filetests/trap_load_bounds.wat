@@ -0,0 +1,7 @@
+(module
+  (memory 1 1)
+  (func (export "load") (result i32)
+    i32.const 65536
+    i32.load
+  )
+)
@@ -0,0 +1,144 @@
+//! The WASI module kinds, which differ in what runs at load time.
+//!
+//! A command exports `_start`, which the embedder calls as the program's
+//! entry point once it has set up the WASI context, so nothing runs at load
+//! time. A reactor may export `_initialize`, which must run before any of
+//! its other exports are called; it is run at load time through an
+//! `.init_array` entry.
+
+use std::str::FromStr;
+use wasmtime_environ::Module;
+
+/// The export a command module is entered through.
+pub const COMMAND_ENTRY: &str = "_start";
+
+/// The export a reactor module is initialized through.
+pub const REACTOR_INITIALIZER: &str = "_initialize";
+
+/// A WASI module kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModuleKind {
+    /// A program with a `_start` entry point.
+    Command,
+    /// A library with an optional `_initialize` function.
+    Reactor,
+}
+
+impl FromStr for ModuleKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "command" => Ok(ModuleKind::Command),
+            "reactor" => Ok(ModuleKind::Reactor),
+            _ => Err(format!(
+                "unknown module kind '{}'; expected 'command' or 'reactor'",
+                s
+            )),
+        }
+    }
+}
+
+/// Returns the kind of `module` from its exports, for `--module-kind auto`,
+/// if it follows either convention.
+pub fn detect_module_kind(module: &Module) -> Result<Option<ModuleKind>, String> {
+    let command = module.exports.contains_key(COMMAND_ENTRY);
+    let reactor = module.exports.contains_key(REACTOR_INITIALIZER);
+    match (command, reactor) {
+        (true, true) => Err(format!(
+            "module exports both {} and {}",
+            COMMAND_ENTRY, REACTOR_INITIALIZER
+        )),
+        (true, false) => Ok(Some(ModuleKind::Command)),
+        (false, true) => Ok(Some(ModuleKind::Reactor)),
+        (false, false) => Ok(None),
+    }
+}
+
+/// Returns the export to run at load time for a module of kind `kind`, if
+/// any, checking that the module follows the convention.
+pub fn load_time_entry(module: &Module, kind: ModuleKind) -> Result<Option<&'static str>, String> {
+    match kind {
+        ModuleKind::Command => {
+            if !module.exports.contains_key(COMMAND_ENTRY) {
+                return Err(format!("command module doesn't export {}", COMMAND_ENTRY));
+            }
+            if module.exports.contains_key(REACTOR_INITIALIZER) {
+                return Err(format!(
+                    "command module can't export {}",
+                    REACTOR_INITIALIZER
+                ));
+            }
+            Ok(None)
+        }
+        ModuleKind::Reactor => {
+            if module.exports.contains_key(COMMAND_ENTRY) {
+                return Err(format!("reactor module can't export {}", COMMAND_ENTRY));
+            }
+            if module.exports.contains_key(REACTOR_INITIALIZER) {
+                Ok(Some(REACTOR_INITIALIZER))
+            } else {
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_entity::EntityRef;
+    use cranelift_wasm::FuncIndex;
+    use wasmtime_environ::Export;
+
+    fn module(exports: &[&str]) -> Module {
+        let mut module = Module::new();
+        for (i, name) in exports.iter().enumerate() {
+            module
+                .exports
+                .insert(name.to_string(), Export::Function(FuncIndex::new(i)));
+        }
+        module
+    }
+
+    #[test]
+    fn test_detect_module_kind() {
+        assert_eq!(
+            detect_module_kind(&module(&["_start"])),
+            Ok(Some(ModuleKind::Command))
+        );
+        assert_eq!(
+            detect_module_kind(&module(&["_initialize", "run"])),
+            Ok(Some(ModuleKind::Reactor))
+        );
+        assert_eq!(detect_module_kind(&module(&["run"])), Ok(None));
+        assert!(detect_module_kind(&module(&["_start", "_initialize"])).is_err());
+    }
+
+    #[test]
+    fn test_load_time_entry() {
+        let command = module(&["_start"]);
+        assert_eq!(load_time_entry(&command, ModuleKind::Command), Ok(None));
+        assert!(load_time_entry(&command, ModuleKind::Reactor).is_err());
+
+        let reactor = module(&["_initialize"]);
+        assert_eq!(
+            load_time_entry(&reactor, ModuleKind::Reactor),
+            Ok(Some(REACTOR_INITIALIZER))
+        );
+        assert!(load_time_entry(&reactor, ModuleKind::Command).is_err());
+
+        // A reactor needn't have an initializer, but a command needs its
+        // entry point.
+        let library = module(&["run"]);
+        assert_eq!(load_time_entry(&library, ModuleKind::Reactor), Ok(None));
+        assert!(load_time_entry(&library, ModuleKind::Command).is_err());
+    }
+
+    #[test]
+    fn test_parse_module_kind() {
+        assert_eq!("command".parse(), Ok(ModuleKind::Command));
+        assert_eq!("reactor".parse(), Ok(ModuleKind::Reactor));
+        assert!("auto".parse::<ModuleKind>().is_err());
+    }
+}
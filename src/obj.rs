@@ -0,0 +1,489 @@
+//! Translation of a wasm module to a native object file, as done by
+//! `wasm2obj`.
+
+use crate::module_kind::{detect_module_kind, load_time_entry, ModuleKind};
+use cranelift_codegen::ir;
+use cranelift_codegen::isa;
+use cranelift_codegen::settings;
+use cranelift_entity::EntityRef;
+use cranelift_wasm::{DefinedFuncIndex, FuncIndex, WasmError};
+use faerie::Artifact;
+use std::collections::HashMap;
+use std::fmt;
+use target_lexicon::{BinaryFormat, Triple};
+use wasmtime_debug::{emit_debugsections, read_debuginfo};
+use wasmtime_environ::{
+    cranelift, rebase_transforms, validate_imports, Compilation, CompileError, DataInitializer,
+    FrameSizes, HostInterface, Module, ModuleEnvironment, Relocations, Tunables,
+};
+use wasmtime_obj::{
+    check_section_prefix, emit_builtin_profiling, emit_comment, emit_init_array, emit_module,
+    emit_tunables, pad_function_entries, patch_elf_data_sections, patch_elf_export_symbols,
+    patch_elf_init_array, patch_elf_note_sections, patch_elf_section_prefix, patch_elf_visibility,
+    thunk_duplicate_functions, BuildId, EmitOptions, Visibility,
+};
+
+/// A malformed wasm module, with the offset of the byte the problem was
+/// found at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranslateError {
+    /// The offset in the wasm binary.
+    pub offset: usize,
+    /// What is wrong at `offset`.
+    pub message: String,
+}
+
+impl fmt::Display for TranslateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid WebAssembly at offset {}: {}",
+            self.offset, self.message
+        )
+    }
+}
+
+/// An error producing an object with `compile_to_object_with_options`.
+#[derive(Debug)]
+pub enum ObjectError {
+    /// The module is malformed.
+    Translate(TranslateError),
+    /// The module uses an unsupported feature, or the target, compilation or
+    /// emission failed.
+    Other(String),
+}
+
+impl fmt::Display for ObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ObjectError::Translate(ref error) => error.fmt(f),
+            ObjectError::Other(ref message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<WasmError> for ObjectError {
+    fn from(error: WasmError) -> Self {
+        match error {
+            WasmError::InvalidWebAssembly { message, offset } => {
+                ObjectError::Translate(TranslateError {
+                    offset,
+                    message: String::from(message),
+                })
+            }
+            error => ObjectError::Other(error.to_string()),
+        }
+    }
+}
+
+impl From<CompileError> for ObjectError {
+    fn from(error: CompileError) -> Self {
+        // Function bodies are only validated as they are compiled.
+        if let CompileError::InFunction { ref source, .. } = error {
+            if let CompileError::Wasm(WasmError::InvalidWebAssembly { message, offset }) = **source
+            {
+                return ObjectError::Translate(TranslateError {
+                    offset,
+                    message: String::from(message),
+                });
+            }
+        }
+        ObjectError::Other(error.to_string())
+    }
+}
+
+impl From<String> for ObjectError {
+    fn from(message: String) -> Self {
+        ObjectError::Other(message)
+    }
+}
+
+/// Returns the ISA builder for `triple`, or for the host if `triple` is
+/// `None`.
+pub fn isa_builder(triple: Option<&Triple>) -> Result<isa::Builder, String> {
+    match triple {
+        Some(triple) => isa::lookup(triple.clone()).map_err(|err| {
+            String::from(match err {
+                isa::LookupError::SupportDisabled => {
+                    "support for architecture disabled at compile time"
+                }
+                isa::LookupError::Unsupported => "unsupported architecture",
+            })
+        }),
+        None => cranelift_native::builder()
+            .map_err(|_| String::from("host machine is not a supported target")),
+    }
+}
+
+/// Options for `compile_to_object_with_options`. The defaults are those of
+/// `compile_to_object` for the host.
+#[derive(Default)]
+pub struct ObjectOptions<'a> {
+    /// The ISA to compile for; default is the host's, with the default
+    /// settings.
+    pub isa: Option<Box<dyn isa::TargetIsa>>,
+    /// The object file format; default follows the ISA's triple.
+    pub binary_format: Option<BinaryFormat>,
+    /// Emit DWARF debug information.
+    pub generate_debug_info: bool,
+    /// With `generate_debug_info`, describe trap sites with DWARF labels.
+    pub dwarf_traps: bool,
+    /// The memory and table layout the code is compiled for.
+    pub tunables: Tunables,
+    /// The calling convention of the module's functions; default follows
+    /// the ISA.
+    pub call_conv: Option<isa::CallConv>,
+    /// How the functions are compiled.
+    pub compile: cranelift::CompileOptions<'a>,
+    /// Compile only these functions, leaving the others with empty bodies.
+    pub only_functions: Option<Vec<FuncIndex>>,
+    /// Check the module's imports against this interface.
+    pub host_interface: Option<HostInterface>,
+    /// The WASI module kind the module must follow.
+    pub module_kind: Option<ModuleKind>,
+    /// Detect the WASI module kind from the exports, if `module_kind` is
+    /// `None`.
+    pub detect_module_kind: bool,
+    /// Run this exported function at load time through an `.init_array`
+    /// entry.
+    pub init_array: Option<String>,
+    /// Check that every function links its frame into the frame-pointer
+    /// chain.
+    pub frame_pointer_chain: bool,
+    /// With `emit.patchable_entry.size`, the functions given patchable
+    /// entries; default is all defined functions that compiled.
+    pub patchable_functions: Option<Vec<FuncIndex>>,
+    /// Check that the code calls no other libcalls than these.
+    pub libcalls: Option<Vec<ir::LibCall>>,
+    /// What `emit_module` writes. `build_id_settings` is filled in from the
+    /// ISA, `patchable_entry.functions` from `patchable_functions` and
+    /// `writable_vmcontext` is set for `init_array`.
+    pub emit: EmitOptions,
+    /// Emit a `.comment` section recording the compiler version and the ISA
+    /// settings.
+    pub comment_section: bool,
+    /// Emit a `.wasm.tunables` section recording `tunables`, for loaders to
+    /// check against their own.
+    pub tunables_section: bool,
+    /// Give each exported function a global ELF symbol named after the
+    /// export.
+    pub export_symbols: bool,
+    /// ELF visibility of exported functions; other functions are then
+    /// hidden.
+    pub visibility: Option<Visibility>,
+    /// Prefix for the names of the ELF code, data and debug sections.
+    pub section_prefix: Option<String>,
+}
+
+/// A module compiled to an object, with the intermediate results a caller
+/// may want to report on.
+pub struct CompiledObject<'data> {
+    /// The object, before the post-processing `into_image` applies.
+    pub obj: Artifact,
+    /// The ISA the code was compiled for.
+    pub isa: Box<dyn isa::TargetIsa>,
+    /// The translated module.
+    pub module: Module,
+    /// The compiled functions.
+    pub compilation: Compilation,
+    /// The relocations of the compiled functions.
+    pub relocations: Relocations,
+    /// The stack frame size of each function.
+    pub frame_sizes: FrameSizes,
+    /// The module's data initializers.
+    pub data_initializers: Vec<DataInitializer<'data>>,
+    patches: ElfPatches,
+}
+
+/// The post-processing of the emitted ELF image, for what faerie can't
+/// express.
+struct ElfPatches {
+    export_symbols: bool,
+    visibility: Option<Visibility>,
+    build_id: bool,
+    init_array: bool,
+    pointer_bytes: u8,
+    data_sections: HashMap<usize, String>,
+    section_prefix: Option<String>,
+}
+
+impl<'data> CompiledObject<'data> {
+    /// Emits the object and applies the post-processing the options asked
+    /// for, returning the object file's bytes.
+    pub fn into_image(self) -> Result<Vec<u8>, String> {
+        let mut bytes = self.obj.emit().map_err(|e| e.to_string())?;
+        let patches = &self.patches;
+        if patches.export_symbols {
+            patch_elf_export_symbols(&mut bytes, &self.module)?;
+        }
+        if let Some(visibility) = patches.visibility {
+            patch_elf_visibility(&mut bytes, &self.module, visibility)?;
+        }
+        if patches.build_id {
+            patch_elf_note_sections(&mut bytes)?;
+        }
+        if patches.init_array {
+            patch_elf_init_array(&mut bytes, patches.pointer_bytes)?;
+        }
+        if !patches.data_sections.is_empty() {
+            patch_elf_data_sections(&mut bytes, &patches.data_sections)?;
+        }
+        if let Some(ref prefix) = patches.section_prefix {
+            patch_elf_section_prefix(&mut bytes, prefix)?;
+        }
+        Ok(bytes)
+    }
+}
+
+/// Returns the defined functions `functions`, failing for those that are
+/// out of range or imported.
+fn defined_functions(
+    module: &Module,
+    functions: &[FuncIndex],
+) -> Result<Vec<DefinedFuncIndex>, String> {
+    functions
+        .iter()
+        .map(|&func_index| {
+            if func_index.index() >= module.functions.len() {
+                return Err(format!("invalid function index {}", func_index.index()));
+            }
+            module
+                .defined_func_index(func_index)
+                .ok_or_else(|| format!("function {} is imported", func_index.index()))
+        })
+        .collect()
+}
+
+/// Fails if `what` is asked for in an object of another format than ELF.
+fn check_elf(what: &str, binary_format: BinaryFormat) -> Result<(), String> {
+    if binary_format != BinaryFormat::Elf {
+        return Err(format!(
+            "{} is only supported for ELF objects, not {}",
+            what, binary_format
+        ));
+    }
+    Ok(())
+}
+
+/// Translates the wasm module `wasm` and compiles it to an object for
+/// `triple`, or for the host if `triple` is `None`, with DWARF debug
+/// information if `generate_debug_info` is set.
+pub fn compile_to_object(
+    wasm: &[u8],
+    triple: Option<&Triple>,
+    generate_debug_info: bool,
+) -> Result<Artifact, String> {
+    let isa = isa_builder(triple)?.finish(settings::Flags::new(settings::builder()));
+    let options = ObjectOptions {
+        isa: Some(isa),
+        generate_debug_info,
+        ..ObjectOptions::default()
+    };
+    compile_to_object_with_options(wasm, options)
+        .map(|compiled| compiled.obj)
+        .map_err(|e| e.to_string())
+}
+
+/// Like `compile_to_object`, with the `options` described by
+/// `ObjectOptions`. Malformed wasm is reported as a `TranslateError`.
+pub fn compile_to_object_with_options<'data>(
+    wasm: &'data [u8],
+    options: ObjectOptions,
+) -> Result<CompiledObject<'data>, ObjectError> {
+    let ObjectOptions {
+        isa,
+        binary_format,
+        generate_debug_info,
+        dwarf_traps,
+        tunables,
+        call_conv,
+        compile,
+        only_functions,
+        host_interface,
+        module_kind,
+        detect_module_kind: detect,
+        mut init_array,
+        frame_pointer_chain,
+        patchable_functions,
+        libcalls,
+        emit: mut emit_options,
+        comment_section,
+        tunables_section,
+        export_symbols,
+        visibility,
+        section_prefix,
+    } = options;
+
+    let isa = match isa {
+        Some(isa) => isa,
+        None => isa_builder(None)?.finish(settings::Flags::new(settings::builder())),
+    };
+    let mut triple = isa.triple().clone();
+    if let Some(binary_format) = binary_format {
+        triple.binary_format = binary_format;
+    }
+    if export_symbols {
+        check_elf("export symbols", triple.binary_format)?;
+    }
+    if visibility.is_some() {
+        check_elf("visibility", triple.binary_format)?;
+    }
+    if let Some(ref prefix) = section_prefix {
+        check_elf("a section prefix", triple.binary_format)?;
+        check_section_prefix(prefix)?;
+    }
+    if isa.flags().is_pic() {
+        check_elf("position-independent code", triple.binary_format)?;
+    }
+
+    let mut frontend_config = isa.frontend_config();
+    if let Some(call_conv) = call_conv {
+        frontend_config.default_call_conv = call_conv;
+    }
+    let translation = ModuleEnvironment::new(frontend_config, tunables.clone()).translate(wasm)?;
+    let module = translation.module;
+
+    if let Some(ref interface) = host_interface {
+        validate_imports(&module, interface).map_err(|e| e.to_string())?;
+    }
+
+    let module_kind = match module_kind {
+        Some(kind) => Some(kind),
+        None if detect => detect_module_kind(&module)?,
+        None => None,
+    };
+    if let Some(kind) = module_kind {
+        if let Some(entry) = load_time_entry(&module, kind)? {
+            if init_array.is_some() {
+                return Err(ObjectError::Other(format!(
+                    "an .init_array entry can't be combined with a reactor's {}",
+                    entry
+                )));
+            }
+            init_array = Some(String::from(entry));
+        }
+    }
+
+    if frame_pointer_chain {
+        cranelift::check_frame_pointer_chain(&module, &*isa)?;
+    }
+
+    let (mut compilation, mut relocations, mut address_transform, mut frame_sizes, mut traps) =
+        match only_functions {
+            Some(ref only) => {
+                let only = defined_functions(&module, only)?;
+                cranelift::compile_functions(
+                    &module,
+                    translation.function_body_inputs,
+                    &*isa,
+                    generate_debug_info,
+                    &compile,
+                    &only,
+                )?
+            }
+            None => cranelift::compile_module_with_options(
+                &module,
+                translation.function_body_inputs,
+                &*isa,
+                generate_debug_info,
+                &compile,
+            )?,
+        };
+
+    thunk_duplicate_functions(
+        &module,
+        &mut compilation,
+        &mut relocations,
+        &mut traps,
+        &mut frame_sizes,
+        &mut address_transform,
+        &*isa,
+    );
+
+    if emit_options.patchable_entry.size > 0 {
+        emit_options.patchable_entry.functions = match patchable_functions {
+            Some(ref functions) => defined_functions(&module, functions)?,
+            None => compilation
+                .functions
+                .keys()
+                .filter(|&i| !compilation.failed.iter().any(|failed| failed.index == i))
+                .collect(),
+        };
+        let layout = pad_function_entries(
+            &mut compilation,
+            &mut relocations,
+            &mut traps,
+            &*isa,
+            &emit_options.patchable_entry,
+        )?;
+        if generate_debug_info {
+            address_transform = rebase_transforms(address_transform, &layout);
+        }
+    }
+
+    if isa.flags().is_pic() {
+        cranelift::check_position_independent(&module, &relocations)?;
+    }
+    if let Some(ref available) = libcalls {
+        cranelift::check_libcalls(&module, &relocations, available)?;
+    }
+
+    let mut obj = Artifact::new(triple, String::from("wasm.o"));
+    emit_options.writable_vmcontext |= init_array.is_some();
+    emit_options.build_id_settings = format!("{}\n{}", isa.triple(), isa);
+    if emit_options.profile_builtins {
+        emit_builtin_profiling(&mut obj, &*isa, &relocations)?;
+    }
+    emit_module(
+        &mut obj,
+        &module,
+        &compilation,
+        &relocations,
+        &translation.data_initializers,
+        &translation.target_config,
+        &emit_options,
+    )?;
+    if comment_section {
+        emit_comment(&mut obj, &*isa)?;
+    }
+    if tunables_section {
+        emit_tunables(&mut obj, &tunables)?;
+    }
+
+    if let Some(ref export) = init_array {
+        emit_init_array(&mut obj, &module, &*isa, export)?;
+    }
+
+    if generate_debug_info {
+        let debug_data = read_debuginfo(wasm);
+        let traps = if dwarf_traps { Some(&traps) } else { None };
+        emit_debugsections(
+            &mut obj,
+            &translation.target_config,
+            &debug_data,
+            &address_transform,
+            traps,
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    let patches = ElfPatches {
+        export_symbols,
+        visibility,
+        build_id: emit_options.build_id != BuildId::None,
+        init_array: init_array.is_some(),
+        pointer_bytes: isa.pointer_bytes(),
+        data_sections: emit_options.data_sections,
+        section_prefix,
+    };
+    Ok(CompiledObject {
+        obj,
+        isa,
+        module,
+        compilation,
+        relocations,
+        frame_sizes,
+        data_initializers: translation.data_initializers,
+        patches,
+    })
+}
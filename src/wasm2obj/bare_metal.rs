@@ -0,0 +1,211 @@
+//! Manifest of what a bare-metal environment must provide to run the object.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "libcalls": [ { "name": "FloorF32", "used_by": [3, 5] } ],
+//!   "builtins": [ { "name": "memory32_grow", "used_by": [4] } ],
+//!   "imports": [ { "module": "env", "field": "putc", "kind": "function" } ]
+//! }
+//! ```
+//!
+//! `libcalls` are the Cranelift runtime library functions the code calls,
+//! such as float rounding on targets without the instructions for it.
+//! `builtins` are the wasmtime runtime functions the code calls through the
+//! vmctx, `signature_table` with `--relocatable-signature-table`, and
+//! `memoryN_base` for memories located by relocation. `imports` are the
+//! module's own imports. `used_by` lists function indices in the wasm
+//! function index space.
+//!
+//! In bare-metal mode the stack probe is disabled, so `Probestack` never
+//! appears among the libcalls.
+
+use cranelift_entity::EntityRef;
+use std::collections::BTreeMap;
+use std::io::Write;
+use wasmtime_environ::{Module, RelocationTarget, Relocations};
+
+/// Version of the JSON document layout.
+pub const BARE_METAL_MANIFEST_VERSION: u32 = 1;
+
+/// Cranelift settings that keep the generated code from depending on an
+/// operating system.
+pub const BARE_METAL_SETTINGS: &[(&str, &str)] = &[("probestack_enabled", "false")];
+
+#[derive(Serialize)]
+struct Manifest {
+    version: u32,
+    libcalls: Vec<Dependency>,
+    builtins: Vec<Dependency>,
+    imports: Vec<Import>,
+}
+
+#[derive(Serialize)]
+struct Dependency {
+    name: String,
+    used_by: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Import {
+    module: String,
+    field: String,
+    kind: &'static str,
+}
+
+fn add_use(deps: &mut BTreeMap<String, Vec<usize>>, name: String, func_index: usize) {
+    let users = deps.entry(name).or_default();
+    if users.last() != Some(&func_index) {
+        users.push(func_index);
+    }
+}
+
+fn dependencies(deps: BTreeMap<String, Vec<usize>>) -> Vec<Dependency> {
+    deps.into_iter()
+        .map(|(name, used_by)| Dependency { name, used_by })
+        .collect()
+}
+
+fn push_imports<'a, I>(imports: &mut Vec<Import>, names: I, kind: &'static str)
+where
+    I: Iterator<Item = &'a (String, String)>,
+{
+    for (module, field) in names {
+        imports.push(Import {
+            module: module.clone(),
+            field: field.clone(),
+            kind,
+        });
+    }
+}
+
+/// Writes the bare-metal dependency manifest for `module` to `out`.
+pub fn write_manifest<W: Write>(
+    out: W,
+    module: &Module,
+    relocations: &Relocations,
+) -> Result<(), String> {
+    let mut libcalls = BTreeMap::new();
+    let mut builtins = BTreeMap::new();
+    for (i, function_relocs) in relocations.iter() {
+        let func_index = module.func_index(i).index();
+        for r in function_relocs {
+            match r.reloc_target {
+                RelocationTarget::UserFunc(_)
+                | RelocationTarget::Ebb(_)
+                | RelocationTarget::JumpTable(_) => {}
+                RelocationTarget::LibCall(libcall) => {
+                    add_use(&mut libcalls, libcall.to_string(), func_index)
+                }
+                RelocationTarget::Memory32Grow => {
+                    add_use(&mut builtins, String::from("memory32_grow"), func_index)
+                }
+                RelocationTarget::ImportedMemory32Grow => add_use(
+                    &mut builtins,
+                    String::from("imported_memory32_grow"),
+                    func_index,
+                ),
+                RelocationTarget::Memory32Size => {
+                    add_use(&mut builtins, String::from("memory32_size"), func_index)
+                }
+                RelocationTarget::ImportedMemory32Size => add_use(
+                    &mut builtins,
+                    String::from("imported_memory32_size"),
+                    func_index,
+                ),
+                RelocationTarget::SignatureTableBase => {
+                    add_use(&mut builtins, String::from("signature_table"), func_index)
+                }
+                RelocationTarget::MemoryBase { memory_index } => add_use(
+                    &mut builtins,
+                    format!("memory{}_base", memory_index.index()),
+                    func_index,
+                ),
+            }
+        }
+    }
+
+    let mut imports = Vec::new();
+    push_imports(&mut imports, module.imported_funcs.values(), "function");
+    push_imports(&mut imports, module.imported_tables.values(), "table");
+    push_imports(&mut imports, module.imported_memories.values(), "memory");
+    push_imports(&mut imports, module.imported_globals.values(), "global");
+
+    let manifest = Manifest {
+        version: BARE_METAL_MANIFEST_VERSION,
+        libcalls: dependencies(libcalls),
+        builtins: dependencies(builtins),
+        imports,
+    };
+    serde_json::to_writer_pretty(out, &manifest).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::isa;
+    use cranelift_codegen::settings::{self, Configurable};
+    use std::str::FromStr;
+    use target_lexicon::Triple;
+    use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+
+    #[test]
+    fn test_write_manifest() {
+        let data = wabt::wat2wasm(
+            r#"
+            (module
+              (import "env" "putc" (func $putc (param i32)))
+              (import "env" "base" (global i32))
+              (memory 1)
+              (func (param f32) (result f32)
+                (f32.floor (get_local 0))
+              )
+              (func (result i32)
+                (call $putc (i32.const 33))
+                (memory.grow (i32.const 1))
+              )
+            )
+            "#,
+        )
+        .unwrap();
+        // Without SSE 4.1, x86-64 rounds floats through libcalls.
+        let mut flag_builder = settings::builder();
+        for (name, value) in BARE_METAL_SETTINGS {
+            flag_builder.set(name, value).unwrap();
+        }
+        let triple = Triple::from_str("x86_64-unknown-linux-gnu").unwrap();
+        let isa = isa::lookup(triple)
+            .unwrap()
+            .finish(settings::Flags::new(flag_builder));
+        let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+            .translate(&data)
+            .unwrap();
+        let (_, relocations, _, _, _) = cranelift::compile_module(
+            &translation.module,
+            translation.function_body_inputs,
+            &*isa,
+            false,
+        )
+        .unwrap();
+
+        let mut out = Vec::new();
+        write_manifest(&mut out, &translation.module, &relocations).unwrap();
+        let manifest: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(manifest["version"], BARE_METAL_MANIFEST_VERSION);
+        assert_eq!(
+            manifest["libcalls"],
+            serde_json::json!([{ "name": "FloorF32", "used_by": [1] }])
+        );
+        assert_eq!(
+            manifest["builtins"],
+            serde_json::json!([{ "name": "memory32_grow", "used_by": [2] }])
+        );
+        assert_eq!(
+            manifest["imports"],
+            serde_json::json!([
+                { "module": "env", "field": "putc", "kind": "function" },
+                { "module": "env", "field": "base", "kind": "global" }
+            ])
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Selection of the calling convention of the module's functions, for
+//! objects embedded in hosts with a non-default ABI.
+//!
+//! Only the module's own functions change convention: the builtins they
+//! call and the `.init_array` trampoline keep the target's default, as
+//! they are called from or implemented by the runtime.
+
+use cranelift_codegen::isa::CallConv;
+use std::str::FromStr;
+use target_lexicon::{Architecture, Triple};
+
+/// Parses the calling convention `name`, checking that Cranelift supports
+/// it for `triple`.
+pub fn parse_call_conv(name: &str, triple: &Triple) -> Result<CallConv, String> {
+    let call_conv = match name {
+        "system_v" | "windows_fastcall" | "fast" | "cold" => CallConv::from_str(name)
+            .map_err(|_| format!("unknown calling convention '{}'", name))?,
+        _ => {
+            return Err(format!(
+                "unknown calling convention '{}'; expected 'system_v', 'windows_fastcall', \
+                 'fast' or 'cold'",
+                name
+            ))
+        }
+    };
+    if call_conv == CallConv::WindowsFastcall && triple.architecture != Architecture::X86_64 {
+        return Err(format!(
+            "the windows_fastcall calling convention is not supported for {}",
+            triple
+        ));
+    }
+    Ok(call_conv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::{isa, settings};
+    use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+
+    #[test]
+    fn test_parse_call_conv() {
+        let x86_64 = Triple::from_str("x86_64-unknown-linux-gnu").unwrap();
+        let i686 = Triple::from_str("i686-unknown-linux-gnu").unwrap();
+        assert_eq!(parse_call_conv("fast", &x86_64), Ok(CallConv::Fast));
+        assert_eq!(
+            parse_call_conv("windows_fastcall", &x86_64),
+            Ok(CallConv::WindowsFastcall)
+        );
+        assert!(parse_call_conv("windows_fastcall", &i686).is_err());
+        assert!(parse_call_conv("baldrdash", &x86_64).is_err());
+    }
+
+    #[test]
+    fn test_fast_call_conv() {
+        let data = wabt::wat2wasm(
+            r#"
+            (module
+              (func (param i32) (result i32)
+                (i32.add (get_local 0) (i32.const 1))
+              )
+            )
+            "#,
+        )
+        .unwrap();
+        let triple = Triple::from_str("x86_64-unknown-linux-gnu").unwrap();
+        let isa_builder = isa::lookup(triple.clone()).unwrap();
+        let isa = isa_builder.finish(settings::Flags::new(settings::builder()));
+
+        let mut frontend_config = isa.frontend_config();
+        frontend_config.default_call_conv = parse_call_conv("fast", &triple).unwrap();
+        let translation = ModuleEnvironment::new(frontend_config, Tunables::default())
+            .translate(&data)
+            .unwrap();
+        let (compilation, _, _, _, _) = cranelift::compile_module_with_options(
+            &translation.module,
+            translation.function_body_inputs,
+            &*isa,
+            false,
+            &cranelift::CompileOptions {
+                capture_ir: true,
+                ..cranelift::CompileOptions::default()
+            },
+        )
+        .unwrap();
+
+        for signature in translation.module.signatures.values() {
+            assert_eq!(signature.call_conv, CallConv::Fast);
+        }
+        let ir = compilation.ir.unwrap();
+        let function = ir.values().next().unwrap();
+        assert!(function.lines().next().unwrap().ends_with(" fast {"));
+    }
+}
@@ -0,0 +1,128 @@
+//! JSON index of the direct call sites targeting each function.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "functions": [
+//!     {
+//!       "func_index": 2,
+//!       "call_sites": [
+//!         { "caller_func_index": 3, "caller_defined_index": 1, "offset": 17 }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Every function of the module is listed, imports included. `offset` is the
+//! offset of the call's relocation within the caller's body. Calls made
+//! through `call_indirect` or to imported functions don't appear.
+
+use cranelift_entity::EntityRef;
+use std::io::Write;
+use wasmtime_environ::{call_sites, Module, Relocations};
+
+/// Version of the JSON document layout.
+pub const CALLERS_JSON_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct CallersDocument {
+    version: u32,
+    functions: Vec<FunctionCallers>,
+}
+
+#[derive(Serialize)]
+struct FunctionCallers {
+    func_index: usize,
+    call_sites: Vec<CallSite>,
+}
+
+#[derive(Serialize)]
+struct CallSite {
+    caller_func_index: usize,
+    caller_defined_index: usize,
+    offset: u32,
+}
+
+/// Writes the call-site index for `module` to `out`.
+pub fn write_callers<W: Write>(
+    out: W,
+    module: &Module,
+    relocations: &Relocations,
+) -> Result<(), String> {
+    let functions = call_sites(module, relocations)
+        .iter()
+        .map(|(func_index, sites)| FunctionCallers {
+            func_index: func_index.index(),
+            call_sites: sites
+                .iter()
+                .map(|&(caller, offset)| CallSite {
+                    caller_func_index: module.func_index(caller).index(),
+                    caller_defined_index: caller.index(),
+                    offset,
+                })
+                .collect(),
+        })
+        .collect();
+
+    let document = CallersDocument {
+        version: CALLERS_JSON_VERSION,
+        functions,
+    };
+    serde_json::to_writer_pretty(out, &document).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::settings;
+    use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+
+    #[test]
+    fn test_write_callers() {
+        let data = wabt::wat2wasm(
+            r#"
+            (module
+              (import "env" "log" (func $log (param i32)))
+              (func (param i32) (result i32)
+                (call $log (get_local 0))
+                (call $double (get_local 0))
+              )
+              (func $double (param i32) (result i32)
+                (i32.add (get_local 0) (get_local 0))
+              )
+            )
+            "#,
+        )
+        .unwrap();
+        let isa_builder = cranelift_native::builder().unwrap();
+        let isa = isa_builder.finish(settings::Flags::new(settings::builder()));
+        let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+            .translate(&data)
+            .unwrap();
+        let (_, relocations, _, _, _) = cranelift::compile_module(
+            &translation.module,
+            translation.function_body_inputs,
+            &*isa,
+            false,
+        )
+        .unwrap();
+
+        let mut out = Vec::new();
+        write_callers(&mut out, &translation.module, &relocations).unwrap();
+        let document: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(document["version"], CALLERS_JSON_VERSION);
+
+        // Every function is listed, but the import is called through the
+        // vmctx and so has no call sites.
+        let functions = document["functions"].as_array().unwrap();
+        assert_eq!(functions.len(), 3);
+        assert_eq!(functions[0]["call_sites"].as_array().unwrap().len(), 0);
+        assert_eq!(functions[1]["call_sites"].as_array().unwrap().len(), 0);
+        let sites = functions[2]["call_sites"].as_array().unwrap();
+        assert_eq!(functions[2]["func_index"], 2);
+        assert_eq!(sites.len(), 1);
+        assert_eq!(sites[0]["caller_func_index"], 1);
+        assert_eq!(sites[0]["caller_defined_index"], 0);
+    }
+}
@@ -0,0 +1,59 @@
+//! Makefile-style dependency fragments, as written by `cc -MMD`.
+
+use std::io::Write;
+
+/// Escapes a path for use as a make target or prerequisite.
+fn escape(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            ' ' | '#' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '$' => escaped.push_str("$$"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes a rule stating that `target` depends on `prerequisites`.
+pub fn write_deps<W: Write>(
+    mut out: W,
+    target: &str,
+    prerequisites: &[&str],
+) -> Result<(), String> {
+    let mut rule = format!("{}:", escape(target));
+    for prerequisite in prerequisites {
+        rule.push_str(" \\\n  ");
+        rule.push_str(&escape(prerequisite));
+    }
+    rule.push('\n');
+    out.write_all(rule.as_bytes()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_deps() {
+        let mut out = Vec::new();
+        write_deps(&mut out, "out/module.o", &["module.wasm", "lib.wasm"]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "out/module.o: \\\n  module.wasm \\\n  lib.wasm\n"
+        );
+    }
+
+    #[test]
+    fn test_write_deps_escaped() {
+        let mut out = Vec::new();
+        write_deps(&mut out, "my module.o", &["#1.wasm", "$HOME.wasm"]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "my\\ module.o: \\\n  \\#1.wasm \\\n  $$HOME.wasm\n"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! A readable table of the relocations produced by `compile_module`, for
+//! `--dump-relocations`.
+
+use cranelift_entity::EntityRef;
+use std::fmt::Write;
+use wasmtime_environ::{Module, Relocations};
+
+/// Describes the relocations of each defined function of `module`, one per
+/// line, giving the offset within the function body, the relocation kind,
+/// the target and the addend.
+pub fn dump_relocations(module: &Module, relocations: &Relocations) -> String {
+    let mut out = String::new();
+    for (i, relocs) in relocations.iter() {
+        writeln!(out, "function {}:", module.func_index(i).index()).unwrap();
+        if relocs.is_empty() {
+            writeln!(out, "  no relocations").unwrap();
+            continue;
+        }
+        writeln!(
+            out,
+            "  {:>8}  {:<16}  {:<32}  {:>6}",
+            "offset", "kind", "target", "addend"
+        )
+        .unwrap();
+        for r in relocs {
+            writeln!(
+                out,
+                "  {:>8}  {:<16}  {:<32}  {:>6}",
+                format!("{:#x}", r.offset),
+                r.reloc.to_string(),
+                r.reloc_target.to_string(),
+                r.addend
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::settings;
+    use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+
+    #[test]
+    fn test_dump_relocations() {
+        let data = wabt::wat2wasm(
+            r#"
+            (module
+              (import "env" "log" (func $log (param i32)))
+              (func (param i32)
+                (call $log (get_local 0))
+                (call $double (get_local 0))
+                (drop)
+              )
+              (func $double (param i32) (result i32)
+                (i32.add (get_local 0) (get_local 0))
+              )
+            )
+            "#,
+        )
+        .unwrap();
+        let isa_builder = cranelift_native::builder().unwrap();
+        let isa = isa_builder.finish(settings::Flags::new(settings::builder()));
+        let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+            .translate(&data)
+            .unwrap();
+        let (_, relocations, _, _, _) = cranelift::compile_module(
+            &translation.module,
+            translation.function_body_inputs,
+            &*isa,
+            false,
+        )
+        .unwrap();
+
+        // Imported functions are called through the vmctx, so only the call
+        // to the locally-defined function is relocated.
+        let dump = dump_relocations(&translation.module, &relocations);
+        let lines = dump.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "function 1:");
+        assert!(lines[1].contains("offset") && lines[1].contains("addend"));
+        assert!(lines[2].contains("function 2"));
+        assert_eq!(lines[3], "function 2:");
+        assert_eq!(lines[4], "  no relocations");
+    }
+}
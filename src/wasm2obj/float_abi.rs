@@ -0,0 +1,136 @@
+//! Selection of the floating-point ABI for the target.
+//!
+//! Cranelift has no separate setting for the float ABI; on ARM it follows
+//! the environment component of the target triple (`gnueabi` vs.
+//! `gnueabihf` and so on), so selecting an ABI rewrites the triple before
+//! the ISA is looked up. Other architectures only have a hard-float ABI.
+
+use std::fmt;
+use std::str::FromStr;
+use target_lexicon::{Environment, Triple};
+
+/// A floating-point calling convention.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatAbi {
+    /// Floating-point values are passed in integer registers.
+    Soft,
+    /// Floating-point values are passed in floating-point registers.
+    Hard,
+}
+
+impl FromStr for FloatAbi {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "soft" => Ok(FloatAbi::Soft),
+            "hard" => Ok(FloatAbi::Hard),
+            _ => Err(format!(
+                "unknown float ABI '{}'; expected 'soft' or 'hard'",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FloatAbi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FloatAbi::Soft => write!(f, "soft"),
+            FloatAbi::Hard => write!(f, "hard"),
+        }
+    }
+}
+
+fn is_arm(triple: &Triple) -> bool {
+    let arch = triple.architecture.to_string();
+    arch.starts_with("arm") || arch.starts_with("thumb")
+}
+
+/// Returns the float ABI implied by `triple`.
+pub fn float_abi_of(triple: &Triple) -> FloatAbi {
+    if !is_arm(triple) {
+        return FloatAbi::Hard;
+    }
+    match triple.environment {
+        Environment::Eabihf | Environment::Gnueabihf | Environment::Musleabihf => FloatAbi::Hard,
+        _ => FloatAbi::Soft,
+    }
+}
+
+/// Rewrites `triple` to use the requested float ABI, or reports why the
+/// target can't use it.
+pub fn apply_float_abi(triple: &mut Triple, abi: FloatAbi) -> Result<(), String> {
+    if float_abi_of(triple) == abi {
+        return Ok(());
+    }
+    if !is_arm(triple) {
+        return Err(format!(
+            "the {} float ABI is not supported for target {}",
+            abi, triple
+        ));
+    }
+    triple.environment = match (triple.environment, abi) {
+        (Environment::Eabi, FloatAbi::Hard) => Environment::Eabihf,
+        (Environment::Gnueabi, FloatAbi::Hard) => Environment::Gnueabihf,
+        (Environment::Musleabi, FloatAbi::Hard) => Environment::Musleabihf,
+        (Environment::Eabihf, FloatAbi::Soft) => Environment::Eabi,
+        (Environment::Gnueabihf, FloatAbi::Soft) => Environment::Gnueabi,
+        (Environment::Musleabihf, FloatAbi::Soft) => Environment::Musleabi,
+        (environment, _) => {
+            return Err(format!(
+                "the {} float ABI is not supported for the {} environment",
+                abi, environment
+            ));
+        }
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triple(s: &str) -> Triple {
+        Triple::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_float_abi() {
+        assert_eq!("soft".parse(), Ok(FloatAbi::Soft));
+        assert_eq!("hard".parse(), Ok(FloatAbi::Hard));
+        assert!("softfp".parse::<FloatAbi>().is_err());
+        assert_eq!(FloatAbi::Soft.to_string(), "soft");
+    }
+
+    #[test]
+    fn test_float_abi_of() {
+        assert_eq!(
+            float_abi_of(&triple("x86_64-unknown-linux-gnu")),
+            FloatAbi::Hard
+        );
+        assert_eq!(
+            float_abi_of(&triple("armv7-unknown-linux-gnueabihf")),
+            FloatAbi::Hard
+        );
+        assert_eq!(
+            float_abi_of(&triple("armv7-unknown-linux-gnueabi")),
+            FloatAbi::Soft
+        );
+    }
+
+    #[test]
+    fn test_apply_float_abi() {
+        let mut arm = triple("armv7-unknown-linux-gnueabihf");
+        apply_float_abi(&mut arm, FloatAbi::Soft).unwrap();
+        assert_eq!(arm, triple("armv7-unknown-linux-gnueabi"));
+        apply_float_abi(&mut arm, FloatAbi::Hard).unwrap();
+        assert_eq!(arm, triple("armv7-unknown-linux-gnueabihf"));
+
+        // Selecting the ABI a target already has is always fine.
+        let mut x86_64 = triple("x86_64-unknown-linux-gnu");
+        apply_float_abi(&mut x86_64, FloatAbi::Hard).unwrap();
+        assert!(apply_float_abi(&mut x86_64, FloatAbi::Soft).is_err());
+        assert_eq!(x86_64, triple("x86_64-unknown-linux-gnu"));
+    }
+}
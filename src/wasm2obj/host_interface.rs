@@ -0,0 +1,154 @@
+//! Loading a host interface description from JSON.
+//!
+//! ```json
+//! {
+//!   "functions": [
+//!     { "module": "env", "field": "putc", "params": ["i32"], "results": [] }
+//!   ],
+//!   "globals": [
+//!     { "module": "env", "field": "stack_top", "type": "i32", "mutable": false }
+//!   ],
+//!   "memories": [ { "module": "env", "field": "memory" } ],
+//!   "tables": [ { "module": "env", "field": "table" } ]
+//! }
+//! ```
+//!
+//! Value types are `i32`, `i64`, `f32` or `f64`. Every section is optional.
+
+use cranelift_codegen::ir;
+use std::io::Read;
+use wasmtime_environ::{HostFunction, HostGlobal, HostInterface};
+
+#[derive(Deserialize)]
+struct InterfaceDocument {
+    #[serde(default)]
+    functions: Vec<FunctionEntry>,
+    #[serde(default)]
+    globals: Vec<GlobalEntry>,
+    #[serde(default)]
+    memories: Vec<NameEntry>,
+    #[serde(default)]
+    tables: Vec<NameEntry>,
+}
+
+#[derive(Deserialize)]
+struct FunctionEntry {
+    module: String,
+    field: String,
+    #[serde(default)]
+    params: Vec<String>,
+    #[serde(default)]
+    results: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct GlobalEntry {
+    module: String,
+    field: String,
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(default)]
+    mutable: bool,
+}
+
+#[derive(Deserialize)]
+struct NameEntry {
+    module: String,
+    field: String,
+}
+
+fn value_type(name: &str) -> Result<ir::Type, String> {
+    match name {
+        "i32" => Ok(ir::types::I32),
+        "i64" => Ok(ir::types::I64),
+        "f32" => Ok(ir::types::F32),
+        "f64" => Ok(ir::types::F64),
+        _ => Err(format!("unknown value type '{}' in host interface", name)),
+    }
+}
+
+fn value_types(names: &[String]) -> Result<Vec<ir::Type>, String> {
+    names.iter().map(|name| value_type(name)).collect()
+}
+
+/// Reads a host interface description from `input`.
+pub fn read_host_interface<R: Read>(input: R) -> Result<HostInterface, String> {
+    let document: InterfaceDocument = serde_json::from_reader(input).map_err(|e| e.to_string())?;
+    let functions = document
+        .functions
+        .into_iter()
+        .map(|f| {
+            Ok(HostFunction {
+                params: value_types(&f.params)?,
+                returns: value_types(&f.results)?,
+                module: f.module,
+                field: f.field,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let globals = document
+        .globals
+        .into_iter()
+        .map(|g| {
+            Ok(HostGlobal {
+                ty: value_type(&g.ty)?,
+                mutable: g.mutable,
+                module: g.module,
+                field: g.field,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let names = |entries: Vec<NameEntry>| {
+        entries
+            .into_iter()
+            .map(|e| (e.module, e.field))
+            .collect::<Vec<_>>()
+    };
+    Ok(HostInterface {
+        functions,
+        globals,
+        memories: names(document.memories),
+        tables: names(document.tables),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_host_interface() {
+        let interface = read_host_interface(
+            r#"{
+                "functions": [
+                    { "module": "env", "field": "putc", "params": ["i32"] }
+                ],
+                "globals": [
+                    { "module": "env", "field": "stack_top", "type": "i64", "mutable": true }
+                ],
+                "memories": [ { "module": "env", "field": "memory" } ]
+            }"#
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(interface.functions.len(), 1);
+        assert_eq!(interface.functions[0].field, "putc");
+        assert_eq!(interface.functions[0].params, [ir::types::I32]);
+        assert!(interface.functions[0].returns.is_empty());
+        assert_eq!(interface.globals[0].ty, ir::types::I64);
+        assert!(interface.globals[0].mutable);
+        assert_eq!(
+            interface.memories,
+            [(String::from("env"), String::from("memory"))]
+        );
+        assert!(interface.tables.is_empty());
+    }
+
+    #[test]
+    fn test_read_host_interface_unknown_type() {
+        let result = read_host_interface(
+            r#"{ "globals": [ { "module": "env", "field": "g", "type": "v128" } ] }"#.as_bytes(),
+        );
+        assert!(result.unwrap_err().contains("v128"));
+    }
+}
@@ -0,0 +1,360 @@
+//! Self-contained container of a compiled module, for a runtime to load
+//! in-process without a system linker.
+//!
+//! All integers are little-endian. The file starts with a header of `u32`s
+//! giving where each part is, as a file offset and a length in bytes or
+//! entries:
+//!
+//! ```text
+//! magic             b"WJIT"
+//! version           JIT_BUNDLE_VERSION
+//! triple            offset, length: UTF-8 target triple
+//! strings           offset, length: NUL-terminated symbol names
+//! functions         offset, count: one entry per defined function
+//! relocations       offset, count
+//! data              offset, length: the data initializer table
+//! code              offset, length: machine code of all functions
+//! ```
+//!
+//! Each function entry is three `u32`s, `func_index` in the wasm function
+//! index space, then the `offset` and `length` of its code within `code`,
+//! in `DefinedFuncIndex` order. Functions start 16-byte aligned.
+//!
+//! Each relocation entry is 32 bytes:
+//!
+//! ```text
+//! defined_index     u32, the function being relocated
+//! offset            u32, relative to the start of the function
+//! kind              u32, RELOC_KINDS index
+//! target_type       u32, TARGET_USER_FUNC, TARGET_SYMBOL or TARGET_EBB
+//! target            u32, a func_index, an offset into strings, or an
+//!                   offset within the relocated function's code, such as
+//!                   an EBB header or a jump table
+//! reserved          u32, zero
+//! addend            i64
+//! ```
+//!
+//! Symbol targets are the runtime functions `wasm2obj` objects import, such
+//! as `wasmtime_memory32_grow`, `_signature_table`, and the bases of memories
+//! located by relocation, such as `_memory_base_0`.
+//!
+//! The data initializer table has the layout of `--defer-data-init`, with
+//! `count` then its entries. `code` is last and page-aligned, so a loader
+//! can map it copy-on-write, apply the relocations, and make it executable.
+//!
+//! Unwind information isn't included, since Cranelift doesn't generate it
+//! yet; nor is debug information. Functions left to the interpreter can't be
+//! bundled.
+
+use cranelift_codegen::binemit::Reloc;
+use cranelift_entity::EntityRef;
+use std::io::Write;
+use std::mem;
+use wasmtime_environ::{Compilation, DataInitializer, Module, RelocationTarget, Relocations};
+use wasmtime_obj::{
+    encode_data_initializers, memory_base_symbol, runtime_symbol, SIGNATURE_TABLE_SYMBOL,
+};
+
+/// Version of the bundle layout.
+pub const JIT_BUNDLE_VERSION: u32 = 1;
+
+/// Relocation kinds, in the order of their `kind` codes.
+pub const RELOC_KINDS: &[Reloc] = &[
+    Reloc::Abs4,
+    Reloc::Abs8,
+    Reloc::X86PCRel4,
+    Reloc::X86CallPCRel4,
+    Reloc::X86CallPLTRel4,
+    Reloc::X86GOTPCRel4,
+    Reloc::Arm32Call,
+    Reloc::Arm64Call,
+    Reloc::RiscvCall,
+];
+
+/// `target_type` of relocations against a wasm function.
+pub const TARGET_USER_FUNC: u32 = 0;
+
+/// `target_type` of relocations against a symbol the runtime provides.
+pub const TARGET_SYMBOL: u32 = 1;
+
+/// `target_type` of relocations against the relocated function's own code.
+pub const TARGET_EBB: u32 = 2;
+
+const MAGIC: &[u8; 4] = b"WJIT";
+const HEADER_BYTES: usize = 8 + 6 * 8;
+const FUNCTION_ALIGN: usize = 16;
+const PAGE_SIZE: usize = 4096;
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn align(out: &mut Vec<u8>, alignment: usize) {
+    while out.len() % alignment != 0 {
+        out.push(0);
+    }
+}
+
+/// Interns `symbol` in the NUL-terminated string table `strings`.
+fn string_offset(strings: &mut Vec<u8>, symbol: &str) -> u32 {
+    let mut start = 0;
+    for name in strings.split(|&b| b == 0) {
+        if name == symbol.as_bytes() {
+            return start as u32;
+        }
+        start += name.len() + 1;
+    }
+    let offset = strings.len() as u32;
+    strings.extend_from_slice(symbol.as_bytes());
+    strings.push(0);
+    offset
+}
+
+/// Encodes the bundle of a compiled module for `triple`.
+pub fn encode_jit_bundle(
+    triple: &str,
+    module: &Module,
+    compilation: &Compilation,
+    relocations: &Relocations,
+    data_initializers: &[DataInitializer],
+) -> Result<Vec<u8>, String> {
+    if let Some(failed) = compilation.failed.first() {
+        return Err(format!(
+            "function {} failed to compile and can't be bundled",
+            module.func_index(failed.index).index()
+        ));
+    }
+
+    let mut code = Vec::new();
+    let mut functions = Vec::new();
+    for (i, body) in compilation.functions.iter() {
+        align(&mut code, FUNCTION_ALIGN);
+        push_u32(&mut functions, module.func_index(i).index() as u32);
+        push_u32(&mut functions, code.len() as u32);
+        push_u32(&mut functions, body.len() as u32);
+        code.extend_from_slice(body);
+    }
+
+    let mut strings = Vec::new();
+    let mut relocs = Vec::new();
+    let mut reloc_count = 0;
+    for (i, function_relocs) in relocations.iter() {
+        for r in function_relocs {
+            let kind = RELOC_KINDS
+                .iter()
+                .position(|kind| mem::discriminant(kind) == mem::discriminant(&r.reloc))
+                .expect("unknown relocation kind");
+            let (target_type, target) = match r.reloc_target {
+                RelocationTarget::UserFunc(index) => (TARGET_USER_FUNC, index.index() as u32),
+                RelocationTarget::Ebb(offset) => (TARGET_EBB, offset),
+                RelocationTarget::JumpTable(jt) => (TARGET_EBB, compilation.jt_offsets[i][jt]),
+                RelocationTarget::SignatureTableBase => (
+                    TARGET_SYMBOL,
+                    string_offset(&mut strings, SIGNATURE_TABLE_SYMBOL),
+                ),
+                RelocationTarget::MemoryBase { memory_index } => (
+                    TARGET_SYMBOL,
+                    string_offset(&mut strings, &memory_base_symbol(memory_index)),
+                ),
+                target => {
+                    let symbol = runtime_symbol(target)
+                        .ok_or_else(|| format!("no runtime symbol for {:?}", target))?;
+                    (TARGET_SYMBOL, string_offset(&mut strings, symbol))
+                }
+            };
+            push_u32(&mut relocs, i.index() as u32);
+            push_u32(&mut relocs, r.offset);
+            push_u32(&mut relocs, kind as u32);
+            push_u32(&mut relocs, target_type);
+            push_u32(&mut relocs, target);
+            push_u32(&mut relocs, 0);
+            relocs.extend_from_slice(&r.addend.to_le_bytes());
+            reloc_count += 1;
+        }
+    }
+
+    let data = encode_data_initializers(data_initializers);
+
+    fn part(body: &mut Vec<u8>, bytes: &[u8], alignment: usize) -> u32 {
+        align(body, alignment);
+        let offset = body.len() as u32;
+        body.extend_from_slice(bytes);
+        offset
+    }
+
+    let mut body = vec![0; HEADER_BYTES];
+    let triple_offset = part(&mut body, triple.as_bytes(), 1);
+    let strings_offset = part(&mut body, &strings, 1);
+    let functions_offset = part(&mut body, &functions, 4);
+    let relocs_offset = part(&mut body, &relocs, 8);
+    let data_offset = part(&mut body, &data, 4);
+    let code_offset = part(&mut body, &code, PAGE_SIZE);
+
+    let mut header = Vec::with_capacity(HEADER_BYTES);
+    header.extend_from_slice(MAGIC);
+    push_u32(&mut header, JIT_BUNDLE_VERSION);
+    for &(offset, len) in &[
+        (triple_offset, triple.len()),
+        (strings_offset, strings.len()),
+        (functions_offset, compilation.functions.len()),
+        (relocs_offset, reloc_count),
+        (data_offset, data.len()),
+        (code_offset, code.len()),
+    ] {
+        push_u32(&mut header, offset);
+        push_u32(&mut header, len as u32);
+    }
+    body[..HEADER_BYTES].copy_from_slice(&header);
+    Ok(body)
+}
+
+/// Writes the bundle of a compiled module for `triple` to `out`.
+pub fn write_jit_bundle<W: Write>(
+    mut out: W,
+    triple: &str,
+    module: &Module,
+    compilation: &Compilation,
+    relocations: &Relocations,
+    data_initializers: &[DataInitializer],
+) -> Result<(), String> {
+    let bundle = encode_jit_bundle(triple, module, compilation, relocations, data_initializers)?;
+    out.write_all(&bundle).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::CodegenError;
+    use cranelift_entity::PrimaryMap;
+    use cranelift_wasm::{DefinedFuncIndex, FuncIndex};
+    use wasmtime_environ::{CompileError, FailedFunction, Relocation};
+
+    fn read_u32(bytes: &[u8], at: usize) -> u32 {
+        let mut word = [0; 4];
+        word.copy_from_slice(&bytes[at..at + 4]);
+        u32::from_le_bytes(word)
+    }
+
+    /// A module importing one function and defining two, the first calling
+    /// the second and both using the signature table.
+    fn compiled() -> (Module, Compilation, Relocations) {
+        let mut module = Module::new();
+        module
+            .imported_funcs
+            .push((String::from("env"), String::from("log")));
+        let mut functions = PrimaryMap::new();
+        functions.push(vec![0xe8, 0, 0, 0, 0, 0xc3]);
+        functions.push(vec![0xc3]);
+        let relocation = |reloc, reloc_target, offset| Relocation {
+            reloc,
+            reloc_target,
+            offset,
+            addend: -4,
+        };
+        let mut relocations = PrimaryMap::new();
+        relocations.push(vec![
+            relocation(
+                Reloc::X86CallPCRel4,
+                RelocationTarget::UserFunc(FuncIndex::new(2)),
+                1,
+            ),
+            relocation(Reloc::Abs8, RelocationTarget::SignatureTableBase, 0),
+        ]);
+        relocations.push(vec![relocation(
+            Reloc::Abs8,
+            RelocationTarget::SignatureTableBase,
+            0,
+        )]);
+        (module, Compilation::new(functions), relocations)
+    }
+
+    #[test]
+    fn test_string_offset() {
+        let mut strings = Vec::new();
+        assert_eq!(string_offset(&mut strings, "a"), 0);
+        assert_eq!(string_offset(&mut strings, "bc"), 2);
+        assert_eq!(string_offset(&mut strings, "a"), 0);
+        assert_eq!(string_offset(&mut strings, "bc"), 2);
+        assert_eq!(strings, b"a\0bc\0");
+    }
+
+    #[test]
+    fn test_encode_jit_bundle() {
+        let (module, compilation, relocations) = compiled();
+        let triple = "x86_64-unknown-linux-gnu";
+        let bundle = encode_jit_bundle(triple, &module, &compilation, &relocations, &[]).unwrap();
+        assert_eq!(&bundle[0..4], MAGIC);
+        assert_eq!(read_u32(&bundle, 4), JIT_BUNDLE_VERSION);
+        let part = |n: usize| {
+            (
+                read_u32(&bundle, 8 + 8 * n) as usize,
+                read_u32(&bundle, 12 + 8 * n) as usize,
+            )
+        };
+
+        let (triple_offset, triple_len) = part(0);
+        assert_eq!(
+            &bundle[triple_offset..triple_offset + triple_len],
+            triple.as_bytes()
+        );
+
+        // Both functions' relocations share one string.
+        let (strings_offset, strings_len) = part(1);
+        let mut strings = SIGNATURE_TABLE_SYMBOL.as_bytes().to_vec();
+        strings.push(0);
+        assert_eq!(
+            &bundle[strings_offset..strings_offset + strings_len],
+            &strings[..]
+        );
+
+        let (functions_offset, function_count) = part(2);
+        assert_eq!(function_count, 2);
+        let function =
+            |n: usize, field: usize| read_u32(&bundle, functions_offset + 12 * n + 4 * field);
+        assert_eq!((function(0, 0), function(0, 1), function(0, 2)), (1, 0, 6));
+        assert_eq!((function(1, 0), function(1, 1), function(1, 2)), (2, 16, 1));
+
+        let (relocs_offset, reloc_count) = part(3);
+        assert_eq!(reloc_count, 3);
+        let reloc = |n: usize, field: usize| read_u32(&bundle, relocs_offset + 32 * n + 4 * field);
+        assert_eq!(reloc(0, 0), 0);
+        assert_eq!(reloc(0, 1), 1);
+        match RELOC_KINDS[reloc(0, 2) as usize] {
+            Reloc::X86CallPCRel4 => {}
+            kind => panic!("unexpected relocation kind {:?}", kind),
+        }
+        assert_eq!((reloc(0, 3), reloc(0, 4)), (TARGET_USER_FUNC, 2));
+        assert_eq!((reloc(1, 3), reloc(1, 4)), (TARGET_SYMBOL, 0));
+        assert_eq!(reloc(2, 0), 1);
+        assert_eq!((reloc(2, 3), reloc(2, 4)), (TARGET_SYMBOL, 0));
+        assert_eq!(
+            &bundle[relocs_offset + 24..relocs_offset + 32],
+            &(-4i64).to_le_bytes()
+        );
+
+        let (data_offset, data_len) = part(4);
+        assert_eq!(&bundle[data_offset..data_offset + data_len], &[0, 0, 0, 0]);
+
+        let (code_offset, code_len) = part(5);
+        assert_eq!(code_offset % PAGE_SIZE, 0);
+        assert_eq!(code_len, 17);
+        assert_eq!(bundle.len(), code_offset + code_len);
+        assert_eq!(
+            &bundle[code_offset..code_offset + 6],
+            &compilation.functions[DefinedFuncIndex::new(0)][..]
+        );
+        assert_eq!(bundle[code_offset + 16], 0xc3);
+    }
+
+    #[test]
+    fn test_encode_jit_bundle_failed_function() {
+        let (module, mut compilation, relocations) = compiled();
+        compilation.failed.push(FailedFunction {
+            index: DefinedFuncIndex::new(1),
+            error: CompileError::Codegen(CodegenError::ImplLimitExceeded),
+            wasm_body: Vec::new(),
+        });
+        let result = encode_jit_bundle("x86_64", &module, &compilation, &relocations, &[]);
+        assert!(result.unwrap_err().contains("function 2"));
+    }
+}
@@ -0,0 +1,306 @@
+//! Versioned JSON manifest of the exports and imports of a set of modules,
+//! for a linker that wires them together.
+//!
+//! The document has the following shape:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "modules": [
+//!     {
+//!       "name": "app",
+//!       "file": "app.wasm",
+//!       "exports": [
+//!         { "name": "run", "kind": "function", "params": [], "results": ["i32"] }
+//!       ],
+//!       "imports": [
+//!         {
+//!           "module": "lib",
+//!           "field": "add",
+//!           "kind": "function",
+//!           "params": ["i32", "i32"],
+//!           "results": ["i32"],
+//!           "resolved": true
+//!         }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Modules are named after their file stem and listed in the order they
+//! were given. Exports are in the module's export order, and imports are
+//! functions, tables, memories and then globals, each in index order.
+//! `kind` is one of `function` (with `params` and `results`), `table`,
+//! `memory` or `global` (with `type` and `mutable`).
+//!
+//! An import is `resolved` when another module in the set, named as its
+//! `module`, exports its `field` with the same kind and type. Unresolved
+//! imports are left for the host to provide.
+//!
+//! `version` is bumped whenever a field is removed or changes meaning;
+//! adding new fields does not bump it, so consumers should ignore what they
+//! don't recognize.
+
+use cranelift_codegen::ir;
+use std::io::Write;
+use std::path::Path;
+use wasmtime_environ::{Export, Module};
+
+/// Version of the JSON document layout.
+pub const LINK_MANIFEST_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct ManifestDocument {
+    version: u32,
+    modules: Vec<ModuleEntry>,
+}
+
+#[derive(Serialize)]
+struct ModuleEntry {
+    name: String,
+    file: String,
+    exports: Vec<ExportEntry>,
+    imports: Vec<ImportEntry>,
+}
+
+#[derive(Serialize)]
+struct ExportEntry {
+    name: String,
+    #[serde(flatten)]
+    entity: Entity,
+}
+
+#[derive(Serialize)]
+struct ImportEntry {
+    module: String,
+    field: String,
+    #[serde(flatten)]
+    entity: Entity,
+    resolved: bool,
+}
+
+#[derive(Serialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Entity {
+    Function {
+        params: Vec<String>,
+        results: Vec<String>,
+    },
+    Table,
+    Memory,
+    Global {
+        #[serde(rename = "type")]
+        ty: String,
+        mutable: bool,
+    },
+}
+
+fn function_entity(signature: &ir::Signature) -> Entity {
+    Entity::Function {
+        params: signature
+            .params
+            .iter()
+            .filter(|p| p.purpose == ir::ArgumentPurpose::Normal)
+            .map(|p| p.value_type.to_string())
+            .collect(),
+        results: signature
+            .returns
+            .iter()
+            .map(|r| r.value_type.to_string())
+            .collect(),
+    }
+}
+
+fn exports(module: &Module) -> Vec<ExportEntry> {
+    module
+        .exports
+        .iter()
+        .map(|(name, export)| ExportEntry {
+            name: name.clone(),
+            entity: match *export {
+                Export::Function(func_index) => {
+                    function_entity(&module.signatures[module.functions[func_index]])
+                }
+                Export::Table(_) => Entity::Table,
+                Export::Memory(_) => Entity::Memory,
+                Export::Global(global_index) => {
+                    let global = &module.globals[global_index];
+                    Entity::Global {
+                        ty: global.ty.to_string(),
+                        mutable: global.mutability,
+                    }
+                }
+            },
+        })
+        .collect()
+}
+
+fn imports(module: &Module) -> Vec<ImportEntry> {
+    let import = |name: &(String, String), entity| ImportEntry {
+        module: name.0.clone(),
+        field: name.1.clone(),
+        entity,
+        resolved: false,
+    };
+    let mut imports = Vec::new();
+    for (func_index, name) in module.imported_funcs.iter() {
+        let signature = &module.signatures[module.functions[func_index]];
+        imports.push(import(name, function_entity(signature)));
+    }
+    for name in module.imported_tables.values() {
+        imports.push(import(name, Entity::Table));
+    }
+    for name in module.imported_memories.values() {
+        imports.push(import(name, Entity::Memory));
+    }
+    for (global_index, name) in module.imported_globals.iter() {
+        let global = &module.globals[global_index];
+        let entity = Entity::Global {
+            ty: global.ty.to_string(),
+            mutable: global.mutability,
+        };
+        imports.push(import(name, entity));
+    }
+    imports
+}
+
+/// Returns the name other modules import `file` by, its file stem.
+fn module_name(file: &str) -> String {
+    Path::new(file)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from(file))
+}
+
+/// Writes the linking manifest for `modules`, given with the files they
+/// were read from, to `out`.
+pub fn write_link_manifest<W: Write>(out: W, modules: &[(&str, Module)]) -> Result<(), String> {
+    let mut entries = modules
+        .iter()
+        .map(|(file, module)| ModuleEntry {
+            name: module_name(file),
+            file: file.to_string(),
+            exports: exports(module),
+            imports: imports(module),
+        })
+        .collect::<Vec<_>>();
+
+    for (i, entry) in entries.iter().enumerate() {
+        if entries[..i].iter().any(|other| other.name == entry.name) {
+            return Err(format!(
+                "more than one module is named '{}', from {}",
+                entry.name, entry.file
+            ));
+        }
+    }
+
+    for i in 0..entries.len() {
+        for j in 0..entries[i].imports.len() {
+            let import = &entries[i].imports[j];
+            let resolved = entries.iter().any(|provider| {
+                provider.name == import.module
+                    && provider
+                        .exports
+                        .iter()
+                        .any(|export| export.name == import.field && export.entity == import.entity)
+            });
+            entries[i].imports[j].resolved = resolved;
+        }
+    }
+
+    let document = ManifestDocument {
+        version: LINK_MANIFEST_VERSION,
+        modules: entries,
+    };
+    serde_json::to_writer_pretty(out, &document).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::settings;
+    use wasmtime_environ::{ModuleEnvironment, Tunables};
+
+    fn translate(wat: &str) -> Module {
+        let data = wabt::wat2wasm(wat).unwrap();
+        let isa_builder = cranelift_native::builder().unwrap();
+        let isa = isa_builder.finish(settings::Flags::new(settings::builder()));
+        ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+            .translate(&data)
+            .unwrap()
+            .module
+    }
+
+    fn lib() -> Module {
+        translate(
+            r#"
+            (module
+              (func (export "add") (param i32 i32) (result i32)
+                (i32.add (get_local 0) (get_local 1))
+              )
+            )
+            "#,
+        )
+    }
+
+    fn app() -> Module {
+        translate(
+            r#"
+            (module
+              (import "lib" "add" (func (param i32 i32) (result i32)))
+              (import "lib" "mul" (func (param i32 i32) (result i32)))
+              (import "env" "memory" (memory 1))
+              (func (export "run") (result i32)
+                (call 0 (i32.const 1) (i32.const 2))
+              )
+            )
+            "#,
+        )
+    }
+
+    #[test]
+    fn test_write_link_manifest() {
+        let mut out = Vec::new();
+        write_link_manifest(&mut out, &[("dir/app.wasm", app()), ("lib.wasm", lib())]).unwrap();
+        let document: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(document["version"], LINK_MANIFEST_VERSION);
+
+        let modules = document["modules"].as_array().unwrap();
+        assert_eq!(modules.len(), 2);
+        assert_eq!(modules[0]["name"], "app");
+        assert_eq!(modules[0]["file"], "dir/app.wasm");
+        assert_eq!(
+            modules[0]["exports"],
+            serde_json::json!([
+                { "name": "run", "kind": "function", "params": [], "results": ["i32"] }
+            ])
+        );
+        assert_eq!(
+            modules[1]["exports"][0],
+            serde_json::json!({
+                "name": "add",
+                "kind": "function",
+                "params": ["i32", "i32"],
+                "results": ["i32"]
+            })
+        );
+
+        // Only the import `lib` exports is resolved.
+        let imports = modules[0]["imports"].as_array().unwrap();
+        assert_eq!(imports.len(), 3);
+        assert_eq!(imports[0]["field"], "add");
+        assert_eq!(imports[0]["resolved"], true);
+        assert_eq!(imports[1]["field"], "mul");
+        assert_eq!(imports[1]["resolved"], false);
+        assert_eq!(imports[2]["kind"], "memory");
+        assert_eq!(imports[2]["resolved"], false);
+    }
+
+    #[test]
+    fn test_write_link_manifest_duplicate_names() {
+        let mut out = Vec::new();
+        let result = write_link_manifest(&mut out, &[("a/lib.wasm", lib()), ("b/lib.wasm", lib())]);
+        assert!(result.unwrap_err().contains("b/lib.wasm"));
+    }
+}
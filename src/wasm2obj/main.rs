@@ -0,0 +1,708 @@
+//! Translation from wasm to native object files.
+//!
+//! Reads a Wasm binary file, translates the functions' code to Cranelift
+//! IL, then translates it to native code, and writes it out to a native
+//! object file with relocations.
+
+#![deny(
+    missing_docs,
+    trivial_numeric_casts,
+    unused_extern_crates,
+    unstable_features
+)]
+#![warn(unused_import_braces)]
+#![cfg_attr(feature = "clippy", plugin(clippy(conf_file = "../clippy.toml")))]
+#![cfg_attr(
+    feature = "cargo-clippy",
+    allow(clippy::new_without_default, clippy::new_without_default_derive)
+)]
+#![cfg_attr(
+    feature = "cargo-clippy",
+    warn(
+        clippy::float_arithmetic,
+        clippy::mut_mut,
+        clippy::nonminimal_bool,
+        clippy::option_map_unwrap_or,
+        clippy::option_map_unwrap_or_else,
+        clippy::unicode_not_nfc,
+        clippy::use_self
+    )
+)]
+
+#[macro_use]
+extern crate serde_derive;
+
+use crate::float_abi::{apply_float_abi, float_abi_of, FloatAbi};
+use crate::target_features::apply_target_features;
+use cranelift_codegen::ir;
+use cranelift_codegen::isa;
+use cranelift_codegen::settings;
+use cranelift_codegen::settings::Configurable;
+use cranelift_entity::EntityRef;
+use cranelift_wasm::FuncIndex;
+use docopt::Docopt;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::format;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::str;
+use std::str::FromStr;
+use target_lexicon::{Architecture, BinaryFormat, Triple};
+use wasmtime_environ::{cranelift, summarize_relocations, ModuleEnvironment, Tunables};
+use wasmtime_obj::{
+    check_section_prefix, BuildId, EmitOptions, IrCompression, PatchableEntry, Visibility,
+};
+use wasmtime_tools::module_kind::ModuleKind;
+use wasmtime_tools::obj::{compile_to_object_with_options, ObjectOptions};
+
+mod bare_metal;
+mod call_conv;
+mod callers_json;
+mod deps;
+mod dump_relocations;
+mod float_abi;
+mod host_interface;
+mod jit_bundle;
+mod link_manifest;
+mod output;
+mod relocs_json;
+mod stack_sizes;
+mod target_features;
+mod verbose;
+
+const USAGE: &str = "
+Wasm to native object translation utility.
+Takes a binary WebAssembly module into a native object file.
+The translation is dependent on the environment chosen.
+The default is a dummy environment that produces placeholder values.
+An <output> of - writes the object to standard output.
+
+Usage:
+    wasm2obj [options] <file> -o <output>
+    wasm2obj [options] --check <file>
+    wasm2obj [options] --link-manifest=<file> <input>...
+    wasm2obj --help | --version
+
+Options:
+    -v, --verbose         displays the module and translated functions
+    -h, --help            print this help message
+    --check               translate and compile <file> for the target without writing an
+                          object, to check that it compiles
+    --target <TARGET>     build for the target triple; default is the host machine
+    --target-feature <FEATURES>
+                          enable (+<feature>) or disable (-<feature>) the comma-separated
+                          ISA features, such as +sse41,+bmi2
+    --format <FORMAT>     object file format, elf, macho or coff; default follows the target
+    -g                    generate debug information
+    --dwarf-traps         with -g, describe trap sites with DWARF labels
+    --relocs-json=<file>  write the relocations in the versioned JSON format to <file>
+    --dump-relocations    print the relocations of each function to stderr
+    --stats               print the counts of relocations by kind of target to stderr
+    --callers-json=<file>
+                          write the direct call sites of each function as JSON to <file>
+    --jit-bundle=<file>   write the code, relocations and data initializers to <file> as a
+                          bundle a runtime can load in-process without a linker
+    --deps=<file>         write a Makefile-style dependency rule for <output> to <file>
+    --emit-stack-sizes=<file>
+                          write frame sizes and worst-case stack depths as JSON to <file>
+    --defer-data-init     emit data initializers as a table for the runtime to apply
+                          instead of pre-populated data segments
+    --build-id <STYLE>    emit a .note.gnu.build-id computed with none, sha1 or hash [default: none]
+    --host-interface=<file>
+                          check the module's imports against the JSON host interface in <file>
+    --libcalls <LIST>     comma-separated libcalls the runtime provides, such as
+                          FloorF32,CeilF64; default is all of them
+    --call-conv <CONV>    calling convention of the module's functions, system_v,
+                          windows_fastcall, fast or cold; default follows the target
+    --float-abi <ABI>     floating-point ABI, either soft or hard; default follows the target
+    --interpret-failures  leave functions that fail to compile to the runtime's interpreter,
+                          recording their wasm bodies in a fallback table
+    --max-function-code-size <N>
+                          fail to compile functions with more than N bytes of code, or
+                          interpret them with --interpret-failures
+    --bare-metal=<file>   avoid code that assumes an operating system, such as stack probes,
+                          and write the dependencies the environment must provide to <file>
+    --init-array <EXPORT>
+                          run the exported function at load time through an .init_array entry
+    --module-kind <KIND>  follow the WASI command or reactor convention, or auto to detect it
+                          from the exports; a reactor's _initialize runs at load time
+    --static-memory-bound <PAGES>
+                          wasm pages of memories that are statically bounded, so that
+                          accesses below it are checked by the guard pages alone
+    --static-memory-guard-size <BYTES>
+                          bytes of guard pages after statically bounded memories
+    --dynamic-memory-guard-size <BYTES>
+                          bytes of guard pages after dynamically bounded memories
+    --relocatable-signature-table
+                          check call_indirect signatures against a table the runtime
+                          provides as _signature_table, instead of against the vmctx
+    --pie                 generate position-independent code that links into a PIE
+    --pic                 generate position-independent code that links into a shared
+                          library, calling functions through the PLT and taking their
+                          addresses from the GOT
+    --frame-pointer-chain
+                          check that every function links its frame into the frame-pointer
+                          chain, for profilers that unwind without DWARF
+    --visibility <V>      ELF visibility of exported functions, default, hidden or protected;
+                          other functions are then hidden
+    --canonicalize-nans   make float operations return the canonical NaN, so that results
+                          are bit-identical across hardware
+    --opt-level <LEVEL>   optimization level, none, speed or speed_and_size, setting
+                          Cranelift's opt_level to fastest, default or best; default speed
+    --bounds-check-hoisting <H>
+                          on or off; with on, loop-invariant code motion hoists invariant
+                          parts of memory address computations out of loops at --opt-level
+                          speed_and_size, the only level running it; off skips it there
+                          too, so every access in a loop recomputes them: slower loops,
+                          but simpler code when diagnosing a bounds-check miscompile
+                          [default: on]
+    --print-isa           print the target ISA and its settings to stderr
+    --only-func <N>       compile only function N, or the comma-separated functions, leaving
+                          the others with empty bodies, such as to inspect one function's code
+    --func-offsets        emit a .wasm.func_offsets section giving the code range of each
+                          defined function, for a loader building its function table
+    --export-names        emit a .wasm.exports section mapping symbols to their export names
+    --comment             emit a .comment section recording the compiler version and the ISA
+                          settings, for reproducibility audits
+    --tunables-section    emit a .wasm.tunables section recording the memory tunables, for
+                          a loader to check against its own
+    --export-symbols      give each exported function a global symbol named after the export
+    --embed-ir            embed each function's Cranelift IR in a .clif section
+    --ir-compression <C>  compression of the .clif section, none or zlib [default: zlib]
+    --data-section <MAP>  comma-separated <segment>=<section> pairs placing data
+                          segments in named ELF sections instead of .data
+    --section-prefix <P>  prefix the names of the ELF code, data and debug sections with P,
+                          such as modA.text, so that several modules link into one binary
+    --profile-builtins    count calls to each libcall and builtin, for a profiling build
+    --patchable-entry <N>
+                          start functions with N bytes of nops a runtime can patch, recording
+                          them in _patchable_function_entries
+    --patchable-functions <LIST>
+                          comma-separated indices of the functions given patchable entries;
+                          default is all defined functions
+    --link-manifest=<file>
+                          write the exports and imports of each <input> as JSON to <file>,
+                          resolving imports between them, instead of compiling
+    --version             print the Cranelift version
+";
+
+#[derive(Deserialize, Debug, Clone)]
+struct Args {
+    arg_file: String,
+    arg_output: String,
+    arg_input: Vec<String>,
+    flag_verbose: bool,
+    flag_check: bool,
+    flag_target: Option<String>,
+    flag_target_feature: Option<String>,
+    flag_format: Option<String>,
+    flag_g: bool,
+    flag_dwarf_traps: bool,
+    flag_relocs_json: Option<String>,
+    flag_dump_relocations: bool,
+    flag_stats: bool,
+    flag_callers_json: Option<String>,
+    flag_jit_bundle: Option<String>,
+    flag_deps: Option<String>,
+    flag_emit_stack_sizes: Option<String>,
+    flag_defer_data_init: bool,
+    flag_build_id: String,
+    flag_host_interface: Option<String>,
+    flag_libcalls: Option<String>,
+    flag_call_conv: Option<String>,
+    flag_float_abi: Option<String>,
+    flag_interpret_failures: bool,
+    flag_max_function_code_size: Option<usize>,
+    flag_bare_metal: Option<String>,
+    flag_init_array: Option<String>,
+    flag_module_kind: Option<String>,
+    flag_static_memory_bound: Option<String>,
+    flag_static_memory_guard_size: Option<String>,
+    flag_dynamic_memory_guard_size: Option<String>,
+    flag_relocatable_signature_table: bool,
+    flag_pie: bool,
+    flag_pic: bool,
+    flag_frame_pointer_chain: bool,
+    flag_visibility: Option<String>,
+    flag_canonicalize_nans: bool,
+    flag_opt_level: Option<String>,
+    flag_bounds_check_hoisting: String,
+    flag_print_isa: bool,
+    flag_func_offsets: bool,
+    flag_export_names: bool,
+    flag_comment: bool,
+    flag_tunables_section: bool,
+    flag_export_symbols: bool,
+    flag_embed_ir: bool,
+    flag_ir_compression: String,
+    flag_data_section: Option<String>,
+    flag_section_prefix: Option<String>,
+    flag_profile_builtins: bool,
+    flag_patchable_entry: Option<usize>,
+    flag_patchable_functions: Option<String>,
+    flag_only_func: Option<String>,
+    flag_link_manifest: Option<String>,
+}
+
+fn read_wasm_file(path: PathBuf) -> Result<Vec<u8>, io::Error> {
+    let mut buf: Vec<u8> = Vec::new();
+    let mut file = File::open(path)?;
+    file.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Parses a comma-separated list of `<segment>=<section>` pairs.
+fn parse_data_sections(map: &str) -> Result<HashMap<usize, String>, String> {
+    let mut data_sections = HashMap::new();
+    for pair in map.split(',').filter(|pair| !pair.is_empty()) {
+        let mut parts = pair.splitn(2, '=');
+        let segment = parts.next().unwrap();
+        let section = parts
+            .next()
+            .ok_or_else(|| format!("expected <segment>=<section>, got '{}'", pair))?;
+        let segment = segment
+            .parse::<usize>()
+            .map_err(|_| format!("invalid data segment index '{}'", segment))?;
+        if data_sections
+            .insert(segment, String::from(section))
+            .is_some()
+        {
+            return Err(format!("data segment {} is placed more than once", segment));
+        }
+    }
+    Ok(data_sections)
+}
+
+/// Returns the flag asking for position-independent code, if any.
+fn pic_flag(args: &Args) -> Option<&'static str> {
+    if args.flag_pic {
+        Some("--pic")
+    } else if args.flag_pie {
+        Some("--pie")
+    } else {
+        None
+    }
+}
+
+/// Returns the Cranelift `opt_level` for `--opt-level`, if it is given.
+fn opt_level(args: &Args) -> Result<Option<&'static str>, String> {
+    match args.flag_opt_level.as_ref().map(String::as_str) {
+        Some("none") => Ok(Some("fastest")),
+        Some("speed") => Ok(Some("default")),
+        Some("speed_and_size") => Ok(Some("best")),
+        Some(level) => Err(format!(
+            "unknown --opt-level '{}'; expected 'none', 'speed' or 'speed_and_size'",
+            level
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Parses `--bounds-check-hoisting`.
+fn bounds_check_hoisting(args: &Args) -> Result<bool, String> {
+    match args.flag_bounds_check_hoisting.as_str() {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        hoisting => Err(format!(
+            "unknown --bounds-check-hoisting '{}'; expected 'on' or 'off'",
+            hoisting
+        )),
+    }
+}
+
+/// Parses an object file format, checking that it can hold code for the
+/// architecture of `triple`.
+fn parse_format(format: &str, triple: &Triple) -> Result<BinaryFormat, String> {
+    let binary_format = match format {
+        "elf" => BinaryFormat::Elf,
+        "macho" => BinaryFormat::Macho,
+        "coff" => BinaryFormat::Coff,
+        _ => {
+            return Err(format!(
+                "unknown object format '{}'; expected 'elf', 'macho' or 'coff'",
+                format
+            ))
+        }
+    };
+    match (binary_format, triple.architecture) {
+        (BinaryFormat::Elf, _) => Ok(binary_format),
+        (BinaryFormat::Macho, Architecture::X86_64)
+        | (BinaryFormat::Macho, Architecture::I386)
+        | (BinaryFormat::Macho, Architecture::I686) => Ok(binary_format),
+        (BinaryFormat::Coff, _) => Err(String::from("COFF objects are not supported yet")),
+        (_, architecture) => Err(format!(
+            "{} objects can't hold {} code",
+            binary_format, architecture
+        )),
+    }
+}
+
+/// Parses the value of a numeric option, in decimal or, with a `0x`
+/// prefix, in hexadecimal.
+fn parse_number(option: &str, value: &str) -> Result<u64, String> {
+    let parsed = if value.starts_with("0x") {
+        u64::from_str_radix(&value[2..], 16)
+    } else {
+        value.parse::<u64>()
+    };
+    parsed.map_err(|_| format!("invalid {} '{}'", option, value))
+}
+
+/// Applies the tunables given on the command line over the defaults.
+fn parse_tunables(args: &Args) -> Result<Tunables, String> {
+    let mut tunables = Tunables::default();
+    if let Some(ref pages) = args.flag_static_memory_bound {
+        let pages = parse_number("--static-memory-bound", pages)?;
+        if pages > 0x1_0000 {
+            return Err(format!(
+                "--static-memory-bound {} is more than the 65536 pages of a 32-bit memory",
+                pages
+            ));
+        }
+        tunables.static_memory_bound = pages as u32;
+    }
+    if let Some(ref bytes) = args.flag_static_memory_guard_size {
+        tunables.static_memory_offset_guard_size =
+            parse_number("--static-memory-guard-size", bytes)?;
+    }
+    if let Some(ref bytes) = args.flag_dynamic_memory_guard_size {
+        tunables.dynamic_memory_offset_guard_size =
+            parse_number("--dynamic-memory-guard-size", bytes)?;
+    }
+    tunables.relocatable_signature_table = args.flag_relocatable_signature_table;
+    Ok(tunables)
+}
+
+fn main() {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| {
+            d.help(true)
+                .version(Some(String::from("0.0.0")))
+                .deserialize()
+        })
+        .unwrap_or_else(|e| e.exit());
+
+    let result = if args.flag_link_manifest.is_some() {
+        handle_link_manifest(&args)
+    } else {
+        handle_module(&args)
+    };
+    match result {
+        Ok(()) => {}
+        Err(message) => {
+            eprintln!(" error: {}", message);
+            process::exit(1);
+        }
+    }
+}
+
+fn isa_builder(args: &Args) -> Result<isa::Builder, String> {
+    let float_abi = match args.flag_float_abi {
+        Some(ref float_abi) => Some(float_abi.parse::<FloatAbi>()?),
+        None => None,
+    };
+
+    let isa_builder = match (&args.flag_target, float_abi) {
+        (None, None) => wasmtime_tools::obj::isa_builder(None)?,
+        (target, float_abi) => {
+            let mut triple = match *target {
+                Some(ref target) => {
+                    Triple::from_str(target).map_err(|_| "could not parse --target")?
+                }
+                None => Triple::host(),
+            };
+            if let Some(float_abi) = float_abi {
+                apply_float_abi(&mut triple, float_abi)?;
+            }
+            wasmtime_tools::obj::isa_builder(Some(&triple))?
+        }
+    };
+    Ok(isa_builder)
+}
+
+fn handle_link_manifest(args: &Args) -> Result<(), String> {
+    let isa = isa_builder(args)?.finish(settings::Flags::new(settings::builder()));
+
+    let mut modules = Vec::new();
+    for input in &args.arg_input {
+        let data = read_wasm_file(PathBuf::from(input)).map_err(|e| e.to_string())?;
+        let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+            .translate(&data)
+            .map_err(|error| format!("{}: {}", input, error))?;
+        modules.push((input.as_str(), translation.module));
+    }
+
+    let manifest = args.flag_link_manifest.as_ref().unwrap();
+    let file = File::create(Path::new(manifest)).map_err(|x| format(format_args!("{}", x)))?;
+    link_manifest::write_link_manifest(file, &modules)
+}
+
+/// Parses a comma-separated list of function indices.
+fn parse_function_list(list: &str) -> Result<Vec<FuncIndex>, String> {
+    list.split(',')
+        .filter(|index| !index.is_empty())
+        .map(|index| {
+            index
+                .parse::<usize>()
+                .map(FuncIndex::new)
+                .map_err(|_| format!("invalid function index '{}'", index))
+        })
+        .collect()
+}
+
+/// Builds the ISA for the target, features and code generation settings
+/// given on the command line.
+fn build_isa(args: &Args) -> Result<Box<dyn isa::TargetIsa>, String> {
+    let mut isa_builder = isa_builder(args)?;
+    if let Some(ref features) = args.flag_target_feature {
+        let triple = match args.flag_target {
+            Some(ref target) => Triple::from_str(target).map_err(|_| "could not parse --target")?,
+            None => Triple::host(),
+        };
+        apply_target_features(&mut isa_builder, &triple, features)
+            .map_err(|e| format!("--target-feature: {}", e))?;
+    }
+    let mut flag_builder = settings::builder();
+    if args.flag_bare_metal.is_some() {
+        for (name, value) in bare_metal::BARE_METAL_SETTINGS {
+            flag_builder
+                .set(name, value)
+                .map_err(|e| format!("--bare-metal: {}", e))?;
+        }
+    }
+    if let Some(flag) = pic_flag(args) {
+        flag_builder
+            .enable("is_pic")
+            .map_err(|e| format!("{}: {}", flag, e))?;
+    }
+    if args.flag_canonicalize_nans {
+        flag_builder
+            .enable("enable_nan_canonicalization")
+            .map_err(|e| format!("--canonicalize-nans: {}", e))?;
+    }
+    if let Some(opt_level) = opt_level(args)? {
+        flag_builder
+            .set("opt_level", opt_level)
+            .map_err(|e| format!("--opt-level: {}", e))?;
+    }
+    Ok(isa_builder.finish(settings::Flags::new(flag_builder)))
+}
+
+/// Converts the command line to the options of
+/// `compile_to_object_with_options`.
+fn object_options(args: &Args) -> Result<ObjectOptions<'static>, String> {
+    let isa = build_isa(args)?;
+    if args.flag_print_isa {
+        eprintln!("isa: {}", isa.name());
+        eprintln!("triple: {}", isa.triple());
+        eprintln!("float ABI: {}", float_abi_of(isa.triple()));
+        eprint!("{}", isa);
+    }
+
+    let binary_format = match args.flag_format {
+        Some(ref format) => Some(parse_format(format, isa.triple())?),
+        None => None,
+    };
+    let call_conv = match args.flag_call_conv {
+        Some(ref call_conv) => Some(
+            call_conv::parse_call_conv(call_conv, isa.triple())
+                .map_err(|e| format!("--call-conv: {}", e))?,
+        ),
+        None => None,
+    };
+    let host_interface = match args.flag_host_interface {
+        Some(ref host_interface) => {
+            let file =
+                File::open(Path::new(host_interface)).map_err(|x| format(format_args!("{}", x)))?;
+            Some(host_interface::read_host_interface(file)?)
+        }
+        None => None,
+    };
+    let (module_kind, detect_module_kind) = match args.flag_module_kind.as_ref().map(String::as_str)
+    {
+        Some("auto") => (None, true),
+        Some(kind) => (Some(kind.parse::<ModuleKind>()?), false),
+        None => (None, false),
+    };
+    let libcalls = match args.flag_libcalls {
+        Some(ref libcalls) => Some(
+            libcalls
+                .split(',')
+                .filter(|name| !name.is_empty())
+                .map(|name| {
+                    ir::LibCall::from_str(name).map_err(|_| format!("unknown libcall '{}'", name))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        None => None,
+    };
+    let only_functions = match args.flag_only_func {
+        Some(ref list) => Some(parse_function_list(list)?),
+        None => None,
+    };
+    let patchable_functions = match args.flag_patchable_functions {
+        Some(ref list) => Some(parse_function_list(list)?),
+        None => None,
+    };
+    let embed_ir = if args.flag_embed_ir {
+        Some(args.flag_ir_compression.parse::<IrCompression>()?)
+    } else {
+        None
+    };
+    let emit = EmitOptions {
+        defer_data_init: args.flag_defer_data_init,
+        build_id: args.flag_build_id.parse::<BuildId>()?,
+        embed_ir,
+        data_sections: match args.flag_data_section {
+            Some(ref map) => parse_data_sections(map)?,
+            None => HashMap::new(),
+        },
+        profile_builtins: args.flag_profile_builtins,
+        patchable_entry: PatchableEntry {
+            size: args.flag_patchable_entry.unwrap_or(0),
+            functions: Vec::new(),
+        },
+        func_offsets: args.flag_func_offsets,
+        export_names: args.flag_export_names,
+        ..EmitOptions::default()
+    };
+    let visibility = match args.flag_visibility {
+        Some(ref visibility) => Some(visibility.parse::<Visibility>()?),
+        None => None,
+    };
+    if let Some(ref prefix) = args.flag_section_prefix {
+        check_section_prefix(prefix).map_err(|e| format!("--section-prefix: {}", e))?;
+    }
+
+    Ok(ObjectOptions {
+        isa: Some(isa),
+        binary_format,
+        generate_debug_info: args.flag_g,
+        dwarf_traps: args.flag_dwarf_traps,
+        tunables: parse_tunables(args)?,
+        call_conv,
+        compile: cranelift::CompileOptions {
+            capture_ir: args.flag_embed_ir || args.flag_verbose,
+            allow_failures: args.flag_interpret_failures,
+            max_code_size: args.flag_max_function_code_size,
+            bounds_check_hoisting: bounds_check_hoisting(args)?,
+            ..cranelift::CompileOptions::default()
+        },
+        only_functions,
+        host_interface,
+        module_kind,
+        detect_module_kind,
+        init_array: args.flag_init_array.clone(),
+        frame_pointer_chain: args.flag_frame_pointer_chain,
+        patchable_functions,
+        libcalls,
+        emit,
+        comment_section: args.flag_comment,
+        tunables_section: args.flag_tunables_section,
+        export_symbols: args.flag_export_symbols,
+        visibility,
+        section_prefix: args.flag_section_prefix.clone(),
+    })
+}
+
+fn handle_module(args: &Args) -> Result<(), String> {
+    let path = Path::new(&args.arg_file);
+    let output = &args.arg_output;
+
+    let data = match read_wasm_file(path.to_path_buf()) {
+        Ok(data) => data,
+        Err(err) => {
+            return Err(String::from(err.description()));
+        }
+    };
+
+    let options = object_options(args)?;
+    let mut compiled = compile_to_object_with_options(&data, options).map_err(|e| e.to_string())?;
+    let module = &compiled.module;
+    let compilation = &compiled.compilation;
+    let relocations = &compiled.relocations;
+
+    if args.flag_verbose {
+        eprint!(
+            "{}{}",
+            verbose::describe_module(module),
+            verbose::describe_functions(module, compilation)
+        );
+    }
+
+    if args.flag_dump_relocations {
+        eprint!(
+            "{}",
+            dump_relocations::dump_relocations(module, relocations)
+        );
+    }
+
+    if args.flag_stats {
+        eprintln!("{}", summarize_relocations(relocations));
+    }
+
+    for failed in &compilation.failed {
+        eprintln!(
+            "warning: function {} will be interpreted: {}",
+            module.func_index(failed.index).index(),
+            failed.error
+        );
+    }
+
+    if args.flag_check {
+        return Ok(());
+    }
+
+    if let Some(ref relocs_json) = args.flag_relocs_json {
+        let file =
+            File::create(Path::new(relocs_json)).map_err(|x| format(format_args!("{}", x)))?;
+        relocs_json::write_relocations(file, module, compilation, relocations)?;
+    }
+
+    if let Some(ref jit_bundle) = args.flag_jit_bundle {
+        let file =
+            File::create(Path::new(jit_bundle)).map_err(|x| format(format_args!("{}", x)))?;
+        jit_bundle::write_jit_bundle(
+            file,
+            &compiled.isa.triple().to_string(),
+            module,
+            compilation,
+            relocations,
+            &compiled.data_initializers,
+        )?;
+    }
+
+    if let Some(ref manifest) = args.flag_bare_metal {
+        let file = File::create(Path::new(manifest)).map_err(|x| format(format_args!("{}", x)))?;
+        bare_metal::write_manifest(file, module, relocations)?;
+    }
+
+    if let Some(ref callers_json) = args.flag_callers_json {
+        let file =
+            File::create(Path::new(callers_json)).map_err(|x| format(format_args!("{}", x)))?;
+        callers_json::write_callers(file, module, relocations)?;
+    }
+
+    if let Some(ref stack_sizes) = args.flag_emit_stack_sizes {
+        let file =
+            File::create(Path::new(stack_sizes)).map_err(|x| format(format_args!("{}", x)))?;
+        stack_sizes::write_stack_sizes(file, module, relocations, &compiled.frame_sizes)?;
+    }
+
+    compiled.obj.name = output.clone();
+    let image = compiled.into_image()?;
+    let mut out = output::open_output(output)?;
+    output::write_image(&image, &mut *out)?;
+
+    if let Some(ref deps) = args.flag_deps {
+        let file = File::create(Path::new(deps)).map_err(|x| format(format_args!("{}", x)))?;
+        deps::write_deps(file, output, &[&args.arg_file])?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! Writing of the emitted object to a file, standard output or any other
+//! writer.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Opens the destination of the object, with `-` standing for standard
+/// output.
+pub fn open_output(output: &str) -> Result<Box<dyn Write>, String> {
+    if output == "-" {
+        Ok(Box::new(io::stdout()))
+    } else {
+        let file = File::create(Path::new(output)).map_err(|e| e.to_string())?;
+        Ok(Box::new(file))
+    }
+}
+
+/// Writes the object file image `image` to `out`.
+pub fn write_image(image: &[u8], out: &mut dyn Write) -> Result<(), String> {
+    out.write_all(image).map_err(|e| e.to_string())?;
+    out.flush().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use faerie::{Artifact, Decl};
+    use std::str::FromStr;
+    use target_lexicon::Triple;
+
+    fn read_u16(bytes: &[u8], at: usize) -> usize {
+        usize::from(bytes[at]) | usize::from(bytes[at + 1]) << 8
+    }
+
+    fn read_u32(bytes: &[u8], at: usize) -> usize {
+        read_u16(bytes, at) | read_u16(bytes, at + 2) << 16
+    }
+
+    /// Reads the names of the sections of a little-endian ELF64 image.
+    fn section_names(bytes: &[u8]) -> Vec<String> {
+        assert_eq!(&bytes[0..4], b"\x7fELF");
+        let shoff = read_u32(bytes, 0x28);
+        let shentsize = read_u16(bytes, 0x3A);
+        let shnum = read_u16(bytes, 0x3C);
+        let shstrndx = read_u16(bytes, 0x3E);
+        let strtab = read_u32(bytes, shoff + shstrndx * shentsize + 0x18);
+        (0..shnum)
+            .map(|index| {
+                let start = strtab + read_u32(bytes, shoff + index * shentsize);
+                let len = bytes[start..].iter().position(|b| *b == 0).unwrap();
+                String::from_utf8(bytes[start..start + len].to_vec()).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_write_object_to_vec() {
+        let triple = Triple::from_str("x86_64-unknown-linux-gnu").unwrap();
+        let mut obj = Artifact::new(triple, String::from("test.o"));
+        obj.declare("f", Decl::function().global()).unwrap();
+        obj.define("f", vec![0xc3]).unwrap();
+
+        let mut out = Vec::new();
+        write_image(&obj.emit().unwrap(), &mut out).unwrap();
+        let names = section_names(&out);
+        assert!(names.iter().any(|name| name == ".text.f"));
+        assert!(names.iter().any(|name| name == ".symtab"));
+    }
+}
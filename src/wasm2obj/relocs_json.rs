@@ -0,0 +1,215 @@
+//! Versioned JSON description of the relocations produced by `compile_module`.
+//!
+//! Unlike the object file itself, this is a stable, format-independent
+//! contract intended for loaders and linkers written in other languages.
+//! The document has the following shape:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "functions": [
+//!     {
+//!       "func_index": 3,
+//!       "defined_index": 1,
+//!       "relocations": [
+//!         {
+//!           "offset": 17,
+//!           "kind": "x86_call_pc_rel4",
+//!           "target": { "type": "user_func", "func_index": 0 },
+//!           "addend": -4
+//!         }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! `func_index` is the index in the wasm function index space (imports
+//! included) and `defined_index` is the index among the locally-defined
+//! functions. `offset` is relative to the start of the function body.
+//!
+//! `target` is a tagged object whose `type` is one of `user_func` (with a
+//! `func_index`), `libcall` (with a `name`), `memory32_grow`,
+//! `imported_memory32_grow`, `memory32_size`, `imported_memory32_size`,
+//! `signature_table_base`, `memory_base` (with a `memory_index`), `ebb` or
+//! `jump_table`. The last two refer to the
+//! relocated function's own body, and have the `offset` of the EBB header
+//! or jump table within it.
+//!
+//! `version` is bumped whenever a field or tag is removed or changes meaning;
+//! adding new fields or tags does not bump it, so consumers should ignore
+//! what they don't recognize.
+
+use cranelift_codegen::binemit::Reloc;
+use cranelift_codegen::ir;
+use cranelift_entity::EntityRef;
+use std::io::Write;
+use wasmtime_environ::{Compilation, Module, Relocation, RelocationTarget, Relocations};
+
+/// Version of the JSON document layout.
+pub const RELOCS_JSON_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct RelocsDocument {
+    version: u32,
+    functions: Vec<FunctionRelocs>,
+}
+
+#[derive(Serialize)]
+struct FunctionRelocs {
+    func_index: usize,
+    defined_index: usize,
+    relocations: Vec<RelocEntry>,
+}
+
+#[derive(Serialize)]
+struct RelocEntry {
+    offset: u32,
+    kind: &'static str,
+    target: TargetEntry,
+    addend: i64,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TargetEntry {
+    UserFunc { func_index: usize },
+    LibCall { name: String },
+    Memory32Grow,
+    ImportedMemory32Grow,
+    Memory32Size,
+    ImportedMemory32Size,
+    SignatureTableBase,
+    MemoryBase { memory_index: usize },
+    Ebb { offset: u32 },
+    JumpTable { offset: u32 },
+}
+
+/// Returns the stable name used for a relocation kind in the JSON document.
+fn reloc_kind_name(reloc: Reloc) -> &'static str {
+    match reloc {
+        Reloc::Abs4 => "abs4",
+        Reloc::Abs8 => "abs8",
+        Reloc::X86PCRel4 => "x86_pc_rel4",
+        Reloc::X86CallPCRel4 => "x86_call_pc_rel4",
+        Reloc::X86CallPLTRel4 => "x86_call_plt_rel4",
+        Reloc::X86GOTPCRel4 => "x86_got_pc_rel4",
+        Reloc::Arm32Call => "arm32_call",
+        Reloc::Arm64Call => "arm64_call",
+        Reloc::RiscvCall => "riscv_call",
+    }
+}
+
+fn target_entry(target: RelocationTarget, jt_offsets: &ir::JumpTableOffsets) -> TargetEntry {
+    match target {
+        RelocationTarget::UserFunc(index) => TargetEntry::UserFunc {
+            func_index: index.index(),
+        },
+        RelocationTarget::LibCall(libcall) => TargetEntry::LibCall {
+            name: libcall.to_string(),
+        },
+        RelocationTarget::Memory32Grow => TargetEntry::Memory32Grow,
+        RelocationTarget::ImportedMemory32Grow => TargetEntry::ImportedMemory32Grow,
+        RelocationTarget::Memory32Size => TargetEntry::Memory32Size,
+        RelocationTarget::ImportedMemory32Size => TargetEntry::ImportedMemory32Size,
+        RelocationTarget::SignatureTableBase => TargetEntry::SignatureTableBase,
+        RelocationTarget::MemoryBase { memory_index } => TargetEntry::MemoryBase {
+            memory_index: memory_index.index(),
+        },
+        RelocationTarget::Ebb(offset) => TargetEntry::Ebb { offset },
+        RelocationTarget::JumpTable(jt) => TargetEntry::JumpTable {
+            offset: jt_offsets[jt],
+        },
+    }
+}
+
+fn reloc_entry(r: &Relocation, jt_offsets: &ir::JumpTableOffsets) -> RelocEntry {
+    RelocEntry {
+        offset: r.offset,
+        kind: reloc_kind_name(r.reloc),
+        target: target_entry(r.reloc_target, jt_offsets),
+        addend: r.addend,
+    }
+}
+
+/// Writes `relocations` as a versioned JSON document to `out`.
+pub fn write_relocations<W: Write>(
+    out: W,
+    module: &Module,
+    compilation: &Compilation,
+    relocations: &Relocations,
+) -> Result<(), String> {
+    let functions = relocations
+        .iter()
+        .map(|(i, relocs)| FunctionRelocs {
+            func_index: module.func_index(i).index(),
+            defined_index: i.index(),
+            relocations: relocs
+                .iter()
+                .map(|r| reloc_entry(r, &compilation.jt_offsets[i]))
+                .collect(),
+        })
+        .collect();
+    let document = RelocsDocument {
+        version: RELOCS_JSON_VERSION,
+        functions,
+    };
+    serde_json::to_writer_pretty(out, &document).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::settings;
+    use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+
+    #[test]
+    fn test_write_relocations() {
+        let data = wabt::wat2wasm(
+            r#"
+            (module
+              (import "env" "log" (func $log (param i32)))
+              (func (param i32) (result i32)
+                (call $double (get_local 0))
+              )
+              (func $double (param i32) (result i32)
+                (i32.add (get_local 0) (get_local 0))
+              )
+            )
+            "#,
+        )
+        .unwrap();
+        let isa_builder = cranelift_native::builder().unwrap();
+        let isa = isa_builder.finish(settings::Flags::new(settings::builder()));
+        let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+            .translate(&data)
+            .unwrap();
+        let (compilation, relocations, _, _, _) = cranelift::compile_module(
+            &translation.module,
+            translation.function_body_inputs,
+            &*isa,
+            false,
+        )
+        .unwrap();
+
+        let mut out = Vec::new();
+        write_relocations(&mut out, &translation.module, &compilation, &relocations).unwrap();
+        let document: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(document["version"], RELOCS_JSON_VERSION);
+
+        // Function indices count the import, defined indices don't.
+        let functions = document["functions"].as_array().unwrap();
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0]["func_index"], 1);
+        assert_eq!(functions[0]["defined_index"], 0);
+        assert_eq!(functions[1]["func_index"], 2);
+        assert_eq!(functions[1]["relocations"].as_array().unwrap().len(), 0);
+
+        let relocs = functions[0]["relocations"].as_array().unwrap();
+        assert_eq!(relocs.len(), 1);
+        assert_eq!(relocs[0]["target"]["type"], "user_func");
+        assert_eq!(relocs[0]["target"]["func_index"], 2);
+        assert!(relocs[0]["kind"].is_string());
+        assert!(relocs[0]["offset"].is_u64());
+    }
+}
@@ -0,0 +1,100 @@
+//! JSON report of per-function frame sizes and worst-case stack depths.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "functions": [
+//!     { "func_index": 1, "defined_index": 0, "frame_size": 16,
+//!       "max_stack_depth": 48, "recursive": false }
+//!   ],
+//!   "exports": [
+//!     { "name": "main", "func_index": 1, "max_stack_depth": 48, "recursive": false }
+//!   ]
+//! }
+//! ```
+//!
+//! `max_stack_depth` is the function's own frame plus the deepest chain of
+//! direct calls below it. When a recursive cycle is reachable there is no
+//! finite bound: `max_stack_depth` is `null` and `recursive` is `true`.
+//!
+//! Only direct calls between defined functions are followed. Calls to
+//! imported functions, `call_indirect` and runtime builtins such as
+//! `memory.grow` are not accounted for, so embedders should reserve
+//! additional headroom for them.
+
+use cranelift_entity::EntityRef;
+use std::io::Write;
+use wasmtime_environ::{worst_case_stack_depths, Export, FrameSizes, Module, Relocations};
+
+/// Version of the JSON document layout.
+pub const STACK_SIZES_JSON_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct StackSizesDocument {
+    version: u32,
+    functions: Vec<FunctionStackSize>,
+    exports: Vec<ExportStackSize>,
+}
+
+#[derive(Serialize)]
+struct FunctionStackSize {
+    func_index: usize,
+    defined_index: usize,
+    frame_size: u32,
+    max_stack_depth: Option<u64>,
+    recursive: bool,
+}
+
+#[derive(Serialize)]
+struct ExportStackSize {
+    name: String,
+    func_index: usize,
+    max_stack_depth: Option<u64>,
+    recursive: bool,
+}
+
+/// Writes the stack-size report for `module` to `out`.
+pub fn write_stack_sizes<W: Write>(
+    out: W,
+    module: &Module,
+    relocations: &Relocations,
+    frame_sizes: &FrameSizes,
+) -> Result<(), String> {
+    let depths = worst_case_stack_depths(module, relocations, frame_sizes);
+
+    let functions = frame_sizes
+        .iter()
+        .map(|(i, frame_size)| FunctionStackSize {
+            func_index: module.func_index(i).index(),
+            defined_index: i.index(),
+            frame_size: *frame_size,
+            max_stack_depth: depths[i],
+            recursive: depths[i].is_none(),
+        })
+        .collect();
+
+    let exports = module
+        .exports
+        .iter()
+        .filter_map(|(name, export)| match *export {
+            Export::Function(func_index) => {
+                module
+                    .defined_func_index(func_index)
+                    .map(|i| ExportStackSize {
+                        name: name.clone(),
+                        func_index: func_index.index(),
+                        max_stack_depth: depths[i],
+                        recursive: depths[i].is_none(),
+                    })
+            }
+            _ => None,
+        })
+        .collect();
+
+    let document = StackSizesDocument {
+        version: STACK_SIZES_JSON_VERSION,
+        functions,
+        exports,
+    };
+    serde_json::to_writer_pretty(out, &document).map_err(|e| e.to_string())
+}
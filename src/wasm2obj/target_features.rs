@@ -0,0 +1,94 @@
+//! Selection of ISA-specific target features, such as the instruction set
+//! extensions code may use.
+//!
+//! Features are given LLVM-style, as a comma-separated list of `+<feature>`
+//! to enable and `-<feature>` to disable, and map onto Cranelift's
+//! `has_<feature>` ISA settings.
+
+use cranelift_codegen::isa;
+use cranelift_codegen::settings::{Configurable, SetError};
+use target_lexicon::{Architecture, Triple};
+
+/// Returns the features Cranelift knows for `triple`.
+fn known_features(triple: &Triple) -> &'static [&'static str] {
+    match triple.architecture {
+        Architecture::I386 | Architecture::I586 | Architecture::I686 | Architecture::X86_64 => &[
+            "sse3", "ssse3", "sse41", "sse42", "popcnt", "bmi1", "bmi2", "lzcnt",
+        ],
+        _ => &[],
+    }
+}
+
+/// Enables and disables the features in the list `features` on
+/// `isa_builder`, which builds an ISA for `triple`.
+pub fn apply_target_features(
+    isa_builder: &mut isa::Builder,
+    triple: &Triple,
+    features: &str,
+) -> Result<(), String> {
+    for feature in features.split(',').filter(|feature| !feature.is_empty()) {
+        let (value, name) = match feature.chars().next() {
+            Some('+') => ("true", &feature[1..]),
+            Some('-') => ("false", &feature[1..]),
+            _ => {
+                return Err(format!(
+                    "expected +<feature> or -<feature>, got '{}'",
+                    feature
+                ))
+            }
+        };
+        isa_builder
+            .set(&format!("has_{}", name), value)
+            .map_err(|err| match err {
+                SetError::BadName(_) => {
+                    let known = known_features(triple);
+                    if known.is_empty() {
+                        format!("unknown target feature '{}' for {}", name, triple)
+                    } else {
+                        format!(
+                            "unknown target feature '{}' for {}; expected one of {}",
+                            name,
+                            triple,
+                            known.join(", ")
+                        )
+                    }
+                }
+                err => format!("target feature '{}': {}", name, err),
+            })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::settings;
+    use std::str::FromStr;
+
+    fn x86_64() -> Triple {
+        Triple::from_str("x86_64-unknown-linux-gnu").unwrap()
+    }
+
+    #[test]
+    fn test_apply_target_features() {
+        let triple = x86_64();
+        let mut isa_builder = isa::lookup(triple.clone()).unwrap();
+        apply_target_features(&mut isa_builder, &triple, "+sse41,+bmi2,-popcnt").unwrap();
+        let isa = isa_builder.finish(settings::Flags::new(settings::builder()));
+        let description = isa.to_string();
+        assert!(description.contains("has_sse41 = true"));
+        assert!(description.contains("has_bmi2 = true"));
+        assert!(description.contains("has_popcnt = false"));
+    }
+
+    #[test]
+    fn test_unknown_target_feature() {
+        let triple = x86_64();
+        let mut isa_builder = isa::lookup(triple.clone()).unwrap();
+        let err = apply_target_features(&mut isa_builder, &triple, "+avx512").unwrap_err();
+        assert!(err.contains("unknown target feature 'avx512'"));
+        assert!(err.contains("bmi2"));
+        let err = apply_target_features(&mut isa_builder, &triple, "sse41").unwrap_err();
+        assert!(err.contains("expected +<feature> or -<feature>"));
+    }
+}
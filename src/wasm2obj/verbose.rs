@@ -0,0 +1,148 @@
+//! Human-readable descriptions of a module and its compiled functions, for
+//! `--verbose`.
+//!
+//! Everything is formatted into a string first and printed in one go, so
+//! that the output isn't interleaved with other diagnostics.
+
+use cranelift_entity::EntityRef;
+use std::fmt::Write;
+use wasmtime_environ::{Compilation, Export, Module};
+
+/// Describes the signatures, imports and exports of `module`.
+pub fn describe_module(module: &Module) -> String {
+    let mut out = String::new();
+    writeln!(out, "signatures:").unwrap();
+    for (index, signature) in module.signatures.iter() {
+        writeln!(out, "  {}: {}", index.index(), signature).unwrap();
+    }
+
+    writeln!(out, "imports:").unwrap();
+    for (index, (module_name, field)) in module.imported_funcs.iter() {
+        writeln!(
+            out,
+            "  func {}: {}.{}, signature {}",
+            index.index(),
+            module_name,
+            field,
+            module.functions[index].index()
+        )
+        .unwrap();
+    }
+    for (index, (module_name, field)) in module.imported_tables.iter() {
+        writeln!(out, "  table {}: {}.{}", index.index(), module_name, field).unwrap();
+    }
+    for (index, (module_name, field)) in module.imported_memories.iter() {
+        writeln!(out, "  memory {}: {}.{}", index.index(), module_name, field).unwrap();
+    }
+    for (index, (module_name, field)) in module.imported_globals.iter() {
+        writeln!(out, "  global {}: {}.{}", index.index(), module_name, field).unwrap();
+    }
+
+    writeln!(out, "exports:").unwrap();
+    for (name, export) in &module.exports {
+        let (kind, index) = match *export {
+            Export::Function(index) => ("func", index.index()),
+            Export::Table(index) => ("table", index.index()),
+            Export::Memory(index) => ("memory", index.index()),
+            Export::Global(index) => ("global", index.index()),
+        };
+        writeln!(out, "  {}: {} {}", name, kind, index).unwrap();
+    }
+    out
+}
+
+/// Describes the Cranelift IR of each defined function, as captured in
+/// `compilation`, and why any failed to compile.
+pub fn describe_functions(module: &Module, compilation: &Compilation) -> String {
+    let mut out = String::new();
+    let ir = match compilation.ir {
+        Some(ref ir) => ir,
+        None => return out,
+    };
+    for (i, text) in ir.iter() {
+        let func_index = module.func_index(i).index();
+        match compilation.failed.iter().find(|failed| failed.index == i) {
+            Some(failed) => {
+                writeln!(out, "function {}: failed: {}", func_index, failed.error).unwrap()
+            }
+            None => writeln!(out, "function {}:\n{}", func_index, text).unwrap(),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::settings;
+    use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+
+    const MODULE: &str = r#"
+        (module
+          (import "env" "log" (func $log (param i32)))
+          (memory (export "memory") 1)
+          (func (export "run") (param i32)
+            (call $log (get_local 0))
+          )
+        )
+        "#;
+
+    #[test]
+    fn test_describe_module() {
+        let data = wabt::wat2wasm(MODULE).unwrap();
+        let isa_builder = cranelift_native::builder().unwrap();
+        let isa = isa_builder.finish(settings::Flags::new(settings::builder()));
+        let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+            .translate(&data)
+            .unwrap();
+
+        let description = describe_module(&translation.module);
+        let lines: Vec<&str> = description.lines().collect();
+        assert_eq!(lines[0], "signatures:");
+        assert!(lines[1].starts_with("  0: "));
+        assert_eq!(lines[2], "imports:");
+        assert_eq!(lines[3], "  func 0: env.log, signature 0");
+        assert_eq!(lines[4], "exports:");
+        assert!(lines.contains(&"  memory: memory 0"));
+        assert!(lines.contains(&"  run: func 1"));
+        assert_eq!(lines.len(), 7);
+    }
+
+    #[test]
+    fn test_describe_functions() {
+        let data = wabt::wat2wasm(MODULE).unwrap();
+        let isa_builder = cranelift_native::builder().unwrap();
+        let isa = isa_builder.finish(settings::Flags::new(settings::builder()));
+        let compile = |capture_ir, max_code_size| {
+            let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+                .translate(&data)
+                .unwrap();
+            let (compilation, _, _, _, _) = cranelift::compile_module_with_options(
+                &translation.module,
+                translation.function_body_inputs,
+                &*isa,
+                false,
+                &cranelift::CompileOptions {
+                    capture_ir,
+                    allow_failures: true,
+                    max_code_size,
+                    ..cranelift::CompileOptions::default()
+                },
+            )
+            .unwrap();
+            describe_functions(&translation.module, &compilation)
+        };
+
+        // Without the IR there is nothing to describe.
+        assert_eq!(compile(false, None), "");
+
+        // Functions are numbered in the module's index space, after the
+        // import.
+        let description = compile(true, None);
+        assert!(description.starts_with("function 1:\nfunction "));
+
+        let description = compile(true, Some(0));
+        assert!(description.starts_with("function 1: failed: "));
+        assert_eq!(description.lines().count(), 1);
+    }
+}
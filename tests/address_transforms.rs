@@ -0,0 +1,39 @@
+use cranelift_entity::EntityRef;
+use wasmtime_environ::{ModuleEnvironment, Tunables};
+
+mod common;
+
+use common::{compile_with, native_isa, read_wat, PATH_MODULE_ARITH};
+
+/// For a module without a name section or DWARF, the address transforms
+/// only hold the offsets of the function's own wasm instructions: the
+/// prologue and epilogue, which have no source location, are left out
+/// instead of producing line rows at offset 0.
+#[test]
+fn test_address_transforms_without_source_info() {
+    let data = read_wat(PATH_MODULE_ARITH);
+    let isa = native_isa(&[]);
+
+    let bodies = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation")
+        .function_body_inputs
+        .values()
+        .map(|input| (input.module_offset, input.data.len()))
+        .collect::<Vec<_>>();
+    let (compilation, _, address_transforms, _, _) =
+        compile_with(&data, &*isa, Tunables::default(), true, &Default::default())
+            .expect("compilation");
+
+    for (i, transform) in address_transforms.iter() {
+        let (module_offset, len) = bodies[i.index()];
+        let body = &compilation.functions[i];
+        assert!(!transform.locations.is_empty());
+        for location in &transform.locations {
+            assert!(!location.srcloc.is_default());
+            let srcloc = location.srcloc.bits() as usize;
+            assert!(module_offset <= srcloc && srcloc < module_offset + len);
+            assert!(location.code_offset + location.code_len <= body.len());
+        }
+    }
+}
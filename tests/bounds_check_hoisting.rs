@@ -0,0 +1,100 @@
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::settings::{self, Configurable};
+use wabt;
+use wasmtime_environ::{cranelift, Tunables};
+
+mod common;
+
+use common::{compile_with, read_wat, Compiled, PATH_MODULE_ARITH};
+
+/// Sums the first `n` words of memory from the byte offset `4 * n`, an
+/// address computation with a loop-invariant part that can be hoisted out of
+/// the loop.
+const WAT: &str = r#"
+(module
+  (memory 1)
+  (func (param $n i32) (result i32)
+    (local $i i32)
+    (local $sum i32)
+    (block $done
+      (loop $top
+        (br_if $done (i32.ge_u (get_local $i) (get_local $n)))
+        (set_local $sum (i32.add (get_local $sum) (i32.load (i32.add (get_local $i) (i32.shl (get_local $n) (i32.const 2))))))
+        (set_local $i (i32.add (get_local $i) (i32.const 4)))
+        (br $top)
+      )
+    )
+    (get_local $sum)
+  )
+)
+"#;
+
+/// A native ISA compiling at `opt_level`.
+fn native_isa_at(opt_level: &str) -> Box<dyn TargetIsa> {
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("enable_verifier").unwrap();
+    flag_builder.set("opt_level", opt_level).unwrap();
+    let isa_builder = cranelift_native::builder().unwrap_or_else(|_| {
+        panic!("host machine is not a supported target");
+    });
+    isa_builder.finish(settings::Flags::new(flag_builder))
+}
+
+fn compile_hoisting(data: &[u8], opt_level: &str, bounds_check_hoisting: bool) -> Compiled {
+    let isa = native_isa_at(opt_level);
+    compile_with(
+        data,
+        &*isa,
+        Tunables::default(),
+        false,
+        &cranelift::CompileOptions {
+            bounds_check_hoisting,
+            ..cranelift::CompileOptions::default()
+        },
+    )
+    .expect("compilation")
+}
+
+/// Hoisting is on by default.
+#[test]
+fn test_bounds_check_hoisting_default() {
+    assert!(cranelift::CompileOptions::default().bounds_check_hoisting);
+}
+
+/// Turning hoisting off changes the code of a loop accessing memory at
+/// `opt_level=best`, where Cranelift runs loop-invariant code motion.
+#[test]
+fn test_bounds_check_hoisting_loop() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let (hoisted, _, _, _, _) = compile_hoisting(&data, "best", true);
+    let (unhoisted, _, _, _, _) = compile_hoisting(&data, "best", false);
+    assert_ne!(
+        hoisted.functions.values().collect::<Vec<_>>(),
+        unhoisted.functions.values().collect::<Vec<_>>()
+    );
+}
+
+/// Below `opt_level=best` Cranelift doesn't run the pass, so the setting
+/// changes nothing.
+#[test]
+fn test_bounds_check_hoisting_default_opt_level() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let (hoisted, _, _, _, _) = compile_hoisting(&data, "default", true);
+    let (unhoisted, _, _, _, _) = compile_hoisting(&data, "default", false);
+    assert_eq!(
+        hoisted.functions.values().collect::<Vec<_>>(),
+        unhoisted.functions.values().collect::<Vec<_>>()
+    );
+}
+
+/// Code without loops has nothing to hoist.
+#[test]
+fn test_bounds_check_hoisting_no_loop() {
+    let data = read_wat(PATH_MODULE_ARITH);
+    let (hoisted, _, _, _, _) = compile_hoisting(&data, "best", true);
+    let (unhoisted, _, _, _, _) = compile_hoisting(&data, "best", false);
+    assert_eq!(
+        hoisted.functions.values().collect::<Vec<_>>(),
+        unhoisted.functions.values().collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,60 @@
+use wasmtime_environ::{cranelift, CompileError, Tunables};
+
+mod common;
+
+use common::{compile_with, native_isa, read_wat, PATH_MODULE_ARITH};
+
+/// Functions with more code than `--max-function-code-size` allows fail to
+/// compile, or are left to the interpreter when failures are allowed.
+#[test]
+fn test_max_function_code_size() {
+    let data = read_wat(PATH_MODULE_ARITH);
+    let isa = native_isa(&[]);
+
+    let compile = |allow_failures, max_code_size| {
+        compile_with(
+            &data,
+            &*isa,
+            Tunables::default(),
+            false,
+            &cranelift::CompileOptions {
+                allow_failures,
+                max_code_size,
+                ..cranelift::CompileOptions::default()
+            },
+        )
+    };
+
+    let (compilation, _, _, _, _) = compile(false, None).expect("compilation");
+    let largest = compilation.functions.values().map(Vec::len).max().unwrap();
+    assert!(compile(false, Some(largest)).is_ok());
+
+    let error = match compile(false, Some(largest - 1)) {
+        Err(error) => error,
+        Ok(_) => panic!("expected the code size limit to be exceeded"),
+    };
+    match error {
+        CompileError::FunctionTooLarge {
+            actual,
+            limit,
+            size_estimate,
+            ..
+        } => {
+            assert_eq!(actual, Some(largest));
+            assert_eq!(limit, largest - 1);
+            assert!(size_estimate > 0);
+        }
+        ref error => panic!("unexpected error: {}", error),
+    }
+    assert!(error
+        .to_string()
+        .contains(&format!("its code is {} bytes, over the limit", largest)));
+
+    let (compilation, _, _, _, _) = compile(true, Some(largest - 1)).expect("compilation");
+    assert!(!compilation.failed.is_empty());
+    for failed in &compilation.failed {
+        assert!(compilation.functions[failed.index].is_empty());
+        // The body is kept for the interpreter, ending with `end`.
+        assert_eq!(failed.wasm_body.last(), Some(&0x0b));
+    }
+}
@@ -0,0 +1,66 @@
+use target_lexicon::BinaryFormat;
+use wabt;
+use wasmtime_obj::COMMENT_SECTION;
+use wasmtime_tools::obj::{compile_to_object_with_options, ObjectOptions};
+
+mod common;
+
+use common::{isa_for, section, x86_64_linux};
+
+/// The image of an object for `data`, with a `.comment` section if
+/// `comment_section` is set.
+#[cfg(test)]
+fn object(data: &[u8], comment_section: bool) -> Vec<u8> {
+    let options = ObjectOptions {
+        isa: Some(isa_for(x86_64_linux())),
+        comment_section,
+        ..ObjectOptions::default()
+    };
+    compile_to_object_with_options(data, options)
+        .expect("object")
+        .into_image()
+        .expect("image")
+}
+
+/// Objects record the target and settings they were compiled for when
+/// asked to.
+#[test]
+fn test_comment_section() {
+    let data = wabt::wat2wasm("(module (func))").expect("expecting valid wat");
+    let bytes = object(&data, true);
+
+    let comment = section(&bytes, COMMENT_SECTION)
+        .expect("comment section")
+        .data;
+    assert_eq!(comment[0], 0);
+    assert_eq!(comment[comment.len() - 1], 0);
+    let strings = comment[1..comment.len() - 1]
+        .split(|b| *b == 0)
+        .map(|s| String::from_utf8(s.to_vec()).unwrap())
+        .collect::<Vec<_>>();
+    assert!(strings[0].contains("cranelift"));
+    assert!(strings.contains(&String::from("target: x86_64-unknown-linux-gnu")));
+    assert!(strings.iter().any(|s| s.starts_with("opt_level: ")));
+
+    assert!(section(&object(&data, false), COMMENT_SECTION).is_none());
+}
+
+/// Mach-O objects get the section too, as `__comment` in the `__DWARF`
+/// segment.
+#[test]
+fn test_comment_section_macho() {
+    let data = wabt::wat2wasm("(module (func))").expect("expecting valid wat");
+    let options = ObjectOptions {
+        isa: Some(isa_for(x86_64_linux())),
+        binary_format: Some(BinaryFormat::Macho),
+        comment_section: true,
+        ..ObjectOptions::default()
+    };
+    let bytes = compile_to_object_with_options(&data, options)
+        .expect("object")
+        .into_image()
+        .expect("image");
+    assert!(bytes
+        .windows(16)
+        .any(|name| name == b"__comment\0\0\0\0\0\0\0"));
+}
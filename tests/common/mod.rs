@@ -0,0 +1,318 @@
+//! Setup shared by the integration tests: reading the modules in
+//! `filetests`, building ISAs, compiling modules, and reading back the ELF
+//! objects written for them.
+
+// Each test crate only uses some of these.
+#![allow(dead_code)]
+
+use cranelift_codegen::isa::{self, TargetIsa};
+use cranelift_codegen::settings::{self, Configurable};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output};
+use std::str::FromStr;
+use target_lexicon::Triple;
+use wabt;
+use wasmtime_environ::{
+    cranelift, AddressTransforms, Compilation, CompileError, FrameSizes, ModuleEnvironment,
+    Relocations, TrapInformation, Tunables,
+};
+
+pub const PATH_MODULE_ARITH: &str = r"filetests/arith.wat";
+pub const PATH_MODULE_BR_TABLE: &str = r"filetests/br_table.wat";
+pub const PATH_MODULE_CALL: &str = r"filetests/call.wat";
+pub const PATH_MODULE_CALL_INDIRECT: &str = r"filetests/call_indirect.wat";
+pub const PATH_MODULE_FIBONACCI: &str = r"filetests/fibonacci.wat";
+pub const PATH_MODULE_IMPORTED_GLOBAL: &str = r"filetests/imported_global.wat";
+pub const PATH_MODULE_MANY_FUNCTIONS: &str = r"filetests/many_functions.wat";
+pub const PATH_MODULE_MEMORY: &str = r"filetests/memory.wat";
+pub const PATH_MODULE_NANS: &str = r"filetests/nans.wat";
+pub const PATH_MODULE_TRAP_LOAD_BOUNDS: &str = r"filetests/trap_load_bounds.wat";
+
+/// Reads the text module at `path` and converts it to a binary one.
+pub fn read_wat(path: &str) -> Vec<u8> {
+    let wat_data = fs::read(path).unwrap();
+    wabt::wat2wasm(wat_data).expect("expecting valid wat-file")
+}
+
+/// Creates an empty temporary directory for the test `name`.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The `wasm2obj` binary cargo builds for the integration tests, in the
+/// target directory above the `deps` directory they run from.
+fn wasm2obj_bin() -> PathBuf {
+    let mut path = env::current_exe().unwrap();
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.join(format!("wasm2obj{}", env::consts::EXE_SUFFIX))
+}
+
+/// Writes the module `data` to `dir` and runs `wasm2obj` on it with `args`,
+/// returning the path of the object it wrote.
+pub fn wasm2obj(dir: &Path, data: &[u8], args: &[&str]) -> PathBuf {
+    let input = dir.join("module.wasm");
+    let output = dir.join("module.o");
+    fs::write(&input, data).unwrap();
+    let status = Command::new(wasm2obj_bin())
+        .args(args)
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .status()
+        .expect("running wasm2obj");
+    assert!(status.success());
+    output
+}
+
+/// Writes the module `data` to `dir` and runs `wasm2obj --check` on it with
+/// `args`, returning what it printed and how it exited.
+pub fn wasm2obj_check(dir: &Path, data: &[u8], args: &[&str]) -> Output {
+    let input = dir.join("module.wasm");
+    fs::write(&input, data).unwrap();
+    Command::new(wasm2obj_bin())
+        .args(args)
+        .arg("--check")
+        .arg(&input)
+        .output()
+        .expect("running wasm2obj")
+}
+
+/// Writes the module `data` to `dir` and runs `wasm2obj -o -` on it with
+/// `args`, returning the object it wrote to stdout, what it printed and how
+/// it exited.
+pub fn wasm2obj_stdout(dir: &Path, data: &[u8], args: &[&str]) -> Output {
+    let input = dir.join("module.wasm");
+    fs::write(&input, data).unwrap();
+    Command::new(wasm2obj_bin())
+        .args(args)
+        .arg(&input)
+        .args(&["-o", "-"])
+        .output()
+        .expect("running wasm2obj")
+}
+
+/// Compiles `c_source` and links it with `object` into a program in `dir`,
+/// passing `cc_args` to the C compiler, and runs the program. Returns
+/// `None` if there is no C compiler to link with.
+pub fn link_and_run(
+    dir: &Path,
+    c_source: &str,
+    object: &Path,
+    cc_args: &[&str],
+) -> Option<ExitStatus> {
+    let source = dir.join("main.c");
+    let program = dir.join("main");
+    fs::write(&source, c_source).unwrap();
+    let linked = Command::new("cc")
+        .args(cc_args)
+        .arg(&source)
+        .arg(object)
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .ok()?;
+    assert!(linked.success(), "linking {} failed", object.display());
+    Some(
+        Command::new(&program)
+            .status()
+            .expect("running the program"),
+    )
+}
+
+/// The triple most object tests are written for.
+pub fn x86_64_linux() -> Triple {
+    Triple::from_str("x86_64-unknown-linux-gnu").unwrap()
+}
+
+/// An ISA for the host with the verifier and each of `flags` enabled.
+pub fn native_isa(flags: &[&str]) -> Box<dyn TargetIsa> {
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("enable_verifier").unwrap();
+    for flag in flags {
+        flag_builder.enable(flag).unwrap();
+    }
+    let isa_builder = cranelift_native::builder().unwrap_or_else(|_| {
+        panic!("host machine is not a supported target");
+    });
+    isa_builder.finish(settings::Flags::new(flag_builder))
+}
+
+/// An ISA for `triple` with the default settings, which for x86-64 means
+/// without any of the optional instruction set extensions.
+pub fn isa_for(triple: Triple) -> Box<dyn TargetIsa> {
+    isa::lookup(triple)
+        .unwrap()
+        .finish(settings::Flags::new(settings::builder()))
+}
+
+/// Everything `compile_module` returns.
+pub type Compiled = (
+    Compilation,
+    Relocations,
+    AddressTransforms,
+    FrameSizes,
+    TrapInformation,
+);
+
+/// Translates `data` with `tunables` and compiles it with `options`.
+pub fn compile_with(
+    data: &[u8],
+    isa: &dyn TargetIsa,
+    tunables: Tunables,
+    generate_debug_info: bool,
+    options: &cranelift::CompileOptions,
+) -> Result<Compiled, CompileError> {
+    let translation = ModuleEnvironment::new(isa.frontend_config(), tunables)
+        .translate(data)
+        .expect("translation");
+    cranelift::compile_module_with_options(
+        &translation.module,
+        translation.function_body_inputs,
+        isa,
+        generate_debug_info,
+        options,
+    )
+}
+
+/// Translates and compiles `data` with the default tunables and options.
+pub fn compile(data: &[u8], isa: &dyn TargetIsa) -> Compiled {
+    compile_with(
+        data,
+        isa,
+        Tunables::default(),
+        false,
+        &cranelift::CompileOptions::default(),
+    )
+    .expect("compilation")
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from(bytes[at]) | u16::from(bytes[at + 1]) << 8
+}
+
+pub fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from(read_u16(bytes, at)) | u32::from(read_u16(bytes, at + 2)) << 16
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from(read_u32(bytes, at)) | u64::from(read_u32(bytes, at + 4)) << 32
+}
+
+fn read_str(bytes: &[u8], start: usize) -> String {
+    let len = bytes[start..].iter().position(|b| *b == 0).unwrap();
+    String::from_utf8(bytes[start..start + len].to_vec()).unwrap()
+}
+
+/// `SHT_PROGBITS` section type.
+pub const SHT_PROGBITS: u32 = 1;
+
+/// `SHT_SYMTAB` section type.
+pub const SHT_SYMTAB: u32 = 2;
+
+/// `SHT_RELA` section type.
+pub const SHT_RELA: u32 = 4;
+
+/// `SHT_NOBITS` section type.
+pub const SHT_NOBITS: u32 = 8;
+
+/// `SHF_WRITE` section flag.
+pub const SHF_WRITE: u64 = 0x1;
+
+/// `SHF_ALLOC` section flag.
+pub const SHF_ALLOC: u64 = 0x2;
+
+/// A section of an ELF image.
+pub struct Section<'a> {
+    pub name: String,
+    pub sh_type: u32,
+    pub sh_flags: u64,
+    pub sh_link: u32,
+    pub sh_addralign: u64,
+    pub data: &'a [u8],
+}
+
+/// Reads the sections of a little-endian ELF64 image.
+pub fn sections(bytes: &[u8]) -> Vec<Section<'_>> {
+    let shoff = read_u64(bytes, 0x28) as usize;
+    let shentsize = read_u16(bytes, 0x3A) as usize;
+    let shnum = read_u16(bytes, 0x3C) as usize;
+    let shstrndx = read_u16(bytes, 0x3E) as usize;
+    let strtab = read_u64(bytes, shoff + shstrndx * shentsize + 0x18) as usize;
+    (0..shnum)
+        .map(|index| {
+            let header = shoff + index * shentsize;
+            let sh_type = read_u32(bytes, header + 4);
+            let offset = read_u64(bytes, header + 0x18) as usize;
+            let size = read_u64(bytes, header + 0x20) as usize;
+            Section {
+                name: read_str(bytes, strtab + read_u32(bytes, header) as usize),
+                sh_type,
+                sh_flags: read_u64(bytes, header + 0x8),
+                sh_link: read_u32(bytes, header + 0x28),
+                sh_addralign: read_u64(bytes, header + 0x30),
+                data: if sh_type == SHT_NOBITS {
+                    &[]
+                } else {
+                    &bytes[offset..offset + size]
+                },
+            }
+        })
+        .collect()
+}
+
+/// Reads the section `name` of a little-endian ELF64 image.
+pub fn section<'a>(bytes: &'a [u8], name: &str) -> Option<Section<'a>> {
+    sections(bytes).into_iter().find(|s| s.name == name)
+}
+
+/// A symbol of an ELF image.
+pub struct Symbol {
+    pub name: String,
+    pub info: u8,
+    pub other: u8,
+    pub shndx: u16,
+    pub value: u64,
+}
+
+impl Symbol {
+    /// Whether the symbol is `STB_GLOBAL` and defined in the image.
+    pub fn is_defined_global(&self) -> bool {
+        self.info >> 4 == 1 && self.shndx != 0
+    }
+}
+
+/// Reads the symbols of a little-endian ELF64 image. Section symbols have
+/// no name of their own, and are given that of their section.
+pub fn symbols(bytes: &[u8]) -> Vec<Symbol> {
+    let sections = sections(bytes);
+    let mut symbols = Vec::new();
+    for symtab in sections.iter().filter(|s| s.sh_type == SHT_SYMTAB) {
+        let strtab = sections[symtab.sh_link as usize].data;
+        for sym in symtab.data.chunks(24) {
+            let info = sym[4];
+            let shndx = read_u16(sym, 6);
+            // STT_SECTION
+            let name = if info & 0xf == 3 {
+                sections[shndx as usize].name.clone()
+            } else {
+                read_str(strtab, read_u32(sym, 0) as usize)
+            };
+            symbols.push(Symbol {
+                name,
+                info,
+                other: sym[5],
+                shndx,
+                value: read_u64(sym, 8),
+            });
+        }
+    }
+    symbols
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::{env, fs, process};
+use wasmtime_environ::{cranelift, CompilationCache, FileSystemCache, Tunables};
+
+mod common;
+
+use common::{
+    compile_with, native_isa, read_wat, Compiled, PATH_MODULE_CALL_INDIRECT, PATH_MODULE_MEMORY,
+};
+
+/// An in-memory cache counting its hits.
+#[derive(Default)]
+struct CountingCache {
+    entries: Mutex<HashMap<Vec<u8>, Vec<u8>>>,
+    hits: AtomicUsize,
+}
+
+impl CompilationCache for CountingCache {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let value = self.entries.lock().unwrap().get(key).cloned();
+        if value.is_some() {
+            self.hits.fetch_add(1, Ordering::SeqCst);
+        }
+        value
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_vec(), value.to_vec());
+    }
+}
+
+#[cfg(test)]
+fn compile(generate_debug_info: bool, cache: &dyn CompilationCache) -> Compiled {
+    let data = read_wat(PATH_MODULE_CALL_INDIRECT);
+    compile_with(
+        &data,
+        &*native_isa(&[]),
+        Tunables::default(),
+        generate_debug_info,
+        &cranelift::CompileOptions {
+            cache: Some(cache),
+            ..cranelift::CompileOptions::default()
+        },
+    )
+    .expect("compilation")
+}
+
+/// Compiling a module again takes every function from the cache, with the
+/// same results, even when only debug info is added.
+#[test]
+fn test_compilation_cache() {
+    let cache = CountingCache::default();
+    let (compilation, relocations, _, frame_sizes, traps) = compile(false, &cache);
+    assert_eq!(cache.hits.load(Ordering::SeqCst), 0);
+    assert_eq!(
+        cache.entries.lock().unwrap().len(),
+        compilation.functions.len()
+    );
+
+    let (cached, cached_relocations, address_transforms, cached_frame_sizes, cached_traps) =
+        compile(true, &cache);
+    assert_eq!(
+        cache.hits.load(Ordering::SeqCst),
+        compilation.functions.len()
+    );
+    assert_eq!(
+        cached.functions.values().collect::<Vec<_>>(),
+        compilation.functions.values().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        format!("{:?}", cached_relocations),
+        format!("{:?}", relocations)
+    );
+    assert_eq!(
+        cached_frame_sizes.values().collect::<Vec<_>>(),
+        frame_sizes.values().collect::<Vec<_>>()
+    );
+    assert_eq!(format!("{:?}", cached_traps), format!("{:?}", traps));
+    assert_eq!(address_transforms.len(), compilation.functions.len());
+    for (i, transform) in address_transforms.iter() {
+        assert_eq!(transform.body_len, compilation.functions[i].len());
+        assert!(!transform.locations.is_empty());
+    }
+}
+
+/// Keys are SHA-1 digests that change with everything the code depends
+/// on, such as the tunables and whether bounds checks are hoisted.
+#[test]
+fn test_compilation_cache_keys() {
+    let data = read_wat(PATH_MODULE_MEMORY);
+    let isa = native_isa(&[]);
+    let keys = |tunables: Tunables, bounds_check_hoisting: bool| {
+        let cache = CountingCache::default();
+        compile_with(
+            &data,
+            &*isa,
+            tunables,
+            false,
+            &cranelift::CompileOptions {
+                cache: Some(&cache),
+                bounds_check_hoisting,
+                ..cranelift::CompileOptions::default()
+            },
+        )
+        .expect("compilation");
+        let mut keys = cache
+            .entries
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys
+    };
+
+    let default_keys = keys(Tunables::default(), true);
+    assert!(!default_keys.is_empty());
+    for key in &default_keys {
+        assert_eq!(key.len(), 20);
+    }
+    assert_eq!(keys(Tunables::default(), true), default_keys);
+
+    // The module's memory has no maximum, so it is dynamic.
+    let unguarded = Tunables {
+        dynamic_memory_offset_guard_size: 0,
+        ..Tunables::default()
+    };
+    let unguarded_keys = keys(unguarded, true);
+    assert_eq!(unguarded_keys.len(), default_keys.len());
+    assert!(unguarded_keys.iter().all(|key| !default_keys.contains(key)));
+
+    let unhoisted_keys = keys(Tunables::default(), false);
+    assert!(unhoisted_keys.iter().all(|key| !default_keys.contains(key)));
+}
+
+/// The file system cache gives back the code it stored.
+#[test]
+fn test_file_system_cache() {
+    let dir = env::temp_dir().join(format!("wasmtime-cache-test-{}", process::id()));
+    let cache = FileSystemCache::new(&dir);
+    assert_eq!(cache.get(b"key"), None);
+    cache.put(b"key", b"value");
+    assert_eq!(cache.get(b"key"), Some(b"value".to_vec()));
+    assert_eq!(cache.get(b"other key"), None);
+    // Entries are named by the SHA-1 digest of their key, in hex.
+    for entry in fs::read_dir(&dir).unwrap() {
+        let name = entry.unwrap().file_name().into_string().unwrap();
+        assert_eq!(name.len(), 40);
+        assert!(name.bytes().all(|b| b.is_ascii_hexdigit()));
+    }
+
+    let (compilation, _, _, _, _) = compile(false, &cache);
+    let (cached, _, _, _, _) = compile(false, &cache);
+    assert_eq!(
+        cached.functions.values().collect::<Vec<_>>(),
+        compilation.functions.values().collect::<Vec<_>>()
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
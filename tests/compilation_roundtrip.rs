@@ -0,0 +1,127 @@
+use faerie::Artifact;
+use serde_json;
+use wabt;
+use wasmtime_environ::{
+    cranelift, Compilation, ModuleEnvironment, Relocations, SerializedCompilation, Tunables,
+};
+use wasmtime_obj::{emit_module, EmitOptions};
+
+mod common;
+
+use common::{isa_for, x86_64_linux};
+
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (import "env" "log" (func $log (param i32)))
+  (memory 1)
+  (func $double (param i32) (result i32)
+    (i32.add (get_local 0) (get_local 0))
+  )
+  (func (export "main") (param i32) (result i32)
+    (call $log (get_local 0))
+    (block
+      (block
+        (br_table 0 1 (get_local 0))
+      )
+    )
+    (call $double (i32.load (get_local 0)))
+  )
+  (data (i32.const 0) "hello")
+)
+"#;
+
+/// A compiled module written out and read back emits the same object as
+/// the module it was compiled from.
+#[test]
+fn test_compilation_roundtrip() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+
+    let triple = x86_64_linux();
+    let isa = isa_for(triple.clone());
+
+    let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation");
+    let (compilation, relocations, address_transforms, frame_sizes, traps) =
+        cranelift::compile_module_with_options(
+            &translation.module,
+            translation.function_body_inputs,
+            &*isa,
+            true,
+            &cranelift::CompileOptions {
+                capture_ir: true,
+                collect_stats: true,
+                ..cranelift::CompileOptions::default()
+            },
+        )
+        .expect("compilation");
+
+    let module = &translation.module;
+    let data_initializers = &translation.data_initializers;
+    let target_config = &translation.target_config;
+    let emit = |compilation: &Compilation, relocations: &Relocations| {
+        let mut obj = Artifact::new(triple.clone(), String::from("roundtrip.o"));
+        emit_module(
+            &mut obj,
+            module,
+            compilation,
+            relocations,
+            data_initializers,
+            target_config,
+            &EmitOptions::default(),
+        )
+        .expect("emit module");
+        obj.emit().expect("object")
+    };
+    let expected = emit(&compilation, &relocations);
+    let ir = compilation
+        .ir
+        .as_ref()
+        .unwrap()
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    let frame_size_values = frame_sizes.values().cloned().collect::<Vec<_>>();
+    let trap_count = traps.len();
+    let transform_count = address_transforms.len();
+
+    let serialized = serde_json::to_string(
+        &SerializedCompilation::new(
+            compilation,
+            relocations,
+            address_transforms,
+            frame_sizes,
+            traps,
+        )
+        .expect("serializable"),
+    )
+    .expect("serialization");
+    let (decoded, decoded_relocations, decoded_transforms, decoded_frame_sizes, decoded_traps) =
+        serde_json::from_str::<SerializedCompilation>(&serialized)
+            .expect("deserialization")
+            .into_parts()
+            .expect("same version");
+
+    assert_eq!(
+        decoded
+            .ir
+            .as_ref()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>(),
+        ir
+    );
+    assert_eq!(decoded_transforms.len(), transform_count);
+    assert_eq!(
+        decoded_frame_sizes.values().cloned().collect::<Vec<_>>(),
+        frame_size_values
+    );
+    assert_eq!(decoded_traps.len(), trap_count);
+    assert!(
+        serde_json::from_str::<SerializedCompilation>(&serialized[..serialized.len() - 1]).is_err()
+    );
+
+    assert_eq!(emit(&decoded, &decoded_relocations), expected);
+}
@@ -0,0 +1,91 @@
+use cranelift_entity::EntityRef;
+use cranelift_wasm::DefinedFuncIndex;
+use wabt;
+use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+
+mod common;
+
+use common::native_isa;
+
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (func (result i32) (i32.const 0))
+  (func (result i32) (i32.const 1))
+  (func (param i32) (result i32)
+    (i32.mul (get_local 0) (i32.const 3))
+  )
+  (func (result i32) (i32.const 0))
+  (func (result i32) (i32.const 4))
+)
+"#;
+
+/// Only the selected function gets code; the others, including one with
+/// the same body as an earlier function, are left empty.
+#[test]
+fn test_compile_functions() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+
+    let isa = native_isa(&[]);
+
+    let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation");
+    let (compilation, relocations, _, _, traps) = cranelift::compile_functions(
+        &translation.module,
+        translation.function_body_inputs,
+        &*isa,
+        false,
+        &cranelift::CompileOptions {
+            capture_ir: true,
+            ..cranelift::CompileOptions::default()
+        },
+        &[DefinedFuncIndex::new(2)],
+    )
+    .expect("compilation");
+
+    assert_eq!(compilation.functions.len(), 5);
+    assert!(compilation.duplicates.is_empty());
+    let ir = compilation.ir.as_ref().unwrap();
+    for (i, body) in compilation.functions.iter() {
+        if i.index() == 2 {
+            assert!(!body.is_empty());
+            assert!(!ir[i].is_empty());
+        } else {
+            assert!(body.is_empty());
+            assert!(ir[i].is_empty());
+            assert!(relocations[i].is_empty());
+            assert!(traps[i].is_empty());
+        }
+    }
+}
+
+/// The other options apply to the selected functions.
+#[test]
+fn test_compile_functions_with_options() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+
+    let isa = native_isa(&[]);
+
+    let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation");
+    let (compilation, _, _, _, _) = cranelift::compile_functions(
+        &translation.module,
+        translation.function_body_inputs,
+        &*isa,
+        false,
+        &cranelift::CompileOptions {
+            allow_failures: true,
+            max_code_size: Some(1),
+            collect_stats: true,
+            ..cranelift::CompileOptions::default()
+        },
+        &[DefinedFuncIndex::new(2)],
+    )
+    .expect("compilation");
+
+    assert_eq!(compilation.failed.len(), 1);
+    assert_eq!(compilation.failed[0].index, DefinedFuncIndex::new(2));
+    assert_eq!(compilation.stats.as_ref().unwrap().len(), 5);
+}
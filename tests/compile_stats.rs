@@ -0,0 +1,37 @@
+use wasmtime_environ::{cranelift, Tunables};
+
+mod common;
+
+use common::{compile_with, native_isa, read_wat, PATH_MODULE_CALL};
+
+/// Statistics are only collected when asked for, and then cover every
+/// function.
+#[test]
+fn test_compile_stats() {
+    let data = read_wat(PATH_MODULE_CALL);
+    let isa = native_isa(&[]);
+    let compile = |collect_stats| {
+        let (compilation, _, _, _, _) = compile_with(
+            &data,
+            &*isa,
+            Tunables::default(),
+            false,
+            &cranelift::CompileOptions {
+                collect_stats,
+                ..cranelift::CompileOptions::default()
+            },
+        )
+        .expect("compilation");
+        compilation
+    };
+
+    assert!(compile(false).stats.is_none());
+
+    let compilation = compile(true);
+    let stats = compilation.stats.expect("stats");
+    assert_eq!(stats.len(), 2);
+    for (i, body) in compilation.functions.iter() {
+        assert_ne!(stats[i].code_size, 0);
+        assert_eq!(stats[i].code_size, body.len());
+    }
+}
@@ -0,0 +1,112 @@
+use cranelift_entity::EntityRef;
+use cranelift_wasm::DefinedFuncIndex;
+use wabt;
+use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+
+mod common;
+
+use common::{compile, native_isa};
+
+/// Streaming a module's functions hands each over once, in order, with the
+/// same code as compiling the whole module.
+#[test]
+fn test_compile_module_streaming() {
+    let mut wat = String::from("(module\n");
+    for i in 0..100 {
+        wat.push_str(&format!(
+            "  (func (param i32) (result i32) (i32.add (get_local 0) (i32.const {})))\n",
+            i
+        ));
+    }
+    wat.push_str(")\n");
+    let data = wabt::wat2wasm(wat).expect("expecting valid wat");
+
+    let isa = native_isa(&[]);
+
+    let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation");
+    let mut next = 0;
+    let mut total_size = 0;
+    cranelift::compile_module_streaming(
+        &translation.module,
+        translation.function_body_inputs,
+        &*isa,
+        true,
+        &cranelift::CompileOptions::default(),
+        |i, code, _relocs, _traps, address_transform, _frame_size| {
+            assert_eq!(i.index(), next);
+            next += 1;
+            assert_eq!(address_transform.unwrap().body_len, code.len());
+            total_size += code.len();
+        },
+    )
+    .expect("compilation");
+    assert_eq!(next, 100);
+
+    let (compilation, _, _, _, _) = compile(&data, &*isa);
+    let expected_size = compilation
+        .functions
+        .values()
+        .map(|body| body.len())
+        .sum::<usize>();
+    assert_eq!(total_size, expected_size);
+}
+
+/// Each function is streamed with its trap sites, and with the options a
+/// whole module is compiled with.
+#[test]
+fn test_compile_module_streaming_traps() {
+    let data = wabt::wat2wasm(
+        r#"
+(module
+  (memory 1)
+  (func (param i32) (result i32)
+    (i32.add (i32.load (get_local 0)) (i32.load offset=4 (get_local 0)))
+  )
+)
+"#,
+    )
+    .expect("expecting valid wat");
+
+    let isa = native_isa(&[]);
+
+    let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation");
+    let mut streamed = Vec::new();
+    cranelift::compile_module_streaming(
+        &translation.module,
+        translation.function_body_inputs,
+        &*isa,
+        false,
+        &cranelift::CompileOptions::default(),
+        |_, _, _, traps, _, _| streamed.push(traps),
+    )
+    .expect("compilation");
+
+    let (_, _, _, _, traps) = compile(&data, &*isa);
+    let expected = &traps[DefinedFuncIndex::new(0)];
+    assert_eq!(streamed.len(), 1);
+    assert_eq!(streamed[0].len(), expected.len());
+    for (streamed, expected) in streamed[0].iter().zip(expected) {
+        assert_eq!(streamed.code_offset, expected.code_offset);
+        assert_eq!(streamed.source_loc, expected.source_loc);
+    }
+
+    let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation");
+    let result = cranelift::compile_module_streaming(
+        &translation.module,
+        translation.function_body_inputs,
+        &*isa,
+        false,
+        &cranelift::CompileOptions {
+            max_code_size: Some(1),
+            ..cranelift::CompileOptions::default()
+        },
+        |_, _, _, _, _, _| panic!("a function larger than the limit was streamed"),
+    );
+    assert!(result.is_err());
+}
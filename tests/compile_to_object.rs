@@ -0,0 +1,45 @@
+use std::str::FromStr;
+use target_lexicon::Triple;
+use wabt;
+use wasmtime_tools::obj::{
+    compile_to_object, compile_to_object_with_options, ObjectError, ObjectOptions,
+};
+
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (get_local 0) (get_local 1))
+  )
+)
+"#;
+
+#[test]
+fn test_compile_to_object() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+
+    let obj = compile_to_object(&data, None, false).expect("object for the host");
+    let bytes = obj.emit().expect("emit");
+    assert!(!bytes.is_empty());
+
+    let triple = Triple::from_str("x86_64-unknown-linux-gnu").unwrap();
+    let obj = compile_to_object(&data, Some(&triple), true).expect("object for x86-64");
+    assert_eq!(obj.target, triple);
+    let bytes = obj.emit().expect("emit");
+    assert_eq!(&bytes[0..4], b"\x7fELF");
+}
+
+#[test]
+fn test_compile_to_object_invalid() {
+    assert!(compile_to_object(b"\0asm\x01\0\0\0\xff", None, false).is_err());
+}
+
+#[test]
+fn test_compile_to_object_truncated() {
+    // The header ends halfway through the version field.
+    match compile_to_object_with_options(b"\0asm\x01\0", ObjectOptions::default()) {
+        Err(ObjectError::Translate(error)) => assert_eq!(error.offset, 4),
+        Err(error) => panic!("expected a translation error, got {}", error),
+        Ok(_) => panic!("expected a translation error"),
+    }
+}
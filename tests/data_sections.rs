@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use wabt;
+use wasmtime_obj::EmitOptions;
+use wasmtime_tools::obj::{compile_to_object_with_options, ObjectOptions};
+
+mod common;
+
+use common::{isa_for, section, symbols, x86_64_linux, SHF_ALLOC, SHF_WRITE, SHT_PROGBITS};
+
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (memory 1)
+  (data (i32.const 0) "persistent")
+  (data (i32.const 64) "volatile")
+)
+"#;
+
+/// A segment mapped to a section of its own is written there as loaded,
+/// writable data, while the others stay in `.data`.
+#[test]
+fn test_data_sections() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let mut data_sections = HashMap::new();
+    data_sections.insert(0, String::from(".nvram"));
+    let options = ObjectOptions {
+        isa: Some(isa_for(x86_64_linux())),
+        emit: EmitOptions {
+            data_sections,
+            ..EmitOptions::default()
+        },
+        ..ObjectOptions::default()
+    };
+    let bytes = compile_to_object_with_options(&data, options)
+        .expect("object")
+        .into_image()
+        .expect("image");
+
+    let nvram = section(&bytes, ".nvram").expect("data section");
+    assert_eq!(nvram.sh_type, SHT_PROGBITS);
+    assert_eq!(
+        nvram.sh_flags & (SHF_ALLOC | SHF_WRITE),
+        SHF_ALLOC | SHF_WRITE
+    );
+    assert_eq!(nvram.data, b"persistent");
+
+    let symbols = symbols(&bytes);
+    assert!(!symbols.iter().any(|sym| sym.name == "_memory_0"));
+    assert!(symbols.iter().any(|sym| sym.name == "_memory_1"));
+}
@@ -0,0 +1,153 @@
+use cranelift_codegen::isa::{self, TargetIsa};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_entity::EntityRef;
+use cranelift_wasm::DefinedFuncIndex;
+use wabt;
+use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+use wasmtime_tools::obj::{compile_to_object_with_options, ObjectOptions};
+
+mod common;
+
+use common::{compile_with, isa_for, native_isa, read_u32, section, x86_64_linux};
+
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (memory 1)
+  (func (param i32) (result i32)
+    (i32.load (get_local 0))
+  )
+  (func (param i32) (result i32)
+    (i32.add (get_local 0) (i32.const 1))
+  )
+  (func (param i32) (result i32)
+    (i32.load (get_local 0))
+  )
+)
+"#;
+
+/// Functions with identical bodies are compiled once, and the copies keep
+/// trap sites pointing at their own bodies.
+#[test]
+fn test_duplicate_functions() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+
+    let isa = native_isa(&[]);
+
+    let offsets = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation")
+        .function_body_inputs
+        .values()
+        .map(|input| input.module_offset)
+        .collect::<Vec<_>>();
+    let (compilation, _, _, _, traps) = compile_with(
+        &data,
+        &*isa,
+        Tunables::default(),
+        false,
+        &cranelift::CompileOptions {
+            collect_stats: true,
+            ..cranelift::CompileOptions::default()
+        },
+    )
+    .expect("compilation");
+
+    let first = DefinedFuncIndex::new(0);
+    let copy = DefinedFuncIndex::new(2);
+    assert_eq!(compilation.duplicates, [(copy, first)]);
+    assert_eq!(compilation.functions[copy], compilation.functions[first]);
+    assert_ne!(
+        compilation.functions[DefinedFuncIndex::new(1)],
+        compilation.functions[first]
+    );
+
+    let stats = compilation.stats.expect("stats");
+    assert_ne!(stats[first].code_size, 0);
+    assert_eq!(stats[copy].code_size, 0);
+    assert_eq!(stats[copy].codegen_time, Default::default());
+
+    assert_eq!(traps[copy].len(), traps[first].len());
+    for (copied, trap) in traps[copy].iter().zip(&traps[first]) {
+        assert_eq!(copied.code_offset, trap.code_offset);
+        if !trap.source_loc.is_default() {
+            assert_eq!(
+                copied.source_loc.bits() - trap.source_loc.bits(),
+                (offsets[2] - offsets[0]) as u32
+            );
+        }
+    }
+}
+
+/// A module of two functions of the same size, the same body if `k` is 1.
+#[cfg(test)]
+fn two_functions(k: i32) -> Vec<u8> {
+    let body = |k| {
+        format!(
+            "(func (param i32) (result i32)
+               (i32.mul (i32.add (i32.load (get_local 0)) (i32.const {}))
+                        (i32.load offset=4 (get_local 0))))",
+            k
+        )
+    };
+    wabt::wat2wasm(format!("(module (memory 1) {} {})", body(1), body(k)))
+        .expect("expecting valid wat")
+}
+
+/// The image of an object for `data` compiled for `isa`.
+#[cfg(test)]
+fn object(data: &[u8], isa: Box<dyn TargetIsa>) -> Vec<u8> {
+    let options = ObjectOptions {
+        isa: Some(isa),
+        ..ObjectOptions::default()
+    };
+    compile_to_object_with_options(data, options)
+        .expect("object")
+        .into_image()
+        .expect("image")
+}
+
+/// The code of each `_wasm_function_N` of an object for `data`.
+#[cfg(test)]
+fn code_sections(data: &[u8]) -> Vec<usize> {
+    let bytes = object(data, isa_for(x86_64_linux()));
+    (0..2)
+        .map(|n| {
+            section(&bytes, &format!(".text._wasm_function_{}", n))
+                .expect("code section")
+                .data
+                .len()
+        })
+        .collect()
+}
+
+/// A duplicate is written to objects as a jump to the function it
+/// duplicates, rather than as a copy of its code.
+#[test]
+fn test_duplicate_functions_object() {
+    let distinct = code_sections(&two_functions(2));
+    let duplicate = code_sections(&two_functions(1));
+    assert_eq!(distinct[0], distinct[1]);
+    assert_eq!(duplicate[0], distinct[0]);
+    // `jmp rel32`
+    assert_eq!(duplicate[1], 5);
+    assert!(duplicate.iter().sum::<usize>() < distinct.iter().sum::<usize>());
+}
+
+/// In PIC, the jump goes through the PLT, so that the object can be linked
+/// into a shared library.
+#[test]
+fn test_duplicate_functions_pic() {
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("is_pic").unwrap();
+    let isa = isa::lookup(x86_64_linux())
+        .unwrap()
+        .finish(settings::Flags::new(flag_builder));
+    let bytes = object(&two_functions(1), isa);
+    let rela = section(&bytes, ".rela.text._wasm_function_1")
+        .expect("thunk relocations")
+        .data;
+    assert_eq!(rela.len(), 24);
+    // R_X86_64_PLT32
+    assert_eq!(read_u32(rela, 8), 4);
+}
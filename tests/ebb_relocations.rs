@@ -0,0 +1,78 @@
+use cranelift_codegen::binemit::Reloc;
+use wabt;
+use wasmtime_environ::RelocationTarget;
+use wasmtime_jit::{ActionOutcome, Context, RuntimeValue};
+use wasmtime_tools::obj::compile_to_object;
+
+mod common;
+
+use common::{compile, isa_for, native_isa, x86_64_linux};
+
+/// Branches forward and backward between EBBs, with a result that depends on
+/// every branch being taken to the right place.
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (func (export "sum_odd") (param i32) (result i32) (local i32 i32)
+    (block
+      (loop
+        (br_if 1 (i32.ge_s (get_local 1) (get_local 0)))
+        (if (i32.and (get_local 1) (i32.const 1))
+          (then (set_local 2 (i32.add (get_local 2) (get_local 1))))
+          (else (nop))
+        )
+        (set_local 1 (i32.add (get_local 1) (i32.const 1)))
+        (br 0)
+      )
+    )
+    (get_local 2)
+  )
+)
+"#;
+
+/// Branches between EBBs of the same function leave no PC-relative
+/// relocations behind, as Cranelift resolves them while emitting the code.
+#[test]
+fn test_ebb_relocations_resolved() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let (_, relocations, _, _, _) = compile(&data, &*isa_for(x86_64_linux()));
+    for (_, function_relocs) in relocations.iter() {
+        for r in function_relocs {
+            if let RelocationTarget::Ebb(_) = r.reloc_target {
+                if let Reloc::X86PCRel4 = r.reloc {
+                    panic!("unresolved EBB relocation at {}", r.offset);
+                }
+            }
+        }
+    }
+}
+
+/// The module runs in the JIT.
+#[test]
+fn test_ebb_relocations_jit() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let mut context = Context::with_isa(native_isa(&[]));
+    let mut instance = context
+        .instantiate_module(None, &data)
+        .expect("instantiation");
+    for &(n, expected) in &[(0, 0), (1, 0), (2, 1), (7, 9), (10, 25)] {
+        match context
+            .invoke(&mut instance, "sum_odd", &[RuntimeValue::I32(n)])
+            .expect("invoke")
+        {
+            ActionOutcome::Returned { values } => {
+                assert_eq!(values[0].unwrap_i32(), expected, "n = {}", n)
+            }
+            ActionOutcome::Trapped { message } => panic!("unexpected trap: {}", message),
+        }
+    }
+}
+
+/// The module can be written as an object.
+#[test]
+fn test_ebb_relocations_object() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let obj = compile_to_object(&data, Some(&x86_64_linux()), false).expect("object");
+    let bytes = obj.emit().expect("emit");
+    assert_eq!(&bytes[0..4], b"\x7fELF");
+}
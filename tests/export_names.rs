@@ -0,0 +1,55 @@
+use faerie::Artifact;
+use wabt;
+use wasmtime_environ::{ModuleEnvironment, Tunables};
+use wasmtime_obj::{emit_module, EmitOptions, EXPORTS_SECTION};
+
+mod common;
+
+use common::{compile, isa_for, sections, x86_64_linux};
+
+const WAT: &str = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (get_local 0) (get_local 1))
+  )
+)
+"#;
+
+/// Emits `WAT` with `options` and returns the names of its sections.
+fn section_names(options: &EmitOptions) -> Vec<String> {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let triple = x86_64_linux();
+    let isa = isa_for(triple.clone());
+    let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation");
+    let (compilation, relocations, _, _, _) = compile(&data, &*isa);
+
+    let mut obj = Artifact::new(triple, String::from("export_names.o"));
+    emit_module(
+        &mut obj,
+        &translation.module,
+        &compilation,
+        &relocations,
+        &translation.data_initializers,
+        &translation.target_config,
+        options,
+    )
+    .expect("emit module");
+    let bytes = obj.emit().expect("object");
+    sections(&bytes).into_iter().map(|s| s.name).collect()
+}
+
+/// The `.wasm.exports` section is only emitted when asked for.
+#[test]
+fn test_export_names_opt_in() {
+    assert!(!section_names(&EmitOptions::default())
+        .iter()
+        .any(|name| name == EXPORTS_SECTION));
+    assert!(section_names(&EmitOptions {
+        export_names: true,
+        ..EmitOptions::default()
+    })
+    .iter()
+    .any(|name| name == EXPORTS_SECTION));
+}
@@ -0,0 +1,86 @@
+use faerie::Artifact;
+use wabt;
+use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+use wasmtime_obj::{emit_module, patch_elf_export_symbols, EmitOptions};
+
+mod common;
+
+use common::{isa_for, symbols, x86_64_linux};
+
+const WAT: &str = r#"
+(module
+  (import "env" "log" (func $log (param i32)))
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (get_local 0) (get_local 1))
+  )
+  (export "log" (func $log))
+)
+"#;
+
+/// Emits `wat` for x86-64 Linux and adds its export symbols.
+fn emit(wat: &str) -> Result<Vec<u8>, String> {
+    let data = wabt::wat2wasm(wat).expect("expecting valid wat");
+
+    let triple = x86_64_linux();
+    let isa = isa_for(triple.clone());
+
+    let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation");
+    let (compilation, relocations, _, _, _) = cranelift::compile_module(
+        &translation.module,
+        translation.function_body_inputs,
+        &*isa,
+        false,
+    )
+    .expect("compilation");
+
+    let mut obj = Artifact::new(triple, String::from("export_symbols.o"));
+    emit_module(
+        &mut obj,
+        &translation.module,
+        &compilation,
+        &relocations,
+        &translation.data_initializers,
+        &translation.target_config,
+        &EmitOptions::default(),
+    )
+    .expect("emit module");
+    let mut bytes = obj.emit().expect("object");
+    patch_elf_export_symbols(&mut bytes, &translation.module)?;
+    Ok(bytes)
+}
+
+/// The export symbol is a second name for the function's code.
+#[test]
+fn test_export_symbols() {
+    let symbols = symbols(&emit(WAT).expect("export symbols"))
+        .into_iter()
+        .filter(|sym| sym.is_defined_global())
+        .collect::<Vec<_>>();
+    let add = symbols
+        .iter()
+        .find(|sym| sym.name == "add")
+        .expect("add symbol");
+    let function = symbols
+        .iter()
+        .find(|sym| sym.name == "_wasm_function_1")
+        .expect("function symbol");
+    assert_eq!(add.shndx, function.shndx);
+    assert_eq!(add.value, function.value);
+    assert_eq!(add.info, function.info);
+    // Re-exported imports have no code to name.
+    assert!(!symbols.iter().any(|sym| sym.name == "log"));
+}
+
+/// An export can't take the name of a symbol the object has.
+#[test]
+fn test_export_symbols_collision() {
+    let error = emit(r#"(module (func (export "_vmcontext_init")))"#).err();
+    assert_eq!(
+        error,
+        Some(String::from(
+            "export '_vmcontext_init' collides with a symbol of the object"
+        ))
+    );
+}
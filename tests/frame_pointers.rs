@@ -0,0 +1,68 @@
+use cranelift_codegen::isa::CallConv;
+use cranelift_entity::EntityRef;
+use cranelift_wasm::FuncIndex;
+use wasmtime_environ::{cranelift, CompileError, ModuleEnvironment, Tunables};
+use wasmtime_tools::obj::{compile_to_object_with_options, ObjectError, ObjectOptions};
+
+mod common;
+
+use common::{isa_for, read_wat, x86_64_linux, PATH_MODULE_CALL, PATH_MODULE_FIBONACCI};
+
+/// Every function compiled for x86 with the native calling convention links
+/// its frame into the frame-pointer chain, leaf functions included, while
+/// the Baldrdash prologue is left to the embedder.
+#[test]
+fn test_frame_pointer_chain() {
+    let isa = isa_for(x86_64_linux());
+    for path in &[PATH_MODULE_FIBONACCI, PATH_MODULE_CALL] {
+        let data = read_wat(path);
+        for &(call_conv, chained) in &[(CallConv::SystemV, true), (CallConv::Baldrdash, false)] {
+            let mut frontend_config = isa.frontend_config();
+            frontend_config.default_call_conv = call_conv;
+            let translation = ModuleEnvironment::new(frontend_config, Tunables::default())
+                .translate(&data)
+                .expect("translation");
+            match cranelift::check_frame_pointer_chain(&translation.module, &*isa) {
+                Ok(()) => assert!(chained, "{:?}", call_conv),
+                Err(CompileError::NoFramePointer { func }) => {
+                    assert!(!chained, "{:?}", call_conv);
+                    assert_eq!(
+                        func,
+                        FuncIndex::new(translation.module.imported_funcs.len())
+                    );
+                }
+                Err(error) => panic!("unexpected error: {}", error),
+            }
+        }
+    }
+}
+
+/// `--frame-pointer-chain` is applied to the calling convention the module
+/// is compiled with.
+#[test]
+fn test_frame_pointer_chain_object() {
+    let data = read_wat(PATH_MODULE_FIBONACCI);
+    let compile = |call_conv| {
+        compile_to_object_with_options(
+            &data,
+            ObjectOptions {
+                isa: Some(isa_for(x86_64_linux())),
+                call_conv,
+                frame_pointer_chain: true,
+                ..ObjectOptions::default()
+            },
+        )
+    };
+    assert!(compile(None).is_ok());
+    match compile(Some(CallConv::Baldrdash)) {
+        Err(ObjectError::Other(message)) => {
+            assert!(
+                message.contains("doesn't set up a frame pointer"),
+                "{}",
+                message
+            )
+        }
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("expected the frame-pointer chain to be rejected"),
+    }
+}
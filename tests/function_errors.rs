@@ -0,0 +1,54 @@
+use cranelift_entity::EntityRef;
+use cranelift_wasm::FuncIndex;
+use wasmtime_environ::{CompileError, Tunables};
+
+mod common;
+
+use common::{compile_with, native_isa};
+
+/// A module of two `() -> ()` functions, the second of which has an
+/// invalid opcode in its body, at offset 27 in the module.
+#[cfg(test)]
+const WASM: &[u8] = b"\0asm\x01\0\0\0\
+    \x01\x04\x01\x60\0\0\
+    \x03\x03\x02\0\0\
+    \x0a\x07\x02\x02\0\x0b\x02\0\xff";
+
+#[cfg(test)]
+fn compile_error() -> CompileError {
+    match compile_with(
+        WASM,
+        &*native_isa(&[]),
+        Tunables::default(),
+        false,
+        &Default::default(),
+    ) {
+        Err(error) => error,
+        Ok(_) => panic!("expected an error"),
+    }
+}
+
+/// An error without function context of its own names the function it
+/// happened in.
+#[test]
+fn test_error_in_function() {
+    match compile_error() {
+        CompileError::InFunction { func, source } => {
+            assert_eq!(func, FuncIndex::new(1));
+            match *source {
+                CompileError::Wasm(_) => {}
+                error => panic!("unexpected error {:?}", error),
+            }
+        }
+        error => panic!("unexpected error {:?}", error),
+    }
+}
+
+/// Translation errors give the offset of the failing operator in the
+/// module, not in the function's body.
+#[test]
+fn test_error_offset() {
+    let error = compile_error();
+    assert_eq!(error.wasm_offset(), Some(27));
+    assert!(error.to_string().contains("offset 27"));
+}
@@ -0,0 +1,36 @@
+use wabt;
+use wasmtime_tools::obj::compile_to_object;
+
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (import "env" "log" (func $log (param i32)))
+  (func $named_for_the_debugger (param i32)
+    (call $log (get_local 0))
+  )
+  (func (result i32)
+    (i32.const 1)
+  )
+)
+"#;
+
+#[cfg(test)]
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+/// With debug info, the function names of the `name` section end up in the
+/// object, even though the module has no DWARF of its own.
+#[test]
+fn test_function_names() {
+    let data = wabt::Wat2Wasm::new()
+        .write_debug_names(true)
+        .convert(WAT)
+        .expect("expecting valid wat");
+    let obj = compile_to_object(data.as_ref(), None, true).expect("object");
+    let bytes = obj.emit().expect("object");
+
+    assert!(contains(&bytes, b"named_for_the_debugger\0"));
+}
@@ -0,0 +1,25 @@
+use wasmtime_jit::{ActionOutcome, Context, RuntimeValue};
+
+mod common;
+
+use common::{native_isa, read_wat, PATH_MODULE_MANY_FUNCTIONS};
+
+/// Functions compiled in parallel end up at the index of their source body.
+#[test]
+fn test_function_order() {
+    let data = read_wat(PATH_MODULE_MANY_FUNCTIONS);
+    let mut context = Context::with_isa(native_isa(&[]));
+    let mut instance = context
+        .instantiate_module(None, &data)
+        .expect("instantiation");
+
+    for k in 0..50 {
+        let field = format!("f{}", k);
+        match context.invoke(&mut instance, &field, &[]).expect("invoke") {
+            ActionOutcome::Returned { values } => {
+                assert_eq!(values, vec![RuntimeValue::I32(1000 + k)], "{}", field)
+            }
+            ActionOutcome::Trapped { message } => panic!("unexpected trap: {}", message),
+        }
+    }
+}
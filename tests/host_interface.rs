@@ -0,0 +1,103 @@
+use cranelift_codegen::ir::types::{F32, I32};
+use wabt;
+use wasmtime_environ::{
+    validate_imports, HostFunction, HostGlobal, HostInterface, ImportMismatch, Module,
+    ModuleEnvironment, Tunables,
+};
+
+mod common;
+
+use common::native_isa;
+
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (import "env" "putc" (func (param i32)))
+  (import "env" "stack_top" (global i32))
+  (import "env" "memory" (memory 1))
+)
+"#;
+
+#[cfg(test)]
+fn module() -> Module {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let isa = native_isa(&[]);
+    ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation")
+        .module
+}
+
+/// The interface the module expects.
+#[cfg(test)]
+fn interface() -> HostInterface {
+    HostInterface {
+        functions: vec![HostFunction {
+            module: String::from("env"),
+            field: String::from("putc"),
+            params: vec![I32],
+            returns: Vec::new(),
+        }],
+        globals: vec![HostGlobal {
+            module: String::from("env"),
+            field: String::from("stack_top"),
+            ty: I32,
+            mutable: false,
+        }],
+        memories: vec![(String::from("env"), String::from("memory"))],
+        tables: Vec::new(),
+    }
+}
+
+#[test]
+fn test_validate_imports() {
+    assert!(validate_imports(&module(), &interface()).is_ok());
+}
+
+#[test]
+fn test_validate_imports_missing() {
+    let mut interface = interface();
+    interface.memories.clear();
+    match validate_imports(&module(), &interface) {
+        Err(ImportMismatch::Missing { module, field }) => {
+            assert_eq!(module, "env");
+            assert_eq!(field, "memory");
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn test_validate_imports_type() {
+    let mut wrong_params = interface();
+    wrong_params.functions[0].params = vec![F32];
+    match validate_imports(&module(), &wrong_params) {
+        Err(ImportMismatch::Type {
+            field,
+            expected,
+            actual,
+            ..
+        }) => {
+            assert_eq!(field, "putc");
+            assert_eq!(expected, "(f32) -> ()");
+            assert_eq!(actual, "(i32) -> ()");
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+
+    let mut mutable_global = interface();
+    mutable_global.globals[0].mutable = true;
+    match validate_imports(&module(), &mutable_global) {
+        Err(ImportMismatch::Type {
+            field,
+            expected,
+            actual,
+            ..
+        }) => {
+            assert_eq!(field, "stack_top");
+            assert_eq!(expected, "mut i32");
+            assert_eq!(actual, "i32");
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+}
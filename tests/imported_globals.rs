@@ -0,0 +1,25 @@
+use wasmtime_environ::{ModuleEnvironment, Tunables};
+
+mod common;
+
+use common::{compile, native_isa, read_wat, PATH_MODULE_IMPORTED_GLOBAL};
+
+/// Imported globals are reached through the pointer to their definition
+/// stored in the vmctx, which the runtime fills in at instantiation, so
+/// reading and writing them needs no relocations.
+#[test]
+fn test_imported_globals_have_no_relocations() {
+    let data = read_wat(PATH_MODULE_IMPORTED_GLOBAL);
+    let isa = native_isa(&[]);
+
+    let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation");
+    assert_eq!(translation.module.imported_globals.len(), 2);
+    let (compilation, relocations, _, _, _) = compile(&data, &*isa);
+
+    assert_eq!(compilation.functions.len(), 2);
+    for relocs in relocations.values() {
+        assert!(relocs.is_empty(), "unexpected relocations: {:?}", relocs);
+    }
+}
@@ -0,0 +1,60 @@
+use std::fs;
+use wabt;
+
+mod common;
+
+use common::{link_and_run, temp_dir, wasm2obj};
+
+/// `init` sets a global in the object's `_vmcontext_init`, which `value`
+/// reads back.
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (global $g (mut i32) (i32.const 0))
+  (func (export "init")
+    (set_global $g (i32.const 42))
+  )
+  (func (export "value") (result i32)
+    (get_global $g)
+  )
+)
+"#;
+
+/// A program exiting successfully if `init` ran before `main`.
+#[cfg(test)]
+const MAIN: &str = r#"
+extern char _vmcontext_init[];
+extern int value(void *vmctx);
+
+int main(void) {
+    return value(_vmcontext_init) == 42 ? 0 : 1;
+}
+"#;
+
+/// Links the module compiled with `args` into `MAIN` and runs it.
+#[cfg(test)]
+fn run(name: &str, args: &[&str]) -> Option<bool> {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let dir = temp_dir(name);
+    let mut args = args.to_vec();
+    args.extend_from_slice(&["--export-symbols", "--target", "x86_64-unknown-linux-gnu"]);
+    let object = wasm2obj(&dir, &data, &args);
+    let status = link_and_run(&dir, MAIN, &object, &[]);
+    fs::remove_dir_all(&dir).unwrap();
+    status.map(|status| status.success())
+}
+
+/// The export named by `--init-array` runs before `main` when linked, and
+/// doesn't without it.
+#[test]
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn test_init_array_runs_before_main() {
+    match run("init_array", &["--init-array", "init"]) {
+        Some(ran) => assert!(ran),
+        None => {
+            eprintln!("no C compiler; skipping linking the init_array entry");
+            return;
+        }
+    }
+    assert_eq!(run("init_array_none", &[]), Some(false));
+}
@@ -0,0 +1,68 @@
+use cranelift_codegen::binemit::Reloc;
+use wasmtime_environ::RelocationTarget;
+use wasmtime_jit::{ActionOutcome, Context, RuntimeValue};
+use wasmtime_tools::obj::compile_to_object;
+
+mod common;
+
+use common::{compile, native_isa, read_wat, x86_64_linux, PATH_MODULE_BR_TABLE};
+
+/// A large `br_table` is lowered to a jump table, whose data follows the
+/// function's code. Cranelift resolves the PC-relative references to it, so
+/// any relocations left against it are absolute ones within the body.
+#[test]
+fn test_jump_table_offsets() {
+    let data = read_wat(PATH_MODULE_BR_TABLE);
+    let (compilation, relocations, _, _, _) = compile(&data, &*native_isa(&[]));
+
+    let (i, body) = compilation.functions.iter().next().unwrap();
+    let jt_offsets = compilation.jt_offsets[i].values().collect::<Vec<_>>();
+    assert!(!jt_offsets.is_empty());
+    for &&jt_offset in &jt_offsets {
+        assert!((jt_offset as usize) < body.len());
+    }
+    for r in &relocations[i] {
+        match r.reloc_target {
+            RelocationTarget::JumpTable(jt) => {
+                assert!((compilation.jt_offsets[i][jt] as usize) < body.len());
+            }
+            RelocationTarget::Ebb(_) => {}
+            _ => continue,
+        }
+        if let Reloc::X86PCRel4 = r.reloc {
+            panic!("unresolved {} relocation within the body", r.reloc)
+        }
+    }
+}
+
+/// Every target of a large `br_table` is reached through the jump table.
+#[test]
+fn test_br_table() {
+    let data = read_wat(PATH_MODULE_BR_TABLE);
+    let mut context = Context::with_isa(native_isa(&[]));
+    let mut instance = context
+        .instantiate_module(None, &data)
+        .expect("instantiation");
+
+    for index in 0..20 {
+        let args = [RuntimeValue::I32(index)];
+        let result = match context
+            .invoke(&mut instance, "select", &args)
+            .expect("invoke")
+        {
+            ActionOutcome::Returned { values } => values[0].unwrap_i32(),
+            ActionOutcome::Trapped { message } => panic!("unexpected trap: {}", message),
+        };
+        let expected = if index < 17 { 100 + index } else { 0 };
+        assert_eq!(result, expected, "index {}", index);
+    }
+}
+
+/// A module with a jump table can be written as an object.
+#[test]
+fn test_br_table_object() {
+    let data = read_wat(PATH_MODULE_BR_TABLE);
+    let obj = compile_to_object(&data, Some(&x86_64_linux()), false).expect("object");
+    let bytes = obj.emit().expect("emit");
+    assert_eq!(&bytes[0..4], b"\x7fELF");
+}
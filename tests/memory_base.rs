@@ -0,0 +1,61 @@
+use cranelift_entity::EntityRef;
+use wabt;
+use wasmtime_environ::{RelocationTarget, Relocations, Tunables};
+
+mod common;
+
+use common::{compile_with, native_isa};
+
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (memory 1 1)
+  (func (param i32) (result i32)
+    (i32.store (get_local 0) (i32.const 42))
+    (i32.load (get_local 0))
+  )
+)
+"#;
+
+#[cfg(test)]
+fn compile_load_store(tunables: Tunables) -> Relocations {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+
+    let (_, relocations, _, _, _) = compile_with(
+        &data,
+        &*native_isa(&[]),
+        tunables,
+        false,
+        &Default::default(),
+    )
+    .expect("compilation");
+    relocations
+}
+
+#[cfg(test)]
+fn memory_base_relocs(relocations: &Relocations) -> Vec<usize> {
+    relocations
+        .values()
+        .flat_map(|relocs| relocs.iter())
+        .filter_map(|r| match r.reloc_target {
+            RelocationTarget::MemoryBase { memory_index } => Some(memory_index.index()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Loads and stores only locate the base of a static memory by relocation
+/// when asked to.
+#[test]
+fn test_relocatable_memory_base() {
+    let relocations = compile_load_store(Tunables::default());
+    assert!(memory_base_relocs(&relocations).is_empty());
+
+    let relocations = compile_load_store(Tunables {
+        relocatable_memory_base: true,
+        ..Tunables::default()
+    });
+    let relocs = memory_base_relocs(&relocations);
+    assert!(!relocs.is_empty());
+    assert!(relocs.iter().all(|&memory_index| memory_index == 0));
+}
@@ -0,0 +1,55 @@
+use wasmtime_jit::{ActionOutcome, Context, RuntimeValue};
+
+mod common;
+
+use common::{native_isa, read_wat, PATH_MODULE_NANS};
+
+/// The canonical NaNs produced with `enable_nan_canonicalization`.
+#[cfg(test)]
+const CANONICAL_NAN_F32: u32 = 0x7fc0_0000;
+#[cfg(test)]
+const CANONICAL_NAN_F64: u64 = 0x7ff8_0000_0000_0000;
+
+#[cfg(test)]
+fn invoke(context: &mut Context, field: &str, args: &[RuntimeValue]) -> RuntimeValue {
+    let data = read_wat(PATH_MODULE_NANS);
+    let mut instance = context
+        .instantiate_module(None, &data)
+        .expect("instantiation");
+    match context.invoke(&mut instance, field, args).expect("invoke") {
+        ActionOutcome::Returned { values } => values[0],
+        ActionOutcome::Trapped { message } => panic!("unexpected trap: {}", message),
+    }
+}
+
+/// With NaN canonicalization, float operations on NaNs with arbitrary
+/// payloads and signs return the same bits on every target.
+#[test]
+fn test_canonicalize_nans() {
+    let mut context = Context::with_isa(native_isa(&["enable_nan_canonicalization"]));
+
+    let f32_nans = [0x7fa0_0001, 0xffc0_1234, 0x7f80_0001];
+    for &nan in &f32_nans {
+        for &field in &["f32_add", "f32_mul"] {
+            let args = [RuntimeValue::F32(nan), RuntimeValue::F32(1.5f32.to_bits())];
+            let result = invoke(&mut context, field, &args);
+            assert_eq!(result.unwrap_f32_bits(), CANONICAL_NAN_F32, "{}", field);
+        }
+    }
+
+    let f64_nans = [0x7ff4_0000_0000_0001, 0xfff8_0000_dead_beef];
+    for &nan in &f64_nans {
+        for &field in &["f64_add", "f64_div"] {
+            let args = [RuntimeValue::F64(nan), RuntimeValue::F64(1.5f64.to_bits())];
+            let result = invoke(&mut context, field, &args);
+            assert_eq!(result.unwrap_f64_bits(), CANONICAL_NAN_F64, "{}", field);
+        }
+    }
+
+    // Results that aren't NaN are unaffected.
+    let args = [
+        RuntimeValue::F32(1.5f32.to_bits()),
+        RuntimeValue::F32(2.0f32.to_bits()),
+    ];
+    assert_eq!(invoke(&mut context, "f32_mul", &args).unwrap_f32(), 3.0);
+}
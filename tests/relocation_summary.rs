@@ -0,0 +1,46 @@
+use wabt;
+use wasmtime_environ::{summarize_relocations, RelocationSummary};
+
+mod common;
+
+use common::{compile, isa_for, x86_64_linux};
+
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (import "env" "log" (func $log (param i32)))
+  (memory 1)
+  (func $double (param i32) (result i32)
+    (i32.add (get_local 0) (get_local 0))
+  )
+  (func (param i32) (param f32) (result f32)
+    (call $log (get_local 0))
+    (call $log (call $double (get_local 0)))
+    (drop (memory.grow (get_local 0)))
+    (drop (memory.size))
+    (f32.floor (get_local 1))
+  )
+)
+"#;
+
+/// Calls to imported functions go through the vmctx and aren't relocated,
+/// unlike those to defined functions, libcalls and memory intrinsics.
+#[test]
+fn test_relocation_summary() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+
+    // Without SSE4.1, float rounding is a libcall.
+    let (_, relocations, _, _, _) = compile(&data, &*isa_for(x86_64_linux()));
+
+    assert_eq!(
+        summarize_relocations(&relocations),
+        RelocationSummary {
+            user_funcs: 1,
+            libcalls: 1,
+            memory_intrinsics: 2,
+            data: 0,
+            internal: 0,
+            total: 4,
+        }
+    );
+}
@@ -0,0 +1,100 @@
+use std::collections::HashSet;
+use wabt;
+use wasmtime_tools::obj::{compile_to_object_with_options, ObjectOptions};
+
+mod common;
+
+use common::{isa_for, sections, symbols, x86_64_linux};
+
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (memory 1)
+  (func $double (param i32) (result i32)
+    (i32.add (get_local 0) (get_local 0))
+  )
+  (func (export "main") (result i32)
+    (call $double (i32.const 21))
+  )
+  (data (i32.const 0) "hello")
+)
+"#;
+
+/// Reads the names of the sections of a little-endian ELF64 image.
+#[cfg(test)]
+fn section_names(bytes: &[u8]) -> Vec<String> {
+    sections(bytes).into_iter().map(|s| s.name).collect()
+}
+
+#[cfg(test)]
+fn prefixed_object(data: &[u8], prefix: &str) -> Vec<u8> {
+    let options = ObjectOptions {
+        isa: Some(isa_for(x86_64_linux())),
+        section_prefix: Some(String::from(prefix)),
+        ..ObjectOptions::default()
+    };
+    compile_to_object_with_options(data, options)
+        .expect("object")
+        .into_image()
+        .expect("image")
+}
+
+/// Objects of the same module with different prefixes share no code, data
+/// or relocation section, so they can be linked together.
+#[test]
+fn test_section_prefix() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let a = section_names(&prefixed_object(&data, "modA"));
+    let b = section_names(&prefixed_object(&data, "modB"));
+
+    for (names, prefix) in &[(&a, "modA"), (&b, "modB")] {
+        assert!(names
+            .iter()
+            .any(|n| n.starts_with(&format!("{}.text", prefix))));
+        assert!(names
+            .iter()
+            .any(|n| n.starts_with(&format!(".rela{}.text", prefix))));
+        assert!(!names
+            .iter()
+            .any(|n| n.starts_with(".text") || n.starts_with(".rela.text")));
+    }
+
+    // Only the tables every ELF object has are left in common.
+    let a = a.into_iter().collect::<HashSet<_>>();
+    let b = b.into_iter().collect::<HashSet<_>>();
+    for name in a.intersection(&b) {
+        assert!(
+            [
+                "",
+                ".shstrtab",
+                ".strtab",
+                ".symtab",
+                ".comment",
+                ".note.GNU-stack"
+            ]
+            .contains(&name.as_str()),
+            "section {} is in both objects",
+            name
+        );
+    }
+}
+
+/// The section symbols that relocations against the renamed sections refer
+/// to follow them.
+#[test]
+fn test_section_prefix_symbols() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let bytes = prefixed_object(&data, "modA");
+
+    // STT_SECTION
+    let section_symbols = symbols(&bytes)
+        .into_iter()
+        .filter(|sym| sym.info & 0xf == 3 && !sym.name.is_empty())
+        .collect::<Vec<_>>();
+    assert!(section_symbols
+        .iter()
+        .any(|sym| sym.name.starts_with("modA.text")));
+    for sym in section_symbols {
+        assert!(!sym.name.starts_with(".text"), "symbol {}", sym.name);
+    }
+}
@@ -0,0 +1,45 @@
+use wasmtime_environ::{RelocationTarget, Relocations, Tunables};
+
+mod common;
+
+use common::{compile_with, native_isa, read_wat, PATH_MODULE_CALL_INDIRECT};
+
+#[cfg(test)]
+fn compile_call_indirect(tunables: Tunables) -> Relocations {
+    let data = read_wat(PATH_MODULE_CALL_INDIRECT);
+    let (_, relocations, _, _, _) = compile_with(
+        &data,
+        &*native_isa(&[]),
+        tunables,
+        false,
+        &Default::default(),
+    )
+    .expect("compilation");
+    relocations
+}
+
+#[cfg(test)]
+fn signature_table_relocs(relocations: &Relocations) -> usize {
+    relocations
+        .values()
+        .flat_map(|relocs| relocs.iter())
+        .filter(|r| match r.reloc_target {
+            RelocationTarget::SignatureTableBase => true,
+            _ => false,
+        })
+        .count()
+}
+
+/// `call_indirect` only refers to the signature table by relocation when
+/// asked to.
+#[test]
+fn test_relocatable_signature_table() {
+    let relocations = compile_call_indirect(Tunables::default());
+    assert_eq!(signature_table_relocs(&relocations), 0);
+
+    let relocations = compile_call_indirect(Tunables {
+        relocatable_signature_table: true,
+        ..Tunables::default()
+    });
+    assert_eq!(signature_table_relocs(&relocations), 1);
+}
@@ -0,0 +1,50 @@
+use std::env;
+use wasmtime_environ::Tunables;
+
+mod common;
+
+use common::{compile_with, native_isa, read_wat, PATH_MODULE_ARITH};
+
+/// Compiling serially with `WASMTIME_SINGLE_THREAD_COMPILE` gives the same
+/// result as compiling in parallel. This is the only test of this file, as
+/// it changes the environment of the whole process.
+#[test]
+fn test_single_thread_compile() {
+    let data = read_wat(PATH_MODULE_ARITH);
+    let isa = native_isa(&[]);
+    let compile = || {
+        compile_with(&data, &*isa, Tunables::default(), true, &Default::default())
+            .expect("compilation")
+    };
+
+    let (parallel, parallel_relocs, parallel_transforms, parallel_frames, parallel_traps) =
+        compile();
+    env::set_var("WASMTIME_SINGLE_THREAD_COMPILE", "1");
+    let (serial, serial_relocs, serial_transforms, serial_frames, serial_traps) = compile();
+    env::remove_var("WASMTIME_SINGLE_THREAD_COMPILE");
+
+    assert_eq!(parallel.functions.len(), serial.functions.len());
+    for (i, body) in parallel.functions.iter() {
+        assert_eq!(body, &serial.functions[i]);
+    }
+    assert_eq!(
+        format!("{:?}", parallel.jt_offsets),
+        format!("{:?}", serial.jt_offsets)
+    );
+    assert_eq!(
+        format!("{:?}", parallel_relocs),
+        format!("{:?}", serial_relocs)
+    );
+    assert_eq!(
+        format!("{:?}", parallel_transforms),
+        format!("{:?}", serial_transforms)
+    );
+    assert_eq!(
+        parallel_frames.values().collect::<Vec<_>>(),
+        serial_frames.values().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        format!("{:?}", parallel_traps),
+        format!("{:?}", serial_traps)
+    );
+}
@@ -0,0 +1,90 @@
+use faerie::Artifact;
+use wabt;
+use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+use wasmtime_obj::{emit_module, EmitOptions, START_FUNC_SYMBOL};
+
+mod common;
+
+use common::{isa_for, read_u32, sections, symbols, x86_64_linux};
+
+/// Translates, compiles and emits the text module `wat` for x86-64 Linux.
+#[cfg(test)]
+fn emit(wat: &str) -> Vec<u8> {
+    let data = wabt::wat2wasm(wat).expect("expecting valid wat");
+
+    let triple = x86_64_linux();
+    let isa = isa_for(triple.clone());
+
+    let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation");
+    let (compilation, relocations, _, _, _) = cranelift::compile_module(
+        &translation.module,
+        translation.function_body_inputs,
+        &*isa,
+        false,
+    )
+    .expect("compilation");
+
+    let mut obj = Artifact::new(triple, String::from("start_func.o"));
+    emit_module(
+        &mut obj,
+        &translation.module,
+        &compilation,
+        &relocations,
+        &translation.data_initializers,
+        &translation.target_config,
+        &EmitOptions::default(),
+    )
+    .expect("emit module");
+    obj.emit().expect("object")
+}
+
+/// The `_start_func` slot is relocated to the start function's body.
+#[test]
+fn test_start_func_symbol() {
+    let bytes = emit(
+        r#"
+        (module
+          (import "env" "log" (func $log (param i32)))
+          (func $init
+            (call $log (i32.const 1))
+          )
+          (start $init)
+        )
+        "#,
+    );
+    let symbols = symbols(&bytes);
+    let start_func = symbols
+        .iter()
+        .find(|sym| sym.name == START_FUNC_SYMBOL)
+        .expect("_start_func symbol");
+    assert!(start_func.is_defined_global());
+
+    // The slot is in its own data section, `.data._start_func` or
+    // `.rodata._start_func`.
+    let rela = sections(&bytes)
+        .into_iter()
+        .find(|s| s.name.starts_with(".rela") && s.name.ends_with(START_FUNC_SYMBOL))
+        .expect("_start_func relocations");
+    assert_eq!(rela.data.len(), 24);
+    assert_eq!(read_u32(rela.data, 0), 0);
+    // Against the symbol of the function's section.
+    let target = read_u32(rela.data, 12) as usize;
+    assert_eq!(symbols[target].name, ".text._wasm_function_1");
+}
+
+/// Modules without a start function have no `_start_func`.
+#[test]
+fn test_no_start_func_symbol() {
+    let bytes = emit(
+        r#"
+        (module
+          (func (export "init"))
+        )
+        "#,
+    );
+    assert!(!symbols(&bytes)
+        .iter()
+        .any(|sym| sym.name == START_FUNC_SYMBOL));
+}
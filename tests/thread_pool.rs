@@ -0,0 +1,51 @@
+use wasmtime_environ::{cranelift, ModuleEnvironment, Tunables};
+
+mod common;
+
+use common::{compile, compile_with, native_isa, read_wat, PATH_MODULE_ARITH};
+
+/// Compiling on a single-threaded pool gives the same code as compiling on
+/// the global pool.
+#[test]
+fn test_compile_module_in_pool() {
+    let data = read_wat(PATH_MODULE_ARITH);
+    let isa = native_isa(&[]);
+    let (parallel, _, _, _, _) = compile(&data, &*isa);
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    let translation = ModuleEnvironment::new(isa.frontend_config(), Tunables::default())
+        .translate(&data)
+        .expect("translation");
+    let (serial, _, _, _, _) = cranelift::compile_module_in_pool(
+        &translation.module,
+        translation.function_body_inputs,
+        &*isa,
+        false,
+        &pool,
+    )
+    .expect("compilation");
+
+    assert_eq!(parallel.functions.len(), serial.functions.len());
+    for (i, body) in parallel.functions.iter() {
+        assert_eq!(body, &serial.functions[i]);
+    }
+
+    // The pool combines with the other options.
+    let (with_stats, _, _, _, _) = compile_with(
+        &data,
+        &*isa,
+        Tunables::default(),
+        false,
+        &cranelift::CompileOptions {
+            collect_stats: true,
+            pool: Some(&pool),
+            ..cranelift::CompileOptions::default()
+        },
+    )
+    .expect("compilation");
+    assert!(with_stats.functions.values().eq(serial.functions.values()));
+    assert!(with_stats.stats.is_some());
+}
@@ -0,0 +1,25 @@
+use cranelift_codegen::ir;
+
+mod common;
+
+use common::{compile, native_isa, read_wat, PATH_MODULE_TRAP_LOAD_BOUNDS};
+
+/// A heap load is recorded as a `heap_oob` trap site, within the function
+/// body and with the wasm offset of the load, so that a signal handler can
+/// tell why an out-of-bounds access faulted.
+#[test]
+fn test_heap_oob_trap_site() {
+    let data = read_wat(PATH_MODULE_TRAP_LOAD_BOUNDS);
+    let (compilation, _, _, _, traps) = compile(&data, &*native_isa(&[]));
+
+    let (i, body) = compilation.functions.iter().next().unwrap();
+    let heap_oob = traps[i]
+        .iter()
+        .filter(|site| site.trap_code == ir::TrapCode::HeapOutOfBounds)
+        .collect::<Vec<_>>();
+    assert!(!heap_oob.is_empty());
+    for site in heap_oob {
+        assert!((site.code_offset as usize) < body.len());
+        assert!(!site.source_loc.is_default());
+    }
+}
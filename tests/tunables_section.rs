@@ -0,0 +1,60 @@
+use wabt;
+use wasmtime_environ::Tunables;
+use wasmtime_obj::{check_tunables, decode_tunables, TUNABLES_SECTION};
+use wasmtime_tools::obj::{compile_to_object_with_options, ObjectOptions};
+
+mod common;
+
+use common::{isa_for, section, x86_64_linux};
+
+/// The image of an object for `data`, with a `.wasm.tunables` section if
+/// `tunables_section` is set.
+#[cfg(test)]
+fn object(data: &[u8], tunables_section: bool) -> Vec<u8> {
+    let options = ObjectOptions {
+        isa: Some(isa_for(x86_64_linux())),
+        tunables_section,
+        ..ObjectOptions::default()
+    };
+    compile_to_object_with_options(data, options)
+        .expect("object")
+        .into_image()
+        .expect("image")
+}
+
+/// Objects record the tunables they were compiled with when asked to,
+/// which a loader with other tunables rejects.
+#[test]
+fn test_tunables_section() {
+    let data = wabt::wat2wasm("(module (memory 1) (func))").expect("expecting valid wat");
+    let bytes = object(&data, true);
+
+    let section = section(&bytes, TUNABLES_SECTION)
+        .expect("tunables section")
+        .data;
+    let tunables = decode_tunables(section).expect("tunables");
+    let expected = Tunables::default();
+    assert_eq!(tunables.static_memory_bound, expected.static_memory_bound);
+    assert_eq!(
+        tunables.static_memory_offset_guard_size,
+        expected.static_memory_offset_guard_size
+    );
+    assert_eq!(
+        tunables.dynamic_memory_offset_guard_size,
+        expected.dynamic_memory_offset_guard_size
+    );
+    assert!(check_tunables(section, &expected).is_ok());
+
+    let smaller_guard = Tunables {
+        static_memory_offset_guard_size: 0x1000,
+        ..Tunables::default()
+    };
+    assert!(check_tunables(section, &smaller_guard).is_err());
+}
+
+/// Without being asked for, the section isn't emitted.
+#[test]
+fn test_tunables_section_default() {
+    let data = wabt::wat2wasm("(module (memory 1) (func))").expect("expecting valid wat");
+    assert!(section(&object(&data, false), TUNABLES_SECTION).is_none());
+}
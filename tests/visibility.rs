@@ -0,0 +1,57 @@
+use wabt;
+use wasmtime_obj::Visibility;
+use wasmtime_tools::obj::{compile_to_object_with_options, ObjectOptions};
+
+mod common;
+
+use common::{isa_for, symbols, x86_64_linux};
+
+#[cfg(test)]
+const WAT: &str = r#"
+(module
+  (func $double (param i32) (result i32)
+    (i32.add (get_local 0) (get_local 0))
+  )
+  (func (export "main") (param i32) (result i32)
+    (call $double (get_local 0))
+  )
+)
+"#;
+
+/// Returns the `st_other` visibility bits of each function symbol of the
+/// object compiled with `visibility`.
+#[cfg(test)]
+fn function_visibility(visibility: Visibility) -> (u8, u8) {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let options = ObjectOptions {
+        isa: Some(isa_for(x86_64_linux())),
+        visibility: Some(visibility),
+        ..ObjectOptions::default()
+    };
+    let bytes = compile_to_object_with_options(&data, options)
+        .expect("object")
+        .into_image()
+        .expect("image");
+    let symbols = symbols(&bytes);
+    let visibility = |name: &str| {
+        symbols
+            .iter()
+            .find(|sym| sym.name == name && sym.is_defined_global())
+            .unwrap_or_else(|| panic!("no symbol {}", name))
+            .other
+            & 0x3
+    };
+    (
+        visibility("_wasm_function_0"),
+        visibility("_wasm_function_1"),
+    )
+}
+
+/// Exported functions get the chosen visibility and the others are hidden.
+#[test]
+fn test_visibility() {
+    // STV_DEFAULT, STV_HIDDEN and STV_PROTECTED.
+    assert_eq!(function_visibility(Visibility::Default), (2, 0));
+    assert_eq!(function_visibility(Visibility::Hidden), (2, 2));
+    assert_eq!(function_visibility(Visibility::Protected), (2, 3));
+}
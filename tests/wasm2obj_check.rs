@@ -0,0 +1,42 @@
+use std::fs;
+use std::process::Output;
+use wabt;
+
+mod common;
+
+use common::{temp_dir, wasm2obj_check};
+
+/// Runs `wasm2obj --check` on the module `data` in the temporary directory
+/// of the test `name`.
+fn check(name: &str, data: &[u8]) -> Output {
+    let dir = temp_dir(name);
+    let output = wasm2obj_check(&dir, data, &[]);
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+#[test]
+fn test_check_valid_module() {
+    let data = wabt::wat2wasm(
+        r#"
+        (module
+          (func (export "add") (param i32 i32) (result i32)
+            (i32.add (get_local 0) (get_local 1))
+          )
+        )
+        "#,
+    )
+    .expect("expecting valid wat");
+    let output = check("wasm2obj_check_valid", &data);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_check_malformed_module() {
+    let output = check("wasm2obj_check_malformed", b"\0asm\x01\0");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("error"));
+}
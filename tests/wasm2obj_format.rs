@@ -0,0 +1,54 @@
+use std::fs;
+use wabt;
+
+mod common;
+
+use common::{temp_dir, wasm2obj, wasm2obj_check};
+
+/// Compiles a small module for x86-64 Linux in the object format `format`,
+/// returning the object.
+fn object(name: &str, format: &str) -> Vec<u8> {
+    let data = wabt::wat2wasm("(module (func))").expect("expecting valid wat");
+    let dir = temp_dir(name);
+    let object = wasm2obj(
+        &dir,
+        &data,
+        &["--target", "x86_64-unknown-linux-gnu", "--format", format],
+    );
+    let bytes = fs::read(&object).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    bytes
+}
+
+/// `--format` overrides the format the target implies.
+#[test]
+fn test_format() {
+    assert_eq!(&object("wasm2obj_format_elf", "elf")[..4], b"\x7fELF");
+    // MH_MAGIC_64, little-endian.
+    assert_eq!(
+        &object("wasm2obj_format_macho", "macho")[..4],
+        &[0xcf, 0xfa, 0xed, 0xfe]
+    );
+}
+
+/// Formats that aren't written are rejected.
+#[test]
+fn test_format_unsupported() {
+    let data = wabt::wat2wasm("(module (func))").expect("expecting valid wat");
+    let dir = temp_dir("wasm2obj_format_unsupported");
+    for &(format, message) in &[
+        ("coff", "COFF objects are not supported"),
+        ("wasm", "unknown object format 'wasm'"),
+    ] {
+        let output = wasm2obj_check(
+            &dir,
+            &data,
+            &["--target", "x86_64-unknown-linux-gnu", "--format", format],
+        );
+        assert!(!output.status.success(), "--format {} succeeded", format);
+        assert!(output.stdout.is_empty());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(message), "{}", stderr);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,59 @@
+use std::fs;
+use std::process::Output;
+use wabt;
+
+mod common;
+
+use common::{temp_dir, wasm2obj_check};
+
+/// Runs `wasm2obj --print-isa --check` on a small module with `args`.
+fn print_isa(name: &str, args: &[&str]) -> Output {
+    let data = wabt::wat2wasm("(module (func))").expect("expecting valid wat");
+    let dir = temp_dir(name);
+    let mut args = args.to_vec();
+    args.push("--print-isa");
+    let output = wasm2obj_check(&dir, &data, &args);
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+/// Returns the `opt_level` line of the settings `--print-isa` printed to
+/// stderr.
+fn opt_level_line(output: Output) -> String {
+    assert!(output.status.success());
+    String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .find(|line| line.trim_start().starts_with("opt_level"))
+        .expect("opt_level setting")
+        .to_string()
+}
+
+/// Each `--opt-level` selects a Cranelift `opt_level`, `speed` by default.
+#[test]
+fn test_opt_level() {
+    for &(level, opt_level) in &[
+        ("none", "fastest"),
+        ("speed", "default"),
+        ("speed_and_size", "best"),
+    ] {
+        let name = format!("wasm2obj_opt_level_{}", level);
+        let line = opt_level_line(print_isa(&name, &["--opt-level", level]));
+        assert!(line.contains(opt_level), "{}", line);
+    }
+    let line = opt_level_line(print_isa("wasm2obj_opt_level_default", &[]));
+    assert!(line.contains("default"), "{}", line);
+}
+
+#[test]
+fn test_opt_level_unknown() {
+    let output = print_isa("wasm2obj_opt_level_unknown", &["--opt-level", "fastest"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("unknown --opt-level 'fastest'"),
+        "{}",
+        stderr
+    );
+}
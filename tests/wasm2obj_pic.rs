@@ -0,0 +1,102 @@
+use std::fs;
+use wabt;
+
+mod common;
+
+use common::{link_and_run, read_u32, sections, temp_dir, wasm2obj, SHT_RELA};
+
+const WAT: &str = r#"
+(module
+  (type $t (func (param i32) (result i32)))
+  (table anyfunc (elem $double))
+  (func $double (type $t)
+    (i32.add (get_local 0) (get_local 0))
+  )
+  (func (export "main") (param i32) (result i32)
+    (call $double (get_local 0))
+    (call_indirect (type $t) (i32.const 0))
+  )
+)
+"#;
+
+/// `R_X86_64_PLT32` relocation type.
+const R_X86_64_PLT32: u32 = 4;
+
+/// Returns the types of the relocations in the `.rela.text.*` sections of a
+/// little-endian ELF64 image.
+fn code_relocations(bytes: &[u8]) -> Vec<u32> {
+    sections(bytes)
+        .iter()
+        .filter(|s| s.sh_type == SHT_RELA && s.name.starts_with(".rela.text"))
+        .flat_map(|s| s.data.chunks(24))
+        .map(|rela| read_u32(rela, 8))
+        .collect()
+}
+
+/// With `--pic`, functions are called through the PLT, and their code has
+/// no absolute relocations, which a shared library can't have.
+#[test]
+fn test_pic_relocations() {
+    let data = wabt::wat2wasm(WAT).expect("expecting valid wat");
+    let dir = temp_dir("wasm2obj_pic");
+    let object = wasm2obj(
+        &dir,
+        &data,
+        &["--pic", "--target", "x86_64-unknown-linux-gnu"],
+    );
+    let bytes = fs::read(&object).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let types = code_relocations(&bytes);
+    assert!(types.contains(&R_X86_64_PLT32));
+    // R_X86_64_64, R_X86_64_32 and R_X86_64_32S.
+    for absolute in &[1, 10, 11] {
+        assert!(!types.contains(absolute));
+    }
+}
+
+/// Calls the module's own functions, so that linking resolves a PLT call.
+const PIE_WAT: &str = r#"
+(module
+  (func $double (param i32) (result i32)
+    (i32.add (get_local 0) (get_local 0))
+  )
+  (func (export "answer") (param i32) (result i32)
+    (i32.add (call $double (get_local 0)) (i32.const 2))
+  )
+)
+"#;
+
+/// A program calling the export, which doesn't use its vmctx.
+const PIE_MAIN: &str = r#"
+extern int answer(void *vmctx, int x);
+
+int main(void) {
+    return answer(0, 20) == 42 ? 0 : 1;
+}
+"#;
+
+/// With `--pie`, the object links into a position-independent executable
+/// that runs.
+#[test]
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn test_pie_runs() {
+    let data = wabt::wat2wasm(PIE_WAT).expect("expecting valid wat");
+    let dir = temp_dir("wasm2obj_pie");
+    let object = wasm2obj(
+        &dir,
+        &data,
+        &[
+            "--pie",
+            "--export-symbols",
+            "--target",
+            "x86_64-unknown-linux-gnu",
+        ],
+    );
+    let status = link_and_run(&dir, PIE_MAIN, &object, &["-fPIE", "-pie"]);
+    fs::remove_dir_all(&dir).unwrap();
+    match status {
+        Some(status) => assert!(status.success()),
+        None => eprintln!("no C compiler; skipping linking the PIE"),
+    }
+}
@@ -0,0 +1,52 @@
+use std::fs;
+use wabt;
+
+mod common;
+
+use common::{temp_dir, wasm2obj_check};
+
+/// `--print-isa` prints the ISA `--target` and `--float-abi` select to
+/// stderr, leaving stdout to an object written to `-`.
+#[test]
+fn test_print_isa() {
+    let data = wabt::wat2wasm("(module (func))").expect("expecting valid wat");
+    let dir = temp_dir("wasm2obj_print_isa");
+    let output = wasm2obj_check(
+        &dir,
+        &data,
+        &[
+            "--print-isa",
+            "--target",
+            "x86_64-unknown-linux-gnu",
+            "--float-abi",
+            "hard",
+        ],
+    );
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("isa: x86"));
+    assert!(stderr.contains("triple: x86_64-unknown-linux-gnu"));
+    assert!(stderr.contains("float ABI: hard"));
+}
+
+/// x86-64 has no soft-float ABI.
+#[test]
+fn test_float_abi_unsupported() {
+    let data = wabt::wat2wasm("(module (func))").expect("expecting valid wat");
+    let dir = temp_dir("wasm2obj_float_abi");
+    let output = wasm2obj_check(
+        &dir,
+        &data,
+        &[
+            "--target",
+            "x86_64-unknown-linux-gnu",
+            "--float-abi",
+            "soft",
+        ],
+    );
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(!output.status.success());
+}
@@ -0,0 +1,30 @@
+use std::fs;
+use wabt;
+
+mod common;
+
+use common::{temp_dir, wasm2obj_stdout};
+
+/// With `-o -`, the object is written to stdout.
+#[test]
+fn test_object_to_stdout() {
+    let data = wabt::wat2wasm("(module (func))").expect("expecting valid wat");
+    let dir = temp_dir("wasm2obj_stdout");
+    let output = wasm2obj_stdout(&dir, &data, &["--target", "x86_64-unknown-linux-gnu"]);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+    assert_eq!(&output.stdout[..4], b"\x7fELF");
+}
+
+/// Errors go to stderr, so that nothing but an object is ever piped from
+/// stdout.
+#[test]
+fn test_error_to_stderr() {
+    let dir = temp_dir("wasm2obj_stdout_error");
+    let output = wasm2obj_stdout(&dir, b"\0asm\x01\0", &[]);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("error"), "{}", stderr);
+}
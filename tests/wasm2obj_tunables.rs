@@ -0,0 +1,59 @@
+use std::fs;
+use wabt;
+use wasmtime_obj::{decode_tunables, TUNABLES_SECTION};
+
+mod common;
+
+use common::{section, temp_dir, wasm2obj, wasm2obj_check};
+
+/// The memory tunables given on the command line, in decimal or
+/// hexadecimal, are the ones the object is compiled with.
+#[test]
+fn test_memory_tunables_flags() {
+    let data = wabt::wat2wasm("(module (memory 1) (func))").expect("expecting valid wat");
+    let dir = temp_dir("wasm2obj_tunables");
+    let object = wasm2obj(
+        &dir,
+        &data,
+        &[
+            "--target",
+            "x86_64-unknown-linux-gnu",
+            "--tunables-section",
+            "--static-memory-bound",
+            "0x100",
+            "--static-memory-guard-size",
+            "65536",
+            "--dynamic-memory-guard-size",
+            "0",
+        ],
+    );
+    let bytes = fs::read(&object).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let section = section(&bytes, TUNABLES_SECTION)
+        .expect("tunables section")
+        .data;
+    let tunables = decode_tunables(section).expect("tunables");
+    assert_eq!(tunables.static_memory_bound, 0x100);
+    assert_eq!(tunables.static_memory_offset_guard_size, 0x1_0000);
+    assert_eq!(tunables.dynamic_memory_offset_guard_size, 0);
+}
+
+/// Values that aren't numbers, or bounds beyond a 32-bit memory, are
+/// rejected.
+#[test]
+fn test_memory_tunables_flags_invalid() {
+    let data = wabt::wat2wasm("(module (memory 1) (func))").expect("expecting valid wat");
+    let dir = temp_dir("wasm2obj_tunables_invalid");
+    for args in &[
+        ["--static-memory-bound", "0x10001"],
+        ["--static-memory-guard-size", "64k"],
+    ] {
+        let output = wasm2obj_check(&dir, &data, args);
+        assert!(!output.status.success());
+        assert!(output.stdout.is_empty());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(args[0]), "{}", stderr);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -4,24 +4,36 @@ use faerie::{Artifact, Decl};
 use failure::Error;
 use target_lexicon::{BinaryFormat, Triple};
 
+pub use crate::line_program::{add_line_program, emit_line_program};
+pub use crate::producer::set_producer;
 pub use crate::read_debuginfo::{read_debuginfo, DebugInfoData};
 pub use crate::transform::transform_dwarf;
-pub use crate::write_debuginfo::{emit_dwarf, ResolvedSymbol, SymbolResolver};
+pub use crate::trap_labels::add_trap_labels;
+pub use crate::write_debuginfo::{emit_dwarf, ResolvedSymbol, SymbolResolver, UnknownEndianness};
 
-use wasmtime_environ::AddressTransforms;
+use wasmtime_environ::{AddressTransforms, TrapInformation};
 
 mod address_transform;
+mod line_program;
+mod macinfo;
+mod producer;
 mod read_debuginfo;
 mod transform;
+mod trap_labels;
 mod write_debuginfo;
 
 #[macro_use]
 extern crate failure_derive;
 
-struct FunctionRelocResolver {}
+/// Resolves the defined function index `symbol` to the function's
+/// `_wasm_function_N` symbol, which is named after its `FuncIndex`.
+struct FunctionRelocResolver {
+    imported_func_count: u32,
+}
 impl SymbolResolver for FunctionRelocResolver {
     fn resolve_symbol(&self, symbol: usize, addend: i64) -> ResolvedSymbol {
-        let name = format!("_wasm_function_{}", symbol);
+        let func_index = self.imported_func_count as usize + symbol;
+        let name = format!("_wasm_function_{}", func_index);
         ResolvedSymbol::Reloc { name, addend }
     }
 }
@@ -31,10 +43,20 @@ pub fn emit_debugsections(
     target_config: &TargetFrontendConfig,
     debuginfo_data: &DebugInfoData,
     at: &AddressTransforms,
+    traps: Option<&TrapInformation>,
 ) -> Result<(), Error> {
-    let dwarf = transform_dwarf(target_config, debuginfo_data, at)?;
-    let resolver = FunctionRelocResolver {};
-    emit_dwarf(obj, dwarf, &resolver);
+    let mut dwarf = transform_dwarf(target_config, debuginfo_data, at)?;
+    if debuginfo_data.dwarf.debug_info.units().next()?.is_none() {
+        add_line_program(&mut dwarf, at, &debuginfo_data.wasm_file);
+    }
+    if let Some(traps) = traps {
+        add_trap_labels(&mut dwarf, traps);
+    }
+    set_producer(&mut dwarf);
+    let resolver = FunctionRelocResolver {
+        imported_func_count: debuginfo_data.wasm_file.imported_func_count,
+    };
+    emit_dwarf(obj, dwarf, &resolver)?;
     Ok(())
 }
 
@@ -61,7 +83,11 @@ pub fn emit_debugsections_image(
         .map(|(ptr, _)| *ptr as u64)
         .collect::<Vec<u64>>();
     let mut obj = Artifact::new(triple, String::from("module"));
-    let dwarf = transform_dwarf(target_config, debuginfo_data, at)?;
+    let mut dwarf = transform_dwarf(target_config, debuginfo_data, at)?;
+    if debuginfo_data.dwarf.debug_info.units().next()?.is_none() {
+        add_line_program(&mut dwarf, at, &debuginfo_data.wasm_file);
+    }
+    set_producer(&mut dwarf);
     let resolver = ImageRelocResolver { func_offsets };
 
     // Assuming all functions in the same code block, looking min/max of its range.
@@ -76,7 +102,7 @@ pub fn emit_debugsections_image(
     let body = unsafe { ::std::slice::from_raw_parts(segment_body.0, segment_body.1) };
     obj.declare_with("all", Decl::function(), body.to_vec())?;
 
-    emit_dwarf(&mut obj, dwarf, &resolver);
+    emit_dwarf(&mut obj, dwarf, &resolver)?;
 
     // LLDB is too "magical" about mach-o, generating elf
     let mut bytes = obj.emit_as(BinaryFormat::Elf)?;
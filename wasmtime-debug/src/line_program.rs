@@ -0,0 +1,218 @@
+use crate::read_debuginfo::WasmFileInfo;
+use crate::transform::TransformedDwarf;
+use cranelift_entity::EntityRef;
+use gimli::write;
+use gimli::LineEncoding;
+use wasmtime_environ::{AddressTransforms, FunctionAddressTransform};
+
+/// Name of the file the line-number program refers to.
+const WASM_FILE_NAME: &str = "module.wasm";
+
+/// Returns the rows of the line-number program for a function, as pairs of
+/// the offset within the function body and the offset of the originating
+/// instruction in the wasm file, in increasing order of body offset.
+/// Consecutive instructions generated for the same wasm instruction share a
+/// row, and the first row starts at the beginning of the body.
+fn line_rows(transform: &FunctionAddressTransform) -> Vec<(u64, u64)> {
+    let mut locations = transform
+        .locations
+        .iter()
+        .filter(|location| !location.srcloc.is_default())
+        .collect::<Vec<_>>();
+    locations.sort_by_key(|location| location.code_offset);
+
+    let mut rows: Vec<(u64, u64)> = Vec::new();
+    for location in locations {
+        let line = u64::from(location.srcloc.bits());
+        if rows.last().map(|&(_, last_line)| last_line) == Some(line) {
+            continue;
+        }
+        let address_offset = if rows.is_empty() {
+            0
+        } else {
+            (location.code_offset - transform.body_offset) as u64
+        };
+        rows.push((address_offset, line));
+    }
+    rows
+}
+
+/// Builds a line-number program mapping the generated code of each function
+/// to the wasm instructions it came from. There is no source to refer to,
+/// so the line of a row is the offset of its instruction in the wasm file.
+pub fn emit_line_program(at: &AddressTransforms, encoding: gimli::Encoding) -> write::LineProgram {
+    let line_encoding = LineEncoding {
+        minimum_instruction_length: 1,
+        maximum_operations_per_instruction: 1,
+        default_is_stmt: true,
+        line_base: -5,
+        line_range: 14,
+    };
+    let mut program = write::LineProgram::new(
+        encoding,
+        line_encoding,
+        write::LineString::String(b".".to_vec()),
+        write::LineString::String(WASM_FILE_NAME.as_bytes().to_vec()),
+        None,
+    );
+    let dir = program.default_directory();
+    let file = program.add_file(
+        write::LineString::String(WASM_FILE_NAME.as_bytes().to_vec()),
+        dir,
+        None,
+    );
+
+    for (i, transform) in at.iter() {
+        let rows = line_rows(transform);
+        if rows.is_empty() {
+            continue;
+        }
+        program.begin_sequence(Some(write::Address::Relative {
+            symbol: i.index(),
+            addend: 0,
+        }));
+        for (address_offset, line) in rows {
+            program.row().address_offset = address_offset;
+            program.row().file = file;
+            program.row().line = line;
+            program.generate_row();
+        }
+        program.end_sequence(transform.body_len as u64);
+    }
+    program
+}
+
+/// Adds a compile unit with the line-number program of `emit_line_program`,
+/// for modules that have no DWARF of their own to give line information,
+/// and a subprogram for each function, named after its entry in the `name`
+/// section or, failing that, its symbol.
+pub fn add_line_program(
+    dwarf: &mut TransformedDwarf,
+    at: &AddressTransforms,
+    wasm_file: &WasmFileInfo,
+) {
+    let program = emit_line_program(at, dwarf.encoding);
+    let unit_id = dwarf.units.add(write::Unit::new(dwarf.encoding, program));
+    let unit = dwarf.units.get_mut(unit_id);
+    let root = unit.root();
+    let root_entry = unit.get_mut(root);
+    root_entry.set(
+        gimli::DW_AT_name,
+        write::AttributeValue::StringRef(dwarf.strings.add(WASM_FILE_NAME)),
+    );
+    root_entry.set(
+        gimli::DW_AT_stmt_list,
+        write::AttributeValue::LineProgramRef,
+    );
+
+    for (i, transform) in at.iter() {
+        let func_index = wasm_file.imported_func_count + i.index() as u32;
+        let name = match wasm_file.func_names.get(&func_index) {
+            Some(name) => name.clone(),
+            None => format!("_wasm_function_{}", func_index),
+        };
+        let subprogram_id = unit.add(root, gimli::DW_TAG_subprogram);
+        let subprogram = unit.get_mut(subprogram_id);
+        subprogram.set(
+            gimli::DW_AT_name,
+            write::AttributeValue::StringRef(dwarf.strings.add(name)),
+        );
+        subprogram.set(
+            gimli::DW_AT_low_pc,
+            write::AttributeValue::Address(write::Address::Relative {
+                symbol: i.index(),
+                addend: 0,
+            }),
+        );
+        subprogram.set(
+            gimli::DW_AT_high_pc,
+            write::AttributeValue::Udata(transform.body_len as u64),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::ir::SourceLoc;
+    use std::collections::HashMap;
+    use wasmtime_environ::InstructionAddressTransform;
+
+    #[test]
+    fn test_line_rows_monotonic() {
+        // Instructions out of order, some without a location, and several
+        // generated from one wasm instruction.
+        let locations = [
+            (4, 0x30),
+            (0, 0),
+            (12, 0x31),
+            (8, 0x30),
+            (16, 0x31),
+            (20, 0x38),
+        ];
+        let transform = FunctionAddressTransform {
+            locations: locations
+                .iter()
+                .map(|&(code_offset, srcloc)| InstructionAddressTransform {
+                    srcloc: if srcloc == 0 {
+                        SourceLoc::default()
+                    } else {
+                        SourceLoc::new(srcloc)
+                    },
+                    code_offset: 0x100 + code_offset,
+                    code_len: 4,
+                })
+                .collect(),
+            body_offset: 0x100,
+            body_len: 24,
+        };
+
+        let rows = line_rows(&transform);
+        assert_eq!(rows, [(0, 0x30), (12, 0x31), (20, 0x38)]);
+        for pair in rows.windows(2) {
+            assert!(pair[0].0 < pair[1].0);
+        }
+    }
+
+    #[test]
+    fn test_subprogram_per_function() {
+        let mut at = AddressTransforms::new();
+        for body_offset in &[0, 0x40, 0x80] {
+            at.push(FunctionAddressTransform {
+                locations: Vec::new(),
+                body_offset: *body_offset,
+                body_len: 0x40,
+            });
+        }
+        let mut func_names = HashMap::new();
+        func_names.insert(2, String::from("named"));
+        let wasm_file = WasmFileInfo {
+            code_section_offset: 0,
+            function_offsets_and_sizes: Box::new([]),
+            imported_func_count: 1,
+            func_names,
+        };
+        let mut dwarf = TransformedDwarf {
+            encoding: gimli::Encoding {
+                format: gimli::Format::Dwarf32,
+                version: 4,
+                address_size: 8,
+            },
+            strings: write::StringTable::default(),
+            units: write::UnitTable::default(),
+            line_strings: write::LineStringTable::default(),
+            range_lists: write::RangeListTable::default(),
+            debug_macinfo: None,
+        };
+        add_line_program(&mut dwarf, &at, &wasm_file);
+
+        assert_eq!(dwarf.units.count(), 1);
+        let unit = dwarf.units.get(dwarf.units.id(0));
+        let root = unit.get(unit.root());
+        let subprograms = root
+            .children()
+            .filter(|&&child| unit.get(child).tag() == gimli::DW_TAG_subprogram)
+            .count();
+        assert_eq!(subprograms, at.len());
+    }
+}
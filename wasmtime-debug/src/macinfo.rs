@@ -0,0 +1,150 @@
+//! Cloning of `.debug_macinfo`, whose `DW_MACINFO_start_file` entries refer
+//! to the files of their unit's line program by index.
+
+use crate::transform::TransformError;
+use failure::Error;
+use gimli::{self, write, EndianSlice, LittleEndian, Reader};
+use std::vec::Vec;
+
+// The `DW_MACINFO_*` entry types of DWARF 4, which gimli 0.17 doesn't name.
+const DW_MACINFO_DEFINE: u8 = 0x01;
+const DW_MACINFO_UNDEF: u8 = 0x02;
+const DW_MACINFO_START_FILE: u8 = 0x03;
+const DW_MACINFO_END_FILE: u8 = 0x04;
+const DW_MACINFO_VENDOR_EXT: u8 = 0xff;
+
+/// Returns the index in the output line program of each input file, by
+/// input index minus one. `LineProgram::add_file` returns the id of an
+/// already added file, so duplicate input entries share one output index.
+pub(crate) fn output_file_indices(files: &[write::FileId]) -> Vec<u64> {
+    let mut distinct: Vec<write::FileId> = Vec::new();
+    files
+        .iter()
+        .map(|file| {
+            let position = match distinct.iter().position(|f| f == file) {
+                Some(position) => position,
+                None => {
+                    distinct.push(*file);
+                    distinct.len() - 1
+                }
+            };
+            position as u64 + 1
+        })
+        .collect()
+}
+
+/// Appends to `out` the entries of the unit's `.debug_macinfo` at `offset`,
+/// with file indices mapped through `file_indices`, and returns the offset
+/// of the copy.
+pub(crate) fn clone_macinfo(
+    debug_macinfo: &[u8],
+    offset: usize,
+    file_indices: &[u64],
+    out: &mut Vec<u8>,
+) -> Result<usize, Error> {
+    if offset > debug_macinfo.len() {
+        return Err(TransformError("invalid debug_macinfo offset").into());
+    }
+    let out_offset = out.len();
+    let mut input = EndianSlice::new(&debug_macinfo[offset..], LittleEndian);
+    loop {
+        let kind = input.read_u8()?;
+        out.push(kind);
+        match kind {
+            0 => break,
+            DW_MACINFO_DEFINE | DW_MACINFO_UNDEF => {
+                gimli::leb128::write::unsigned(out, input.read_uleb128()?)?;
+                out.extend_from_slice(input.read_null_terminated_slice()?.slice());
+                out.push(0);
+            }
+            DW_MACINFO_START_FILE => {
+                gimli::leb128::write::unsigned(out, input.read_uleb128()?)?;
+                let file = input.read_uleb128()?;
+                let index = match file.checked_sub(1) {
+                    Some(i) if (i as usize) < file_indices.len() => file_indices[i as usize],
+                    _ => {
+                        return Err(TransformError("invalid file index in debug_macinfo").into());
+                    }
+                };
+                gimli::leb128::write::unsigned(out, index)?;
+            }
+            DW_MACINFO_END_FILE => {}
+            DW_MACINFO_VENDOR_EXT => {
+                gimli::leb128::write::unsigned(out, input.read_uleb128()?)?;
+                out.extend_from_slice(input.read_null_terminated_slice()?.slice());
+                out.push(0);
+            }
+            _ => return Err(TransformError("unknown debug_macinfo entry").into()),
+        }
+    }
+    Ok(out_offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gimli::LineEncoding;
+
+    fn line_string(s: &str) -> write::LineString {
+        write::LineString::String(s.as_bytes().to_vec())
+    }
+
+    /// Returns the entries of a unit's `.debug_macinfo` starting `file`.
+    fn start_file_macinfo(file: u8) -> Vec<u8> {
+        let mut macinfo = vec![DW_MACINFO_START_FILE, 0, file];
+        macinfo.push(DW_MACINFO_DEFINE);
+        macinfo.push(1);
+        macinfo.extend_from_slice(b"X 1\0");
+        macinfo.extend_from_slice(&[DW_MACINFO_END_FILE, 0]);
+        macinfo
+    }
+
+    #[test]
+    fn test_start_file_matches_line_program() {
+        let mut program = write::LineProgram::new(
+            gimli::Encoding {
+                format: gimli::Format::Dwarf32,
+                version: 3,
+                address_size: 8,
+            },
+            LineEncoding {
+                minimum_instruction_length: 1,
+                maximum_operations_per_instruction: 1,
+                default_is_stmt: true,
+                line_base: -5,
+                line_range: 14,
+            },
+            line_string("/src"),
+            line_string("a.c"),
+            None,
+        );
+        let dir = program.default_directory();
+        // The input line table lists `a.c` twice, which the output line
+        // program holds once.
+        let files: Vec<_> = ["a.c", "b.h", "a.c"]
+            .iter()
+            .map(|name| program.add_file(line_string(name), dir, None))
+            .collect();
+        let file_indices = output_file_indices(&files);
+        assert_eq!(file_indices, [1, 2, 1]);
+
+        for (input, output) in [(1u8, 1u8), (2, 2), (3, 1)].iter() {
+            let mut out = vec![0xff];
+            let offset = clone_macinfo(&start_file_macinfo(*input), 0, &file_indices, &mut out)
+                .expect("valid macinfo");
+            assert_eq!(offset, 1);
+            assert_eq!(out[1..], start_file_macinfo(*output)[..]);
+            assert_eq!(
+                files[(*input - 1) as usize],
+                files[(file_indices[(*input - 1) as usize] - 1) as usize]
+            );
+        }
+    }
+
+    #[test]
+    fn test_start_file_out_of_range() {
+        let mut out = Vec::new();
+        assert!(clone_macinfo(&start_file_macinfo(4), 0, &[1, 2, 1], &mut out).is_err());
+        assert!(clone_macinfo(&start_file_macinfo(0), 0, &[1, 2, 1], &mut out).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use crate::transform::TransformedDwarf;
+use gimli::write;
+
+/// Returns the `DW_AT_producer` of the compile units, naming the crates that
+/// generated the code.
+fn producer() -> String {
+    format!(
+        "wasmtime-debug {} (cranelift {})",
+        env!("CARGO_PKG_VERSION"),
+        cranelift_codegen::VERSION
+    )
+}
+
+/// Sets the `DW_AT_producer` of every compile unit to this crate and
+/// Cranelift, which generated the code they now describe, replacing that of
+/// the compiler of the original wasm. The units' languages are those of
+/// their sources, and left as they are; DWARF has none for WebAssembly, so
+/// the units that are added for modules without DWARF of their own have no
+/// `DW_AT_language`.
+pub fn set_producer(dwarf: &mut TransformedDwarf) {
+    let producer = dwarf.strings.add(producer());
+    for index in 0..dwarf.units.count() {
+        let unit = dwarf.units.get_mut(dwarf.units.id(index));
+        let root = unit.root();
+        unit.get_mut(root).set(
+            gimli::DW_AT_producer,
+            write::AttributeValue::StringRef(producer),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_producer_of_every_unit() {
+        let encoding = gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version: 4,
+            address_size: 8,
+        };
+        let mut dwarf = TransformedDwarf {
+            encoding,
+            strings: write::StringTable::default(),
+            units: write::UnitTable::default(),
+            line_strings: write::LineStringTable::default(),
+            range_lists: write::RangeListTable::default(),
+            debug_macinfo: None,
+        };
+        let original = dwarf.strings.add("clang version 8.0.0");
+        for _ in 0..2 {
+            let unit_id = dwarf
+                .units
+                .add(write::Unit::new(encoding, write::LineProgram::none()));
+            let unit = dwarf.units.get_mut(unit_id);
+            let root = unit.root();
+            unit.get_mut(root).set(
+                gimli::DW_AT_producer,
+                write::AttributeValue::StringRef(original),
+            );
+        }
+        dwarf
+            .units
+            .add(write::Unit::new(encoding, write::LineProgram::none()));
+        set_producer(&mut dwarf);
+
+        assert!(producer().contains(cranelift_codegen::VERSION));
+        let expected = write::AttributeValue::StringRef(dwarf.strings.add(producer()));
+        assert_eq!(dwarf.units.count(), 3);
+        for index in 0..dwarf.units.count() {
+            let unit = dwarf.units.get(dwarf.units.id(index));
+            let root = unit.get(unit.root());
+            assert_eq!(root.get(gimli::DW_AT_producer), Some(&expected));
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use wasmparser::{ModuleReader, SectionCode};
+use wasmparser::{ImportSectionEntryType, ModuleReader, Name, Section, SectionCode};
 
 use gimli;
 
@@ -19,20 +19,30 @@ pub type Dwarf<'input> = gimli::Dwarf<gimli::EndianSlice<'input, LittleEndian>>;
 pub struct WasmFileInfo {
     pub code_section_offset: u64,
     pub function_offsets_and_sizes: Box<[(u64, u32)]>,
+    /// The number of imported functions, which come first in the function
+    /// index space.
+    pub imported_func_count: u32,
+    /// Function names from the `name` section, by function index.
+    pub func_names: HashMap<u32, String>,
 }
 
 #[derive(Debug)]
 pub struct DebugInfoData<'a> {
     pub dwarf: Dwarf<'a>,
     pub wasm_file: WasmFileInfo,
+    /// The `.debug_macinfo` section, which gimli doesn't read or write.
+    pub debug_macinfo: Option<&'a [u8]>,
 }
 
 fn convert_sections<'a>(sections: HashMap<&str, &'a [u8]>) -> Dwarf<'a> {
     let endian = LittleEndian;
-    let debug_str = DebugStr::new(sections[".debug_str"], endian);
-    let debug_abbrev = DebugAbbrev::new(sections[".debug_abbrev"], endian);
-    let debug_info = DebugInfo::new(sections[".debug_info"], endian);
-    let debug_line = DebugLine::new(sections[".debug_line"], endian);
+    // A module without DWARF has none of these sections, and reads as
+    // having no units.
+    let section = |name: &str| sections.get(name).cloned().unwrap_or(&[]);
+    let debug_str = DebugStr::new(section(".debug_str"), endian);
+    let debug_abbrev = DebugAbbrev::new(section(".debug_abbrev"), endian);
+    let debug_info = DebugInfo::new(section(".debug_info"), endian);
+    let debug_line = DebugLine::new(section(".debug_line"), endian);
 
     if sections.contains_key(".debug_addr") {
         panic!("Unexpected .debug_addr");
@@ -96,14 +106,46 @@ fn convert_sections<'a>(sections: HashMap<&str, &'a [u8]>) -> Dwarf<'a> {
     }
 }
 
+/// Reads the function names from a `name` section. The section is only
+/// informative, so a malformed one is reported as an error for the caller
+/// to ignore.
+fn read_func_names(section: &Section) -> wasmparser::Result<HashMap<u32, String>> {
+    let mut func_names = HashMap::new();
+    let mut reader = section.get_name_section_reader()?;
+    while !reader.eof() {
+        if let Name::Function(names) = reader.read()? {
+            let mut map = names.get_map()?;
+            for _ in 0..map.get_count() {
+                let naming = map.read()?;
+                func_names.insert(naming.index, naming.name.to_string());
+            }
+        }
+    }
+    Ok(func_names)
+}
+
 pub fn read_debuginfo(data: &[u8]) -> DebugInfoData {
     let mut reader = ModuleReader::new(data).expect("reader");
     let mut sections = HashMap::new();
     let mut code_section_offset = 0;
     let mut function_offsets_and_sizes = Vec::new();
+    let mut imported_func_count = 0;
+    let mut func_names = HashMap::new();
     while !reader.eof() {
         let section = reader.read().expect("section");
+        if let SectionCode::Import = section.code {
+            let mut reader = section.get_import_section_reader().expect("import reader");
+            for _ in 0..reader.get_count() {
+                let import = reader.read().expect("import read");
+                if let ImportSectionEntryType::Function(_) = import.ty {
+                    imported_func_count += 1;
+                }
+            }
+        }
         if let SectionCode::Custom { name, .. } = section.code {
+            if name == "name" {
+                func_names = read_func_names(&section).unwrap_or_default();
+            }
             if name.starts_with(".debug_") {
                 let mut reader = section.get_binary_reader();
                 let len = reader.bytes_remaining();
@@ -124,11 +166,15 @@ pub fn read_debuginfo(data: &[u8]) -> DebugInfoData {
         }
     }
     let function_offsets_and_sizes = function_offsets_and_sizes.into_boxed_slice();
+    let debug_macinfo = sections.get(".debug_macinfo").cloned();
     DebugInfoData {
         dwarf: convert_sections(sections),
+        debug_macinfo,
         wasm_file: WasmFileInfo {
             code_section_offset,
             function_offsets_and_sizes,
+            imported_func_count,
+            func_names,
         },
     }
 }
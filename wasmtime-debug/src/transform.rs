@@ -1,4 +1,5 @@
 use crate::address_transform::AddressTransform;
+use crate::macinfo::{clone_macinfo, output_file_indices};
 pub use crate::read_debuginfo::DebugInfoData;
 use cranelift_codegen::isa::TargetFrontendConfig;
 use cranelift_entity::EntityRef;
@@ -22,7 +23,7 @@ impl<'input, Endian> Reader for gimli::EndianSlice<'input, Endian> where Endian:
 
 #[derive(Fail, Debug)]
 #[fail(display = "Debug info transform error: {}", _0)]
-pub struct TransformError(&'static str);
+pub struct TransformError(pub(crate) &'static str);
 
 pub struct TransformedDwarf {
     pub encoding: gimli::Encoding,
@@ -30,6 +31,9 @@ pub struct TransformedDwarf {
     pub units: write::UnitTable,
     pub line_strings: write::LineStringTable,
     pub range_lists: write::RangeListTable,
+    /// The `.debug_macinfo` entries of the units, with the file indices of
+    /// `DW_MACINFO_start_file` mapped into the rewritten line programs.
+    pub debug_macinfo: Option<Vec<u8>>,
 }
 
 struct DebugInputContext<'a, R>
@@ -43,6 +47,7 @@ where
     debug_addr_base: DebugAddrBase<R::Offset>,
     rnglists: &'a RangeLists<R>,
     loclists: &'a LocationLists<R>,
+    debug_macinfo: Option<&'a [u8]>,
 }
 
 type PendingDieRef = (write::UnitEntryId, gimli::DwAt, UnitOffset);
@@ -72,6 +77,11 @@ where
     let mut attrs = entry.attrs();
     let mut low_pc = None;
     while let Some(attr) = attrs.next()? {
+        // `.debug_macro` refers to `.debug_str` and `.debug_line` offsets,
+        // which change when they are rewritten.
+        if attr.name() == gimli::DW_AT_macros || attr.name() == gimli::DW_AT_GNU_macros {
+            return Err(TransformError("DW_AT_macros (.debug_macro) is not supported").into());
+        }
         let attr_value = match attr.value() {
             AttributeValue::Addr(_)
                 if attr.name() == gimli::DW_AT_low_pc && subprogram_range.is_some() =>
@@ -120,6 +130,10 @@ where
                     return Err(TransformError("unexpected file index attribute").into());
                 }
             }
+            AttributeValue::DebugMacinfoRef(_) => {
+                // Set by `clone_unit` with the offset of the cloned entries.
+                continue;
+            }
             AttributeValue::DebugStrRef(str_offset) => {
                 let s = context.debug_str.get_str(str_offset)?.to_slice()?.to_vec();
                 write::AttributeValue::StringRef(out_strings.add(s))
@@ -460,6 +474,7 @@ fn clone_unit<'a, R>(
     out_encoding: &gimli::Encoding,
     out_units: &mut write::UnitTable,
     out_strings: &mut write::StringTable,
+    out_macinfo: &mut Vec<u8>,
 ) -> Result<(), Error>
 where
     R: Reader,
@@ -505,6 +520,24 @@ where
                 FileAttributeContext::Root(Some(debug_line_offset)),
             )?;
 
+            if let Some(AttributeValue::DebugMacinfoRef(offset)) =
+                entry.attr_value(gimli::DW_AT_macro_info)?
+            {
+                let debug_macinfo = context
+                    .debug_macinfo
+                    .ok_or(TransformError("debug_macinfo not found"))?;
+                let out_offset = clone_macinfo(
+                    debug_macinfo,
+                    offset.0,
+                    &output_file_indices(&file_map),
+                    out_macinfo,
+                )?;
+                comp_unit.get_mut(root_id).set(
+                    gimli::DW_AT_macro_info,
+                    write::AttributeValue::DebugMacinfoRef(gimli::DebugMacinfoOffset(out_offset)),
+                );
+            }
+
             stack.push(root_id);
             (comp_unit, file_map)
         } else {
@@ -587,6 +620,7 @@ pub fn transform_dwarf(
         debug_addr_base: DebugAddrBase(0),
         rnglists: &di.dwarf.ranges,
         loclists: &di.dwarf.locations,
+        debug_macinfo: di.debug_macinfo,
     };
 
     let out_encoding = gimli::Encoding {
@@ -601,6 +635,7 @@ pub fn transform_dwarf(
 
     let mut out_strings = write::StringTable::default();
     let mut out_units = write::UnitTable::default();
+    let mut out_macinfo = Vec::new();
 
     let out_range_lists = write::RangeListTable::default();
     let out_line_strings = write::LineStringTable::default();
@@ -614,6 +649,7 @@ pub fn transform_dwarf(
             &out_encoding,
             &mut out_units,
             &mut out_strings,
+            &mut out_macinfo,
         )?;
     }
 
@@ -634,5 +670,10 @@ pub fn transform_dwarf(
         units: out_units,
         line_strings: out_line_strings,
         range_lists: out_range_lists,
+        debug_macinfo: if out_macinfo.is_empty() {
+            None
+        } else {
+            Some(out_macinfo)
+        },
     })
 }
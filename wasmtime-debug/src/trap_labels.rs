@@ -0,0 +1,113 @@
+use crate::transform::TransformedDwarf;
+use cranelift_entity::EntityRef;
+use gimli::write;
+use wasmtime_environ::TrapInformation;
+
+/// Adds a compile unit holding one `DW_TAG_label` per trap site, named after
+/// the trap code and located at the trapping instruction, so that unmodified
+/// debuggers can show where and why generated code traps.
+pub fn add_trap_labels(dwarf: &mut TransformedDwarf, traps: &TrapInformation) {
+    let unit_id = dwarf
+        .units
+        .add(write::Unit::new(dwarf.encoding, write::LineProgram::none()));
+    let unit = dwarf.units.get_mut(unit_id);
+    let root = unit.root();
+    unit.get_mut(root).set(
+        gimli::DW_AT_name,
+        write::AttributeValue::StringRef(dwarf.strings.add("wasm traps")),
+    );
+
+    for (func, sites) in traps.iter() {
+        for site in sites {
+            let label_id = unit.add(root, gimli::DW_TAG_label);
+            let label = unit.get_mut(label_id);
+            let name = format!("trap: {}", site.trap_code);
+            label.set(
+                gimli::DW_AT_name,
+                write::AttributeValue::StringRef(dwarf.strings.add(name)),
+            );
+            label.set(
+                gimli::DW_AT_low_pc,
+                write::AttributeValue::Address(write::Address::Relative {
+                    symbol: func.index(),
+                    addend: i64::from(site.code_offset),
+                }),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::ir::{SourceLoc, TrapCode};
+    use wasmtime_environ::TrapSite;
+
+    fn trap_site(code_offset: u32, trap_code: TrapCode) -> TrapSite {
+        TrapSite {
+            code_offset,
+            trap_code,
+            source_loc: SourceLoc::new(code_offset),
+        }
+    }
+
+    #[test]
+    fn test_trap_labels_at_trap_offsets() {
+        let mut traps = TrapInformation::new();
+        traps.push(vec![
+            trap_site(0x4, TrapCode::HeapOutOfBounds),
+            trap_site(0x1c, TrapCode::IntegerDivisionByZero),
+        ]);
+        traps.push(Vec::new());
+        traps.push(vec![trap_site(0x8, TrapCode::UnreachableCodeReached)]);
+
+        let mut dwarf = TransformedDwarf {
+            encoding: gimli::Encoding {
+                format: gimli::Format::Dwarf32,
+                version: 4,
+                address_size: 8,
+            },
+            strings: write::StringTable::default(),
+            units: write::UnitTable::default(),
+            line_strings: write::LineStringTable::default(),
+            range_lists: write::RangeListTable::default(),
+            debug_macinfo: None,
+        };
+        add_trap_labels(&mut dwarf, &traps);
+
+        assert_eq!(dwarf.units.count(), 1);
+        let unit = dwarf.units.get(dwarf.units.id(0));
+        let root = unit.get(unit.root());
+        assert_eq!(
+            root.get(gimli::DW_AT_name),
+            Some(&write::AttributeValue::StringRef(
+                dwarf.strings.add("wasm traps")
+            ))
+        );
+
+        let labels = root
+            .children()
+            .map(|&child| unit.get(child))
+            .collect::<Vec<_>>();
+        let expected = [
+            (0, 0x4, "trap: heap_oob"),
+            (0, 0x1c, "trap: int_divz"),
+            (2, 0x8, "trap: unreachable"),
+        ];
+        assert_eq!(labels.len(), expected.len());
+        for (label, &(func, offset, name)) in labels.iter().zip(expected.iter()) {
+            assert_eq!(label.tag(), gimli::DW_TAG_label);
+            assert_eq!(
+                label.get(gimli::DW_AT_low_pc),
+                Some(&write::AttributeValue::Address(write::Address::Relative {
+                    symbol: func,
+                    addend: offset,
+                }))
+            );
+            assert_eq!(
+                label.get(gimli::DW_AT_name),
+                Some(&write::AttributeValue::StringRef(dwarf.strings.add(name)))
+            );
+        }
+    }
+}
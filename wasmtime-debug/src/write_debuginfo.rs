@@ -5,9 +5,11 @@ use gimli::write::{
     EndianVec, Result, SectionId, Sections, Writer,
 };
 use gimli::RunTimeEndian;
+use target_lexicon::{Endianness, Triple};
 
 use faerie::artifact::Decl;
 use faerie::*;
+use failure::Error;
 
 struct DebugReloc {
     offset: u32,
@@ -57,12 +59,26 @@ pub trait SymbolResolver {
     fn resolve_symbol(&self, symbol: usize, addend: i64) -> ResolvedSymbol;
 }
 
+#[derive(Fail, Debug)]
+#[fail(display = "Unknown endianness for target {}", _0)]
+pub struct UnknownEndianness(String);
+
+/// Returns the byte order of the debug sections for `triple`, which must
+/// match the byte order of the object file.
+fn debug_endianness(triple: &Triple) -> ::std::result::Result<RunTimeEndian, UnknownEndianness> {
+    match triple.endianness() {
+        Ok(Endianness::Big) => Ok(RunTimeEndian::Big),
+        Ok(Endianness::Little) => Ok(RunTimeEndian::Little),
+        Err(()) => Err(UnknownEndianness(triple.to_string())),
+    }
+}
+
 pub fn emit_dwarf(
     artifact: &mut Artifact,
     mut dwarf: TransformedDwarf,
     symbol_resolver: &SymbolResolver,
-) {
-    let endian = RunTimeEndian::Little;
+) -> ::std::result::Result<(), Error> {
+    let endian = debug_endianness(&artifact.target)?;
     let debug_abbrev = DebugAbbrev::from(WriterRelocate::new(endian, symbol_resolver));
     let debug_info = DebugInfo::from(WriterRelocate::new(endian, symbol_resolver));
     let debug_str = DebugStr::from(WriterRelocate::new(endian, symbol_resolver));
@@ -106,6 +122,18 @@ pub fn emit_dwarf(
         decl_section!(artifact.DebugRngLists = sections.debug_rnglists);
     }
 
+    // Macro entries are ULEB128 numbers and strings, so the section needs
+    // neither byte swapping nor relocations.
+    if let Some(debug_macinfo) = dwarf.debug_macinfo.take() {
+        artifact
+            .declare_with(
+                SectionId::DebugMacinfo.name(),
+                Decl::debug_section(),
+                debug_macinfo,
+            )
+            .unwrap();
+    }
+
     sect_relocs!(artifact.DebugAbbrev = sections.debug_abbrev);
     sect_relocs!(artifact.DebugInfo = sections.debug_info);
     sect_relocs!(artifact.DebugStr = sections.debug_str);
@@ -118,6 +146,7 @@ pub fn emit_dwarf(
     if debug_rnglists_not_empty {
         sect_relocs!(artifact.DebugRngLists = sections.debug_rnglists);
     }
+    Ok(())
 }
 
 struct WriterRelocate<'a> {
@@ -205,3 +234,35 @@ impl<'a> Writer for WriterRelocate<'a> {
         self.write_word_at(offset, val as u64, size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_debug_endianness() {
+        let little = Triple::from_str("x86_64-unknown-linux-gnu").unwrap();
+        let big = Triple::from_str("s390x-unknown-linux-gnu").unwrap();
+        assert_eq!(debug_endianness(&little).unwrap(), RunTimeEndian::Little);
+        assert_eq!(debug_endianness(&big).unwrap(), RunTimeEndian::Big);
+
+        // Sections written with the target's endianness read back with it.
+        let mut writer = EndianVec::new(debug_endianness(&big).unwrap());
+        writer.write_u32(0x0102_0304).unwrap();
+        let mut reader = gimli::EndianSlice::new(writer.slice(), RunTimeEndian::Big);
+        assert_eq!(gimli::Reader::read_u32(&mut reader).unwrap(), 0x0102_0304);
+        assert_eq!(writer.slice(), &[1, 2, 3, 4]);
+    }
+
+    /// A target whose endianness isn't known is an error, not a panic.
+    #[test]
+    fn test_debug_endianness_unknown() {
+        let unknown = Triple::default();
+        let error = debug_endianness(&unknown).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("Unknown endianness for target {}", unknown)
+        );
+    }
+}
@@ -0,0 +1,442 @@
+//! Caching of compiled functions, so that compiling the same module again,
+//! such as with only the debug flags changed, can skip code generation.
+//!
+//! Entries are content-addressed: the key of a function is a digest of its
+//! body bytes and everything else its code depends on, that is the target
+//! ISA and its settings, the parts of the module the function environment
+//! reads, which carry the tunables, and the version of this crate.
+
+use crate::compilation::{InstructionAddressTransform, Relocation, RelocationTarget, TrapSite};
+use crate::module::{MemoryStyle, Module, TableStyle};
+use crate::module_environ::FunctionBodyData;
+use core::mem;
+use cranelift_codegen::binemit;
+use cranelift_codegen::ir;
+use cranelift_codegen::isa;
+use cranelift_entity::EntityRef;
+use cranelift_wasm::{FuncIndex, GlobalInit, MemoryIndex, TableElementType};
+use std::str;
+use std::string::ToString;
+use std::vec::Vec;
+
+/// A store of compiled functions, consulted by `compile_module_with_options`.
+///
+/// Keys and values are opaque bytes. A cache may drop entries at any time,
+/// and failing to store one isn't an error, since the function is just
+/// compiled again next time.
+pub trait CompilationCache: Sync {
+    /// Returns the value stored for `key`, if any.
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+
+    /// Stores `value` for `key`, replacing any previous value.
+    fn put(&self, key: &[u8], value: &[u8]);
+}
+
+/// Version of the layout of keys and values, to invalidate old entries when
+/// it changes.
+const CACHE_FORMAT_VERSION: u32 = 2;
+
+/// Relocation kinds, in the order of their codes in cached values.
+const RELOC_KINDS: &[binemit::Reloc] = &[
+    binemit::Reloc::Abs4,
+    binemit::Reloc::Abs8,
+    binemit::Reloc::X86PCRel4,
+    binemit::Reloc::X86CallPCRel4,
+    binemit::Reloc::X86CallPLTRel4,
+    binemit::Reloc::X86GOTPCRel4,
+    binemit::Reloc::Arm32Call,
+    binemit::Reloc::Arm64Call,
+    binemit::Reloc::RiscvCall,
+];
+
+/// The outputs of compiling one function that are kept in the cache.
+pub(crate) struct CachedFunction {
+    pub code: Vec<u8>,
+    pub jt_offsets: ir::JumpTableOffsets,
+    pub relocs: Vec<Relocation>,
+    pub locations: Vec<InstructionAddressTransform>,
+    pub frame_size: u32,
+    pub traps: Vec<TrapSite>,
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    push_u32(out, bytes.len() as u32);
+    out.extend_from_slice(bytes);
+}
+
+/// Reads back what the `push_*` functions wrote, returning `None` once the
+/// bytes run out.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.bytes.len() {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Some(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Some(u64::from_le_bytes(bytes))
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn str(&mut self) -> Option<&'a str> {
+        str::from_utf8(self.bytes()?).ok()
+    }
+}
+
+/// Appends `value`, `None` being distinct from every `Some`.
+fn push_option_u32(out: &mut Vec<u8>, value: Option<u32>) {
+    match value {
+        Some(value) => {
+            push_u32(out, 1);
+            push_u32(out, value);
+        }
+        None => push_u32(out, 0),
+    }
+}
+
+/// Appends the parts of `module` the function environment reads when
+/// translating a function: the signatures and function types, the table
+/// and memory plans, which carry the tunables, the globals, and the import
+/// counts, which decide the vmctx offsets.
+fn push_environment(out: &mut Vec<u8>, module: &Module) {
+    push_u32(out, module.signatures.len() as u32);
+    for (_, signature) in module.signatures.iter() {
+        push_bytes(out, signature.to_string().as_bytes());
+    }
+
+    push_u32(out, module.functions.len() as u32);
+    for (_, signature) in module.functions.iter() {
+        push_u32(out, signature.as_u32());
+    }
+
+    push_u32(out, module.table_plans.len() as u32);
+    for (_, plan) in module.table_plans.iter() {
+        match plan.table.ty {
+            TableElementType::Val(ty) => {
+                push_u32(out, 0);
+                push_bytes(out, ty.to_string().as_bytes());
+            }
+            TableElementType::Func => push_u32(out, 1),
+        }
+        push_u32(out, plan.table.minimum);
+        push_option_u32(out, plan.table.maximum);
+        push_u32(
+            out,
+            match plan.style {
+                TableStyle::CallerChecksSignature => 0,
+                TableStyle::CallerChecksRelocatedSignature => 1,
+            },
+        );
+    }
+
+    push_u32(out, module.memory_plans.len() as u32);
+    for (_, plan) in module.memory_plans.iter() {
+        push_u32(out, plan.memory.minimum);
+        push_option_u32(out, plan.memory.maximum);
+        push_u32(out, u32::from(plan.memory.shared));
+        push_option_u32(
+            out,
+            match plan.style {
+                MemoryStyle::Dynamic => None,
+                MemoryStyle::Static { bound } => Some(bound),
+            },
+        );
+        push_u64(out, plan.offset_guard_size);
+        push_u32(out, u32::from(plan.relocated_base));
+    }
+
+    push_u32(out, module.globals.len() as u32);
+    for (_, global) in module.globals.iter() {
+        push_bytes(out, global.ty.to_string().as_bytes());
+        push_u32(out, u32::from(global.mutability));
+        let (tag, value) = match global.initializer {
+            GlobalInit::I32Const(value) => (0, u64::from(value as u32)),
+            GlobalInit::I64Const(value) => (1, value as u64),
+            GlobalInit::F32Const(bits) => (2, u64::from(bits)),
+            GlobalInit::F64Const(bits) => (3, bits),
+            GlobalInit::GetGlobal(index) => (4, u64::from(index.as_u32())),
+            GlobalInit::Import => (5, 0),
+        };
+        push_u32(out, tag);
+        push_u64(out, value);
+    }
+
+    push_u32(out, module.imported_funcs.len() as u32);
+    push_u32(out, module.imported_tables.len() as u32);
+    push_u32(out, module.imported_memories.len() as u32);
+    push_u32(out, module.imported_globals.len() as u32);
+}
+
+/// Returns the cache key of the function `func_index` with body `input`,
+/// compiled with or without `bounds_check_hoisting`.
+///
+/// The key is the SHA-1 digest of everything the function's code depends
+/// on, each field encoded explicitly in little-endian order, so that keys
+/// are the same across processes, hosts and compiler versions for the same
+/// inputs.
+pub(crate) fn cache_key(
+    module: &Module,
+    isa: &dyn isa::TargetIsa,
+    bounds_check_hoisting: bool,
+    func_index: FuncIndex,
+    input: &FunctionBodyData,
+) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    push_bytes(&mut encoded, crate::VERSION.as_bytes());
+    push_u32(&mut encoded, CACHE_FORMAT_VERSION);
+    push_bytes(&mut encoded, isa.triple().to_string().as_bytes());
+    push_bytes(&mut encoded, isa.to_string().as_bytes());
+    push_u32(&mut encoded, u32::from(bounds_check_hoisting));
+    push_environment(&mut encoded, module);
+    push_u32(&mut encoded, func_index.as_u32());
+    push_u64(&mut encoded, input.module_offset as u64);
+    push_bytes(&mut encoded, input.data);
+
+    let mut hasher = sha1::Sha1::new();
+    hasher.update(&encoded);
+    hasher.digest().bytes().to_vec()
+}
+
+fn encode_target(out: &mut Vec<u8>, target: RelocationTarget) {
+    let (tag, value) = match target {
+        RelocationTarget::UserFunc(index) => (0, index.index() as u32),
+        RelocationTarget::LibCall(libcall) => {
+            push_u32(out, 1);
+            push_bytes(out, libcall.to_string().as_bytes());
+            return;
+        }
+        RelocationTarget::Memory32Grow => (2, 0),
+        RelocationTarget::ImportedMemory32Grow => (3, 0),
+        RelocationTarget::Memory32Size => (4, 0),
+        RelocationTarget::ImportedMemory32Size => (5, 0),
+        RelocationTarget::SignatureTableBase => (6, 0),
+        RelocationTarget::Ebb(offset) => (7, offset),
+        RelocationTarget::JumpTable(jt) => (8, jt.index() as u32),
+        RelocationTarget::MemoryBase { memory_index } => (9, memory_index.as_u32()),
+    };
+    push_u32(out, tag);
+    push_u32(out, value);
+}
+
+fn decode_target(reader: &mut Reader) -> Option<RelocationTarget> {
+    let tag = reader.u32()?;
+    if tag == 1 {
+        return reader.str()?.parse().ok().map(RelocationTarget::LibCall);
+    }
+    let value = reader.u32()?;
+    Some(match tag {
+        0 => RelocationTarget::UserFunc(FuncIndex::from_u32(value)),
+        2 => RelocationTarget::Memory32Grow,
+        3 => RelocationTarget::ImportedMemory32Grow,
+        4 => RelocationTarget::Memory32Size,
+        5 => RelocationTarget::ImportedMemory32Size,
+        6 => RelocationTarget::SignatureTableBase,
+        7 => RelocationTarget::Ebb(value),
+        8 => RelocationTarget::JumpTable(ir::JumpTable::new(value as usize)),
+        9 => RelocationTarget::MemoryBase {
+            memory_index: MemoryIndex::from_u32(value),
+        },
+        _ => return None,
+    })
+}
+
+/// Encodes a compiled function as a cache value, returning `None` if it
+/// has a relocation kind cache values have no code for, so that the
+/// function is just not cached.
+pub(crate) fn encode_cached_function(function: &CachedFunction) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    push_bytes(&mut out, &function.code);
+
+    push_u32(&mut out, function.jt_offsets.values().len() as u32);
+    for (_, &offset) in function.jt_offsets.iter() {
+        push_u32(&mut out, offset);
+    }
+
+    push_u32(&mut out, function.relocs.len() as u32);
+    for r in &function.relocs {
+        let kind = RELOC_KINDS
+            .iter()
+            .position(|kind| mem::discriminant(kind) == mem::discriminant(&r.reloc))?;
+        push_u32(&mut out, kind as u32);
+        encode_target(&mut out, r.reloc_target);
+        push_u32(&mut out, r.offset);
+        push_u64(&mut out, r.addend as u64);
+    }
+
+    push_u32(&mut out, function.locations.len() as u32);
+    for location in &function.locations {
+        push_u32(&mut out, location.srcloc.bits());
+        push_u64(&mut out, location.code_offset as u64);
+        push_u64(&mut out, location.code_len as u64);
+    }
+
+    push_u32(&mut out, function.frame_size);
+
+    push_u32(&mut out, function.traps.len() as u32);
+    for trap in &function.traps {
+        push_u32(&mut out, trap.code_offset);
+        push_bytes(&mut out, trap.trap_code.to_string().as_bytes());
+        push_u32(&mut out, trap.source_loc.bits());
+    }
+    Some(out)
+}
+
+/// Decodes a cache value, returning `None` if it is malformed, so that a
+/// damaged entry is treated as a miss.
+pub(crate) fn decode_cached_function(bytes: &[u8]) -> Option<CachedFunction> {
+    let mut reader = Reader { bytes };
+    let code = reader.bytes()?.to_vec();
+
+    let mut jt_offsets = ir::JumpTableOffsets::new();
+    for jt in 0..reader.u32()? {
+        jt_offsets[ir::JumpTable::new(jt as usize)] = reader.u32()?;
+    }
+
+    let mut relocs = Vec::new();
+    for _ in 0..reader.u32()? {
+        let reloc = *RELOC_KINDS.get(reader.u32()? as usize)?;
+        let reloc_target = decode_target(&mut reader)?;
+        let offset = reader.u32()?;
+        let addend = reader.u64()? as binemit::Addend;
+        relocs.push(Relocation {
+            reloc,
+            reloc_target,
+            offset,
+            addend,
+        });
+    }
+
+    let mut locations = Vec::new();
+    for _ in 0..reader.u32()? {
+        locations.push(InstructionAddressTransform {
+            srcloc: ir::SourceLoc::new(reader.u32()?),
+            code_offset: reader.u64()? as usize,
+            code_len: reader.u64()? as usize,
+        });
+    }
+
+    let frame_size = reader.u32()?;
+
+    let mut traps = Vec::new();
+    for _ in 0..reader.u32()? {
+        let code_offset = reader.u32()?;
+        let trap_code = reader.str()?.parse().ok()?;
+        let source_loc = ir::SourceLoc::new(reader.u32()?);
+        traps.push(TrapSite {
+            code_offset,
+            trap_code,
+            source_loc,
+        });
+    }
+
+    if !reader.bytes.is_empty() {
+        return None;
+    }
+    Some(CachedFunction {
+        code,
+        jt_offsets,
+        relocs,
+        locations,
+        frame_size,
+        traps,
+    })
+}
+
+#[cfg(feature = "std")]
+mod file_system {
+    use super::CompilationCache;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    /// A `CompilationCache` keeping one file per entry in a directory.
+    ///
+    /// Files are named by the SHA-1 digest of the key, which is the same in
+    /// every process, and hold the key before the value. An entry whose key
+    /// doesn't match, such as a truncated file, is a miss. The keys of
+    /// `compile_module_with_options` are already digests, so functions whose
+    /// inputs collide under SHA-1 would share an entry.
+    pub struct FileSystemCache {
+        dir: PathBuf,
+    }
+
+    impl FileSystemCache {
+        /// Creates a cache in `dir`, which is created on the first store.
+        pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+            Self { dir: dir.into() }
+        }
+
+        fn path(&self, key: &[u8]) -> PathBuf {
+            let mut hasher = sha1::Sha1::new();
+            hasher.update(key);
+            self.dir.join(hasher.digest().to_string())
+        }
+    }
+
+    impl CompilationCache for FileSystemCache {
+        fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+            let entry = fs::read(self.path(key)).ok()?;
+            if entry.len() < 8 {
+                return None;
+            }
+            let mut len = [0; 8];
+            len.copy_from_slice(&entry[..8]);
+            let len = u64::from_le_bytes(len) as usize;
+            let (stored_key, value) = entry[8..].split_at(len.min(entry.len() - 8));
+            if stored_key != key {
+                return None;
+            }
+            Some(value.to_vec())
+        }
+
+        fn put(&self, key: &[u8], value: &[u8]) {
+            let mut entry = Vec::with_capacity(8 + key.len() + value.len());
+            entry.extend_from_slice(&(key.len() as u64).to_le_bytes());
+            entry.extend_from_slice(key);
+            entry.extend_from_slice(value);
+
+            // Write to a temporary file and rename it into place, so that
+            // concurrent compilations never read a partial entry.
+            let path = self.path(key);
+            let temp = path.with_extension(format!("{}.tmp", process::id()));
+            let written = fs::create_dir_all(&self.dir)
+                .and_then(|()| fs::write(&temp, &entry))
+                .and_then(|()| fs::rename(&temp, &path));
+            if written.is_err() {
+                let _ = fs::remove_file(&temp);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub use self::file_system::FileSystemCache;
@@ -0,0 +1,224 @@
+//! Analyses over the direct call graph recovered from a module's relocations.
+
+use crate::compilation::{FrameSizes, RelocationTarget, Relocations};
+use crate::module::Module;
+use cranelift_codegen::binemit::CodeOffset;
+use cranelift_entity::{EntityRef, PrimaryMap};
+use cranelift_wasm::{DefinedFuncIndex, FuncIndex};
+use std::vec::Vec;
+
+/// Returns the defined functions directly called by each defined function,
+/// derived from its `UserFunc` relocations.
+///
+/// Calls to imported functions and `call_indirect` go through the vmctx and
+/// leave no relocation behind, so they are not part of this graph.
+pub fn direct_callees(
+    module: &Module,
+    relocations: &Relocations,
+) -> PrimaryMap<DefinedFuncIndex, Vec<DefinedFuncIndex>> {
+    let mut result = PrimaryMap::with_capacity(relocations.len());
+    for (_, function_relocs) in relocations.iter() {
+        let mut callees = Vec::new();
+        for r in function_relocs {
+            if let RelocationTarget::UserFunc(func_index) = r.reloc_target {
+                if let Some(defined) = module.defined_func_index(func_index) {
+                    if !callees.contains(&defined) {
+                        callees.push(defined);
+                    }
+                }
+            }
+        }
+        result.push(callees);
+    }
+    result
+}
+
+/// Returns the direct call sites targeting each function in the module's
+/// function index space, as the calling function and the offset of the
+/// relocation within its body. This is the inverse of `direct_callees`;
+/// imported functions are only ever called through the vmctx, so their
+/// lists are empty.
+pub fn call_sites(
+    module: &Module,
+    relocations: &Relocations,
+) -> PrimaryMap<FuncIndex, Vec<(DefinedFuncIndex, CodeOffset)>> {
+    let mut result = PrimaryMap::with_capacity(module.functions.len());
+    for _ in 0..module.functions.len() {
+        result.push(Vec::new());
+    }
+    for (caller, function_relocs) in relocations.iter() {
+        for r in function_relocs {
+            if let RelocationTarget::UserFunc(func_index) = r.reloc_target {
+                result[func_index].push((caller, r.offset));
+            }
+        }
+    }
+    result
+}
+
+/// Computes the worst-case stack depth, in bytes, of a call to each defined
+/// function: its own frame size plus the deepest chain of direct callees.
+///
+/// Functions that can reach a recursive cycle have no finite bound and are
+/// reported as `None`.
+pub fn worst_case_stack_depths(
+    module: &Module,
+    relocations: &Relocations,
+    frame_sizes: &FrameSizes,
+) -> PrimaryMap<DefinedFuncIndex, Option<u64>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Unvisited,
+        OnStack,
+        Done,
+    }
+
+    let callees = direct_callees(module, relocations);
+    let mut state = vec![State::Unvisited; callees.len()];
+    let mut depths: Vec<Option<u64>> = vec![None; callees.len()];
+
+    for root in callees.keys() {
+        if state[root.index()] != State::Unvisited {
+            continue;
+        }
+        // Iterative post-order walk so deep call chains can't overflow our
+        // own stack. Each entry is a function and the next callee to visit.
+        let mut worklist = vec![(root, 0)];
+        state[root.index()] = State::OnStack;
+        while let Some(&mut (func, ref mut next)) = worklist.last_mut() {
+            if let Some(&callee) = callees[func].get(*next) {
+                *next += 1;
+                if state[callee.index()] == State::Unvisited {
+                    state[callee.index()] = State::OnStack;
+                    worklist.push((callee, 0));
+                }
+                continue;
+            }
+            worklist.pop();
+            let mut deepest = Some(0);
+            for callee in &callees[func] {
+                deepest = match state[callee.index()] {
+                    // A callee still on the stack closes a cycle.
+                    State::OnStack => None,
+                    _ => deepest.and_then(|d| depths[callee.index()].map(|c| d.max(c))),
+                };
+            }
+            depths[func.index()] = deepest.map(|d| d + u64::from(frame_sizes[func]));
+            state[func.index()] = State::Done;
+        }
+    }
+
+    let mut result = PrimaryMap::with_capacity(depths.len());
+    for depth in depths {
+        result.push(depth);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compilation::Relocation;
+    use cranelift_codegen::binemit::Reloc;
+    use cranelift_wasm::SignatureIndex;
+    use std::string::String;
+
+    /// A module importing one function and defining `calls.len()` others,
+    /// with the given frame sizes, where defined function `i` calls each
+    /// of `calls[i]`, as indices in the function index space.
+    fn graph(calls: &[&[RelocationTarget]], frames: &[u32]) -> (Module, Relocations, FrameSizes) {
+        let mut module = Module::new();
+        let sig = SignatureIndex::new(0);
+        module
+            .imported_funcs
+            .push((String::from("env"), String::from("f")));
+        module.functions.push(sig);
+        let mut relocations = PrimaryMap::new();
+        for targets in calls {
+            module.functions.push(sig);
+            relocations.push(
+                targets
+                    .iter()
+                    .enumerate()
+                    .map(|(n, &reloc_target)| Relocation {
+                        reloc: Reloc::Abs8,
+                        reloc_target,
+                        offset: 8 * n as CodeOffset,
+                        addend: 0,
+                    })
+                    .collect(),
+            );
+        }
+        let mut frame_sizes = PrimaryMap::new();
+        for &frame in frames {
+            frame_sizes.push(frame);
+        }
+        (module, relocations, frame_sizes)
+    }
+
+    fn call(func: usize) -> RelocationTarget {
+        RelocationTarget::UserFunc(FuncIndex::new(func))
+    }
+
+    fn depths(calls: &[&[RelocationTarget]], frames: &[u32]) -> Vec<Option<u64>> {
+        let (module, relocations, frame_sizes) = graph(calls, frames);
+        worst_case_stack_depths(&module, &relocations, &frame_sizes)
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn test_stack_depths_chain() {
+        // 0 -> 1 -> 2, and 0 -> 2 directly; calls to the import add nothing.
+        let depths = depths(
+            &[&[call(2), call(3)], &[call(3), call(0)], &[]],
+            &[16, 32, 8],
+        );
+        assert_eq!(depths, [Some(56), Some(40), Some(8)]);
+    }
+
+    #[test]
+    fn test_stack_depths_self_recursive() {
+        // 1 calls itself, and 0 calls 1; 2 is unaffected.
+        let depths = depths(&[&[call(2)], &[call(2)], &[]], &[16, 32, 8]);
+        assert_eq!(depths, [None, None, Some(8)]);
+    }
+
+    #[test]
+    fn test_stack_depths_mutual_recursion() {
+        // 0 and 1 call each other, and 2 calls into the cycle; 3 only calls
+        // 4, outside of it.
+        let depths = depths(
+            &[&[call(2)], &[call(1)], &[call(1)], &[call(5)], &[]],
+            &[16, 32, 8, 4, 2],
+        );
+        assert_eq!(depths, [None, None, None, Some(6), Some(2)]);
+    }
+
+    #[test]
+    fn test_stack_depths_indirect_call() {
+        // A `call_indirect` leaves no `UserFunc` relocation, so the callee
+        // it may reach is not counted.
+        let depths = depths(&[&[RelocationTarget::SignatureTableBase], &[]], &[16, 64]);
+        assert_eq!(depths, [Some(16), Some(64)]);
+        let (module, relocations, _) = graph(&[&[RelocationTarget::SignatureTableBase], &[]], &[]);
+        assert!(direct_callees(&module, &relocations)
+            .values()
+            .all(|callees| callees.is_empty()));
+    }
+
+    #[test]
+    fn test_call_sites() {
+        // Defined function 0 calls function 2 twice and function 3 once,
+        // and defined function 1 calls function 1.
+        let (module, relocations, _) = graph(&[&[call(2), call(3), call(2)], &[call(1)], &[]], &[]);
+        let sites = call_sites(&module, &relocations);
+        assert_eq!(sites.len(), 4);
+        let d = DefinedFuncIndex::new;
+        assert!(sites[FuncIndex::new(0)].is_empty());
+        assert_eq!(sites[FuncIndex::new(1)], [(d(1), 0)]);
+        assert_eq!(sites[FuncIndex::new(2)], [(d(0), 0), (d(0), 16)]);
+        assert_eq!(sites[FuncIndex::new(3)], [(d(0), 8)]);
+    }
+}
@@ -1,31 +1,111 @@
 //! A `Compilation` contains the compiled function bodies for a WebAssembly
 //! module.
 
+use core::fmt;
+use core::time::Duration;
 use cranelift_codegen::binemit;
 use cranelift_codegen::ir;
 use cranelift_codegen::CodegenError;
-use cranelift_entity::PrimaryMap;
-use cranelift_wasm::{DefinedFuncIndex, FuncIndex, WasmError};
+use cranelift_entity::{EntityRef, PrimaryMap};
+use cranelift_wasm::{DefinedFuncIndex, FuncIndex, MemoryIndex, WasmError};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::boxed::Box;
+use std::string::String;
 use std::vec::Vec;
 
 /// The result of compiling a WebAssembly module's functions.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Compilation {
     /// Compiled machine code for the function bodies.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::primary_map"))]
     pub functions: PrimaryMap<DefinedFuncIndex, Vec<u8>>,
+
+    /// Textual Cranelift IR of each function after code generation, if it
+    /// was requested.
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serialize::option_primary_map")
+    )]
+    pub ir: Option<PrimaryMap<DefinedFuncIndex, String>>,
+
+    /// Functions that failed to compile, when failures were allowed. Their
+    /// entries in `functions` are empty.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub failed: Vec<FailedFunction>,
+
+    /// Offset of each function's jump tables within its body, where their
+    /// data follows the code.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::jt_offsets"))]
+    pub jt_offsets: PrimaryMap<DefinedFuncIndex, ir::JumpTableOffsets>,
+
+    /// How long each function took to compile and the size of its code, if
+    /// it was requested.
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serialize::option_primary_map")
+    )]
+    pub stats: Option<CompileStats>,
+
+    /// Functions with the same signature and body as an earlier function,
+    /// paired with that function. They weren't compiled: their code is a
+    /// copy of the earlier function's, and their IR and stats are empty.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::entity_pairs"))]
+    pub duplicates: Vec<(DefinedFuncIndex, DefinedFuncIndex)>,
 }
 
 impl Compilation {
     /// Allocates the compilation result with the given function bodies.
     pub fn new(functions: PrimaryMap<DefinedFuncIndex, Vec<u8>>) -> Self {
-        Self { functions }
+        let mut jt_offsets = PrimaryMap::with_capacity(functions.len());
+        for _ in functions.keys() {
+            jt_offsets.push(ir::JumpTableOffsets::new());
+        }
+        Self {
+            functions,
+            ir: None,
+            failed: Vec::new(),
+            jt_offsets,
+            stats: None,
+            duplicates: Vec::new(),
+        }
     }
 }
 
+/// Statistics of compiling a single function.
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FunctionStats {
+    /// Time spent translating the wasm to Cranelift IR.
+    pub translation_time: Duration,
+    /// Time spent optimizing the IR and emitting machine code.
+    pub codegen_time: Duration,
+    /// Size in bytes of the machine code.
+    pub code_size: usize,
+}
+
+/// Statistics of compiling each function. Functions taken from a cache took
+/// no time, and those that failed to compile have empty statistics.
+pub type CompileStats = PrimaryMap<DefinedFuncIndex, FunctionStats>;
+
+/// A function that couldn't be compiled to machine code.
+#[derive(Debug)]
+pub struct FailedFunction {
+    /// The index of the function.
+    pub index: DefinedFuncIndex,
+    /// Why compiling it failed.
+    pub error: CompileError,
+    /// The function's original WebAssembly body, for an interpreter to run.
+    pub wasm_body: Vec<u8>,
+}
+
 /// A record of a relocation to perform.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Relocation {
     /// The relocation code.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::RelocDef"))]
     pub reloc: binemit::Reloc,
     /// Relocation target.
     pub reloc_target: RelocationTarget,
@@ -37,11 +117,15 @@ pub struct Relocation {
 
 /// Destination function. Can be either user function or some special one, like `memory.grow`.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum RelocationTarget {
     /// The user function index.
-    UserFunc(FuncIndex),
+    UserFunc(#[cfg_attr(feature = "serde", serde(with = "crate::serialize::entity"))] FuncIndex),
     /// A compiler-generated libcall.
-    LibCall(ir::LibCall),
+    LibCall(
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialize::display_from_str"))]
+        ir::LibCall,
+    ),
     /// Function for growing a locally-defined 32-bit memory by the specified amount of pages.
     Memory32Grow,
     /// Function for growing an imported 32-bit memory by the specified amount of pages.
@@ -50,27 +134,272 @@ pub enum RelocationTarget {
     Memory32Size,
     /// Function for query current size of an imported 32-bit linear memory.
     ImportedMemory32Size,
+    /// The table of signature ids, indexed by `SignatureIndex`, used to
+    /// check `call_indirect` with `TableStyle::CallerChecksRelocatedSignature`.
+    SignatureTableBase,
+    /// The base of a locally-defined memory whose plan has a
+    /// `relocated_base`.
+    MemoryBase {
+        /// The memory.
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialize::entity"))]
+        memory_index: MemoryIndex,
+    },
+    /// An offset within the relocated function's own body, such as an EBB
+    /// header, relative to the start of the body.
+    Ebb(binemit::CodeOffset),
+    /// A jump table of the relocated function, at the offset within its
+    /// body given by `Compilation::jt_offsets`.
+    JumpTable(
+        #[cfg_attr(feature = "serde", serde(with = "crate::serialize::entity"))] ir::JumpTable,
+    ),
+}
+
+impl fmt::Display for RelocationTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RelocationTarget::UserFunc(index) => write!(f, "function {}", index.index()),
+            RelocationTarget::LibCall(libcall) => write!(f, "libcall {}", libcall),
+            RelocationTarget::Memory32Grow => write!(f, "memory32_grow"),
+            RelocationTarget::ImportedMemory32Grow => write!(f, "imported_memory32_grow"),
+            RelocationTarget::Memory32Size => write!(f, "memory32_size"),
+            RelocationTarget::ImportedMemory32Size => write!(f, "imported_memory32_size"),
+            RelocationTarget::SignatureTableBase => write!(f, "signature table"),
+            RelocationTarget::MemoryBase { memory_index } => {
+                write!(f, "memory {} base", memory_index.index())
+            }
+            RelocationTarget::Ebb(offset) => write!(f, "ebb at {:#x}", offset),
+            RelocationTarget::JumpTable(jt) => write!(f, "{}", jt),
+        }
+    }
 }
 
 /// Relocations to apply to function bodies.
 pub type Relocations = PrimaryMap<DefinedFuncIndex, Vec<Relocation>>;
 
+/// Counts of a module's relocations by kind of target, as a measure of how
+/// much linking its code needs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RelocationSummary {
+    /// Calls to the module's own functions.
+    pub user_funcs: usize,
+    /// Calls to libcalls.
+    pub libcalls: usize,
+    /// Calls to the `memory.grow` and `memory.size` intrinsics, of both
+    /// defined and imported memories.
+    pub memory_intrinsics: usize,
+    /// References to the signature table or to relocated memory bases.
+    pub data: usize,
+    /// References within the relocated function's own body, to EBBs and
+    /// jump tables.
+    pub internal: usize,
+    /// All relocations.
+    pub total: usize,
+}
+
+impl fmt::Display for RelocationSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} relocations: {} to functions, {} to libcalls, {} to memory intrinsics, \
+             {} to data, {} internal",
+            self.total,
+            self.user_funcs,
+            self.libcalls,
+            self.memory_intrinsics,
+            self.data,
+            self.internal
+        )
+    }
+}
+
+/// Tallies `relocations` by kind of target.
+pub fn summarize_relocations(relocations: &Relocations) -> RelocationSummary {
+    let mut summary = RelocationSummary::default();
+    for r in relocations.values().flatten() {
+        let count = match r.reloc_target {
+            RelocationTarget::UserFunc(_) => &mut summary.user_funcs,
+            RelocationTarget::LibCall(_) => &mut summary.libcalls,
+            RelocationTarget::Memory32Grow
+            | RelocationTarget::ImportedMemory32Grow
+            | RelocationTarget::Memory32Size
+            | RelocationTarget::ImportedMemory32Size => &mut summary.memory_intrinsics,
+            RelocationTarget::SignatureTableBase | RelocationTarget::MemoryBase { .. } => {
+                &mut summary.data
+            }
+            RelocationTarget::Ebb(_) | RelocationTarget::JumpTable(_) => &mut summary.internal,
+        };
+        *count += 1;
+        summary.total += 1;
+    }
+    summary
+}
+
+/// A trapping instruction within a function body.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TrapSite {
+    /// Offset of the trapping instruction within the function body.
+    pub code_offset: binemit::CodeOffset,
+    /// The reason for the trap.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::display_from_str"))]
+    pub trap_code: ir::TrapCode,
+    /// Location of the wasm instruction the trap was generated for.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::source_loc"))]
+    pub source_loc: ir::SourceLoc,
+}
+
+/// Trap sites for each function body.
+pub type TrapInformation = PrimaryMap<DefinedFuncIndex, Vec<TrapSite>>;
+
+/// Size in bytes of each function's stack frame, as laid out by Cranelift.
+pub type FrameSizes = PrimaryMap<DefinedFuncIndex, u32>;
+
 /// An error while compiling WebAssembly to machine code.
 #[derive(Fail, Debug)]
 pub enum CompileError {
     /// A wasm translation error occured.
-    #[fail(display = "WebAssembly translation error: {}", _0)]
     Wasm(WasmError),
 
     /// A compilation error occured.
-    #[fail(display = "Compilation error: {}", _0)]
     Codegen(CodegenError),
+
+    /// The generated code calls a libcall that isn't available.
+    UnavailableLibCall {
+        /// The function containing the call.
+        func: FuncIndex,
+        /// The missing libcall.
+        libcall: ir::LibCall,
+    },
+
+    /// The generated code contains an absolute relocation, which can't be
+    /// linked into a position-independent executable.
+    NotPositionIndependent {
+        /// The function containing the relocation.
+        func: FuncIndex,
+        /// The kind of relocation.
+        reloc: binemit::Reloc,
+        /// The offset of the relocation in the function body.
+        offset: binemit::CodeOffset,
+    },
+
+    /// The code of a function is larger than allowed, either by
+    /// `CompileOptions::max_code_size` or by the reach of the target's
+    /// branch displacements, in which case Cranelift couldn't lay it out.
+    FunctionTooLarge {
+        /// The function.
+        func: FuncIndex,
+        /// The size of its code in bytes, if Cranelift could lay it out.
+        actual: Option<usize>,
+        /// The maximum size allowed in bytes.
+        limit: usize,
+        /// The size of the function's wasm body in bytes.
+        size_estimate: usize,
+    },
+
+    /// The generated code refers to an external name the relocation sink
+    /// doesn't know how to resolve.
+    UnrecognizedExternalName {
+        /// The function containing the relocation.
+        func: FuncIndex,
+        /// The name, as displayed.
+        name: String,
+    },
+
+    /// A function doesn't maintain the frame-pointer chain.
+    NoFramePointer {
+        /// The function.
+        func: FuncIndex,
+    },
+
+    /// Translating or compiling a function failed with an error that
+    /// doesn't name the function by itself.
+    InFunction {
+        /// The function.
+        func: FuncIndex,
+        /// The `Wasm` or `Codegen` error.
+        source: Box<CompileError>,
+    },
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompileError::Wasm(ref error) => {
+                write!(f, "WebAssembly translation error: {}", error)
+            }
+            CompileError::Codegen(ref error) => write!(f, "Compilation error: {}", error),
+            CompileError::UnavailableLibCall { func, libcall } => write!(
+                f,
+                "Function {:?} requires unavailable libcall {}",
+                func, libcall
+            ),
+            CompileError::NotPositionIndependent {
+                func,
+                reloc,
+                offset,
+            } => write!(
+                f,
+                "Function {:?} has absolute relocation {} at offset {}",
+                func, reloc, offset
+            ),
+            CompileError::FunctionTooLarge {
+                func,
+                actual,
+                limit,
+                size_estimate,
+            } => {
+                write!(
+                    f,
+                    "Function {:?} is too large to compile; its code is ",
+                    func
+                )?;
+                if let Some(actual) = actual {
+                    write!(f, "{} bytes, ", actual)?;
+                }
+                write!(
+                    f,
+                    "over the limit of {} bytes, and its body has {} bytes of wasm",
+                    limit, size_estimate
+                )
+            }
+            CompileError::UnrecognizedExternalName { func, ref name } => write!(
+                f,
+                "Function {:?} refers to unrecognized external name {}",
+                func, name
+            ),
+            CompileError::NoFramePointer { func } => {
+                write!(f, "Function {:?} doesn't set up a frame pointer", func)
+            }
+            CompileError::InFunction { func, ref source } => {
+                write!(f, "Function {:?} failed to compile: {}", func, source)
+            }
+        }
+    }
+}
+
+impl CompileError {
+    /// Returns the offset in the module file of the invalid WebAssembly
+    /// that caused this error, if it is a translation error.
+    ///
+    /// Function bodies are translated with their offset in the module, so
+    /// this is the offset of the failing operator, not an offset within
+    /// its function's body.
+    pub fn wasm_offset(&self) -> Option<usize> {
+        match *self {
+            CompileError::Wasm(WasmError::InvalidWebAssembly { offset, .. }) => Some(offset),
+            CompileError::InFunction { ref source, .. } => source.wasm_offset(),
+            _ => None,
+        }
+    }
 }
 
-/// Single address point transform.
+/// Address transform of consecutive instructions generated for the same
+/// source location.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct InstructionAddressTransform {
     /// Original source location.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::source_loc"))]
     pub srcloc: ir::SourceLoc,
 
     /// Generated instructions offset.
@@ -82,6 +411,7 @@ pub struct InstructionAddressTransform {
 
 /// Function and its instructions transforms.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FunctionAddressTransform {
     /// Instructions transforms
     pub locations: Vec<InstructionAddressTransform>,
@@ -95,3 +425,68 @@ pub struct FunctionAddressTransform {
 
 /// Function AddressTransforms collection.
 pub type AddressTransforms = PrimaryMap<DefinedFuncIndex, FunctionAddressTransform>;
+
+/// Moves each function's transforms to the body offset given by `layout`,
+/// such as after reordering the functions within a single code section, so
+/// that the line information follows the code.
+pub fn rebase_transforms(
+    mut transforms: AddressTransforms,
+    layout: &PrimaryMap<DefinedFuncIndex, usize>,
+) -> AddressTransforms {
+    for (i, transform) in transforms.iter_mut() {
+        let body_offset = layout[i];
+        for location in &mut transform.locations {
+            location.code_offset = location.code_offset - transform.body_offset + body_offset;
+        }
+        transform.body_offset = body_offset;
+    }
+    transforms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform(
+        body_offset: usize,
+        body_len: usize,
+        srclocs: &[(u32, usize)],
+    ) -> FunctionAddressTransform {
+        FunctionAddressTransform {
+            locations: srclocs
+                .iter()
+                .map(|&(srcloc, code_offset)| InstructionAddressTransform {
+                    srcloc: ir::SourceLoc::new(srcloc),
+                    code_offset: body_offset + code_offset,
+                    code_len: 1,
+                })
+                .collect(),
+            body_offset,
+            body_len,
+        }
+    }
+
+    #[test]
+    fn test_rebase_transforms() {
+        // Two functions laid out in order, then swapped.
+        let mut transforms = AddressTransforms::new();
+        let f0 = transforms.push(transform(0, 16, &[(10, 0), (12, 4)]));
+        let f1 = transforms.push(transform(16, 8, &[(20, 0), (23, 6)]));
+        let mut layout = PrimaryMap::new();
+        layout.push(8);
+        layout.push(0);
+
+        let rebased = rebase_transforms(transforms, &layout);
+        assert_eq!(rebased[f0].body_offset, 8);
+        assert_eq!(rebased[f1].body_offset, 0);
+        let offsets = |i| {
+            rebased[i]
+                .locations
+                .iter()
+                .map(|t: &InstructionAddressTransform| (t.srcloc.bits(), t.code_offset))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(offsets(f0), [(10, 8), (12, 12)]);
+        assert_eq!(offsets(f1), [(20, 0), (23, 6)]);
+    }
+}
@@ -1,40 +1,65 @@
 //! Support for compiling with Cranelift.
 
+use crate::cache::{
+    cache_key, decode_cached_function, encode_cached_function, CachedFunction, CompilationCache,
+};
 use crate::compilation::{
-    AddressTransforms, Compilation, CompileError, FunctionAddressTransform,
-    InstructionAddressTransform, Relocation, RelocationTarget, Relocations,
+    AddressTransforms, Compilation, CompileError, FailedFunction, FrameSizes,
+    FunctionAddressTransform, FunctionStats, InstructionAddressTransform, Relocation,
+    RelocationTarget, Relocations, TrapInformation, TrapSite,
 };
 use crate::func_environ::{
     get_func_name, get_imported_memory32_grow_name, get_imported_memory32_size_name,
-    get_memory32_grow_name, get_memory32_size_name, FuncEnvironment,
+    get_memory32_grow_name, get_memory32_size_name, get_signature_table_name, FuncEnvironment,
 };
 use crate::module::Module;
 use crate::module_environ::FunctionBodyData;
+use core::time::Duration;
 use cranelift_codegen::binemit;
 use cranelift_codegen::ir;
 use cranelift_codegen::ir::ExternalName;
 use cranelift_codegen::isa;
-use cranelift_codegen::Context;
-use cranelift_entity::PrimaryMap;
-use cranelift_wasm::{DefinedFuncIndex, FuncIndex, FuncTranslator};
+use cranelift_codegen::settings::OptLevel;
+use cranelift_codegen::{CodegenError, Context};
+use cranelift_entity::{EntityRef, PrimaryMap};
+use cranelift_wasm::{DefinedFuncIndex, FuncIndex, FuncTranslator, MemoryIndex};
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use std::boxed::Box;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::string::{String, ToString};
+#[cfg(feature = "std")]
+use std::time::Instant;
 use std::vec::Vec;
 
 /// Implementation of a relocation sink that just saves all the information for later
 struct RelocSink {
+    /// The function whose relocations are recorded.
+    func_index: FuncIndex,
     /// Relocations recorded for the function.
     func_relocs: Vec<Relocation>,
+    /// The first error encountered, as `binemit::RelocSink` can't report it.
+    error: Option<CompileError>,
 }
 
 impl binemit::RelocSink for RelocSink {
     fn reloc_ebb(
         &mut self,
-        _offset: binemit::CodeOffset,
-        _reloc: binemit::Reloc,
-        _ebb_offset: binemit::CodeOffset,
+        offset: binemit::CodeOffset,
+        reloc: binemit::Reloc,
+        ebb_offset: binemit::CodeOffset,
     ) {
-        // This should use the `offsets` field of `ir::Function`.
-        panic!("ebb headers not yet implemented");
+        // `ebb_offset` comes from the `offsets` field of `ir::Function`,
+        // which is final by the time code is emitted.
+        if is_resolved_within_body(reloc) {
+            return;
+        }
+        self.func_relocs.push(Relocation {
+            reloc,
+            reloc_target: RelocationTarget::Ebb(ebb_offset),
+            offset,
+            addend: 0,
+        });
     }
     fn reloc_external(
         &mut self,
@@ -51,13 +76,30 @@ impl binemit::RelocSink for RelocSink {
             RelocationTarget::Memory32Size
         } else if *name == get_imported_memory32_size_name() {
             RelocationTarget::ImportedMemory32Size
+        } else if *name == get_signature_table_name() {
+            RelocationTarget::SignatureTableBase
+        } else if let ExternalName::User {
+            namespace: 3,
+            index,
+        } = *name
+        {
+            // As named by `get_memory_base_name`.
+            RelocationTarget::MemoryBase {
+                memory_index: MemoryIndex::from_u32(index),
+            }
         } else if let ExternalName::User { namespace, index } = *name {
             debug_assert!(namespace == 0);
             RelocationTarget::UserFunc(FuncIndex::from_u32(index))
         } else if let ExternalName::LibCall(libcall) = *name {
             RelocationTarget::LibCall(libcall)
         } else {
-            panic!("unrecognized external name")
+            if self.error.is_none() {
+                self.error = Some(CompileError::UnrecognizedExternalName {
+                    func: self.func_index,
+                    name: format!("{}", name),
+                });
+            }
+            return;
         };
         self.func_relocs.push(Relocation {
             reloc,
@@ -66,25 +108,102 @@ impl binemit::RelocSink for RelocSink {
             addend,
         });
     }
-    fn reloc_jt(
-        &mut self,
-        _offset: binemit::CodeOffset,
-        _reloc: binemit::Reloc,
-        _jt: ir::JumpTable,
-    ) {
-        panic!("jump tables not yet implemented");
+    fn reloc_jt(&mut self, offset: binemit::CodeOffset, reloc: binemit::Reloc, jt: ir::JumpTable) {
+        if is_resolved_within_body(reloc) {
+            return;
+        }
+        self.func_relocs.push(Relocation {
+            reloc,
+            reloc_target: RelocationTarget::JumpTable(jt),
+            offset,
+            addend: 0,
+        });
+    }
+}
+
+/// Whether a relocation of kind `reloc` against code or a jump table within
+/// the same function body is already resolved. Cranelift writes the
+/// PC-relative displacement into the code as it emits it, and that
+/// displacement doesn't change wherever the body is placed.
+fn is_resolved_within_body(reloc: binemit::Reloc) -> bool {
+    match reloc {
+        binemit::Reloc::X86PCRel4 => true,
+        _ => false,
     }
 }
 
 impl RelocSink {
-    /// Return a new `RelocSink` instance.
-    pub fn new() -> Self {
+    /// Return a new `RelocSink` instance for the function `func_index`.
+    pub fn new(func_index: FuncIndex) -> Self {
         Self {
+            func_index,
             func_relocs: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Returns the recorded relocations, or the first error encountered
+    /// while recording them.
+    pub fn finish(self) -> Result<Vec<Relocation>, CompileError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.func_relocs),
         }
     }
 }
 
+/// Implementation of a trap sink that saves the trap sites for later.
+struct TrapSink {
+    /// Traps recorded for the function.
+    traps: Vec<TrapSite>,
+}
+
+impl TrapSink {
+    /// Return a new `TrapSink` instance.
+    pub fn new() -> Self {
+        Self { traps: Vec::new() }
+    }
+}
+
+impl binemit::TrapSink for TrapSink {
+    fn trap(
+        &mut self,
+        code_offset: binemit::CodeOffset,
+        source_loc: ir::SourceLoc,
+        trap_code: ir::TrapCode,
+    ) {
+        self.traps.push(TrapSite {
+            code_offset,
+            trap_code,
+            source_loc,
+        });
+    }
+}
+
+/// Adds the instruction at `code_offset` to `transforms`, extending the last
+/// range instead if the instruction follows it and has the same `srcloc`.
+fn push_address_transform(
+    transforms: &mut Vec<InstructionAddressTransform>,
+    srcloc: ir::SourceLoc,
+    code_offset: usize,
+    code_len: usize,
+) {
+    if let Some(last) = transforms.last_mut() {
+        if last.srcloc == srcloc && last.code_offset + last.code_len == code_offset {
+            last.code_len += code_len;
+            return;
+        }
+    }
+    transforms.push(InstructionAddressTransform {
+        srcloc,
+        code_offset,
+        code_len,
+    });
+}
+
+/// Returns the source location of the function's code, as ranges of
+/// consecutive instructions generated for the same wasm instruction.
+/// Instructions without a source location are left out.
 fn get_address_transform(
     context: &Context,
     isa: &isa::TargetIsa,
@@ -99,79 +218,943 @@ fn get_address_transform(
     for ebb in ebbs {
         for (offset, inst, size) in func.inst_offsets(ebb, &encinfo) {
             let srcloc = func.srclocs[inst];
-            result.push(InstructionAddressTransform {
-                srcloc,
-                code_offset: offset as usize,
-                code_len: size as usize,
-            });
+            if srcloc.is_default() {
+                continue;
+            }
+            push_address_transform(&mut result, srcloc, offset as usize, size as usize);
         }
     }
     result
 }
 
+/// The largest function Cranelift lays out, as documented for
+/// `CodegenError::CodeTooLarge`.
+const CRANELIFT_MAX_CODE_SIZE: usize = 1 << 30;
+
+/// Converts an error of `compile_and_emit` for the function `func_index`,
+/// naming the function.
+fn codegen_error(
+    func_index: FuncIndex,
+    input: &FunctionBodyData,
+    error: CodegenError,
+) -> CompileError {
+    match error {
+        CodegenError::CodeTooLarge => CompileError::FunctionTooLarge {
+            func: func_index,
+            actual: None,
+            limit: CRANELIFT_MAX_CODE_SIZE,
+            size_estimate: input.data.len(),
+        },
+        error => CompileError::InFunction {
+            func: func_index,
+            source: Box::new(CompileError::Codegen(error)),
+        },
+    }
+}
+
+/// Runs `f`, returning its result and how long it took.
+#[cfg(feature = "std")]
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+/// Runs `f`; there is no clock without `std`, so it always took no time.
+#[cfg(not(feature = "std"))]
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    (f(), Duration::default())
+}
+
+/// Whether `WASMTIME_SINGLE_THREAD_COMPILE` is set, making `compile_module`
+/// compile the functions one after the other on the calling thread, such as
+/// to get readable stack traces when debugging a miscompile.
+#[cfg(feature = "std")]
+fn single_threaded() -> bool {
+    std::env::var_os("WASMTIME_SINGLE_THREAD_COMPILE").is_some()
+}
+
+/// Without `std` there is no environment to ask, so compiling is parallel.
+#[cfg(not(feature = "std"))]
+fn single_threaded() -> bool {
+    false
+}
+
+/// Compiles `context.func` for `isa` and appends its code to `mem`.
+///
+/// This is `Context::compile_and_emit`, unless `bounds_check_hoisting` is
+/// off at `opt_level=best`, the only level where Cranelift runs
+/// loop-invariant code motion. The same passes then run in the same order,
+/// except for that one.
+fn compile_and_emit(
+    context: &mut Context,
+    isa: &dyn isa::TargetIsa,
+    bounds_check_hoisting: bool,
+    mem: &mut Vec<u8>,
+    relocs: &mut dyn binemit::RelocSink,
+    traps: &mut dyn binemit::TrapSink,
+) -> Result<(), CodegenError> {
+    if bounds_check_hoisting || isa.flags().opt_level() != OptLevel::Best {
+        return context.compile_and_emit(isa, mem, relocs, traps);
+    }
+
+    context.verify_if(isa)?;
+    context.compute_cfg();
+    context.preopt(isa)?;
+    if isa.flags().enable_nan_canonicalization() {
+        context.canonicalize_nans(isa)?;
+    }
+    context.legalize(isa)?;
+    context.postopt(isa)?;
+    context.compute_domtree();
+    context.simple_gvn(isa)?;
+    context.compute_domtree();
+    context.eliminate_unreachable_code(isa)?;
+    context.dce(isa)?;
+    context.regalloc(isa)?;
+    context.prologue_epilogue(isa)?;
+    context.shrink_instructions(isa)?;
+    let code_size = context.relax_branches(isa)?;
+
+    let old_len = mem.len();
+    mem.resize(old_len + code_size as usize, 0);
+    // The buffer was just sized to the code `relax_branches` laid out.
+    unsafe { context.emit_to_memory(isa, mem.as_mut_ptr().add(old_len), relocs, traps) };
+    Ok(())
+}
+
+/// The context a function's code was emitted from, the code with its
+/// relocations and trap sites, and the time spent translating and compiling
+/// it.
+type CompiledFunction = (
+    Context,
+    Vec<u8>,
+    Vec<Relocation>,
+    Vec<TrapSite>,
+    (Duration, Duration),
+);
+
+/// Translates the body of `func_index` and compiles it.
+fn compile_function(
+    module: &Module,
+    func_index: FuncIndex,
+    input: &FunctionBodyData,
+    isa: &dyn isa::TargetIsa,
+    bounds_check_hoisting: bool,
+) -> Result<CompiledFunction, CompileError> {
+    let mut context = Context::new();
+    context.func.name = get_func_name(func_index);
+    context.func.signature = module.signatures[module.functions[func_index]].clone();
+
+    let mut trans = FuncTranslator::new();
+    let (translated, translation_time) = timed(|| {
+        trans.translate(
+            input.data,
+            input.module_offset,
+            &mut context.func,
+            &mut FuncEnvironment::new(isa.frontend_config(), module),
+        )
+    });
+    translated.map_err(|error| CompileError::InFunction {
+        func: func_index,
+        source: Box::new(CompileError::Wasm(error)),
+    })?;
+
+    let mut code_buf: Vec<u8> = Vec::new();
+    let mut reloc_sink = RelocSink::new(func_index);
+    let mut trap_sink = TrapSink::new();
+    let (emitted, codegen_time) = timed(|| {
+        compile_and_emit(
+            &mut context,
+            isa,
+            bounds_check_hoisting,
+            &mut code_buf,
+            &mut reloc_sink,
+            &mut trap_sink,
+        )
+    });
+    emitted.map_err(|error| codegen_error(func_index, input, error))?;
+    let relocs = reloc_sink.finish()?;
+
+    Ok((
+        context,
+        code_buf,
+        relocs,
+        trap_sink.traps,
+        (translation_time, codegen_time),
+    ))
+}
+
+/// Options for `compile_module_with_options`. The defaults are those of
+/// `compile_module`.
+#[derive(Clone, Copy)]
+pub struct CompileOptions<'a> {
+    /// Keep the final IR of each function in `Compilation::ir`.
+    pub capture_ir: bool,
+    /// Record functions that fail to compile in `Compilation::failed`, with
+    /// an empty body, instead of failing the whole module.
+    pub allow_failures: bool,
+    /// Fail any function whose code is larger than this many bytes with
+    /// `CompileError::FunctionTooLarge`.
+    pub max_code_size: Option<usize>,
+    /// Skip code generation for functions found in this cache, and store
+    /// those compiled in it. It isn't used with `capture_ir`.
+    pub cache: Option<&'a dyn CompilationCache>,
+    /// Keep the time spent compiling each function and the size of its code
+    /// in `Compilation::stats`.
+    pub collect_stats: bool,
+    /// Compile the functions in parallel on this pool instead of rayon's
+    /// global thread pool.
+    pub pool: Option<&'a rayon::ThreadPool>,
+    /// Let loop-invariant code motion hoist the invariant parts of the
+    /// address computations of memory accesses out of loops, where the ISA's
+    /// `opt_level` runs it, which is only at `opt_level=best`. Turning it
+    /// off skips the pass there too: memory accesses in loops then
+    /// recompute them on every iteration, which is slower but keeps the
+    /// code next to the bounds checks it belongs to.
+    pub bounds_check_hoisting: bool,
+}
+
+impl<'a> Default for CompileOptions<'a> {
+    fn default() -> Self {
+        Self {
+            capture_ir: false,
+            allow_failures: false,
+            max_code_size: None,
+            cache: None,
+            collect_stats: false,
+            pool: None,
+            bounds_check_hoisting: true,
+        }
+    }
+}
+
 /// Compile the module using Cranelift, producing a compilation result with
-/// associated relocations.
+/// associated relocations, frame sizes and trap sites.
+///
+/// With `generate_debug_info`, the address transforms map the code of each
+/// function to the offsets in the wasm file of the instructions it was
+/// generated from. Code that comes from no wasm instruction, such as the
+/// prologue and epilogue, has no source location and is left out of them,
+/// rather than being attributed to offset 0.
+///
+/// Functions with the same signature and body as an earlier function are
+/// only compiled once, and are listed in `Compilation::duplicates`.
+///
+/// Functions are compiled in parallel, unless the
+/// `WASMTIME_SINGLE_THREAD_COMPILE` environment variable is set.
 pub fn compile_module<'data, 'module>(
     module: &'module Module,
     function_body_inputs: PrimaryMap<DefinedFuncIndex, FunctionBodyData<'data>>,
     isa: &dyn isa::TargetIsa,
     generate_debug_info: bool,
-) -> Result<(Compilation, Relocations, AddressTransforms), CompileError> {
-    let mut functions = PrimaryMap::with_capacity(function_body_inputs.len());
-    let mut relocations = PrimaryMap::with_capacity(function_body_inputs.len());
-    let mut address_transforms = PrimaryMap::with_capacity(function_body_inputs.len());
+) -> Result<
+    (
+        Compilation,
+        Relocations,
+        AddressTransforms,
+        FrameSizes,
+        TrapInformation,
+    ),
+    CompileError,
+> {
+    compile_module_with_options(
+        module,
+        function_body_inputs,
+        isa,
+        generate_debug_info,
+        &CompileOptions::default(),
+    )
+}
+
+/// Like `compile_module`, with the `options` described by `CompileOptions`.
+pub fn compile_module_with_options<'data, 'module>(
+    module: &'module Module,
+    function_body_inputs: PrimaryMap<DefinedFuncIndex, FunctionBodyData<'data>>,
+    isa: &dyn isa::TargetIsa,
+    generate_debug_info: bool,
+    options: &CompileOptions,
+) -> Result<
+    (
+        Compilation,
+        Relocations,
+        AddressTransforms,
+        FrameSizes,
+        TrapInformation,
+    ),
+    CompileError,
+> {
+    let compile = || {
+        compile_selected_functions(
+            module,
+            function_body_inputs,
+            isa,
+            generate_debug_info,
+            options,
+            None,
+        )
+    };
+    match options.pool {
+        Some(pool) => pool.install(compile),
+        None => compile(),
+    }
+}
+
+/// Like `compile_module_with_options`, but only compiles the functions in
+/// `only`, such as to inspect the code generated for one function of a large
+/// module. The other functions are left with empty bodies, and without
+/// relocations, trap sites or source locations.
+pub fn compile_functions<'data, 'module>(
+    module: &'module Module,
+    function_body_inputs: PrimaryMap<DefinedFuncIndex, FunctionBodyData<'data>>,
+    isa: &dyn isa::TargetIsa,
+    generate_debug_info: bool,
+    options: &CompileOptions,
+    only: &[DefinedFuncIndex],
+) -> Result<
+    (
+        Compilation,
+        Relocations,
+        AddressTransforms,
+        FrameSizes,
+        TrapInformation,
+    ),
+    CompileError,
+> {
+    let compile = || {
+        compile_selected_functions(
+            module,
+            function_body_inputs,
+            isa,
+            generate_debug_info,
+            options,
+            Some(only),
+        )
+    };
+    match options.pool {
+        Some(pool) => pool.install(compile),
+        None => compile(),
+    }
+}
+
+/// Compiles the functions in `only`, or all of them if `None`, as described
+/// by `compile_module_with_options`.
+fn compile_selected_functions<'data, 'module>(
+    module: &'module Module,
+    function_body_inputs: PrimaryMap<DefinedFuncIndex, FunctionBodyData<'data>>,
+    isa: &dyn isa::TargetIsa,
+    generate_debug_info: bool,
+    options: &CompileOptions,
+    only: Option<&[DefinedFuncIndex]>,
+) -> Result<
+    (
+        Compilation,
+        Relocations,
+        AddressTransforms,
+        FrameSizes,
+        TrapInformation,
+    ),
+    CompileError,
+> {
+    let mut functions: PrimaryMap<DefinedFuncIndex, Vec<u8>> =
+        PrimaryMap::with_capacity(function_body_inputs.len());
+    let mut jt_offsets: PrimaryMap<DefinedFuncIndex, ir::JumpTableOffsets> =
+        PrimaryMap::with_capacity(function_body_inputs.len());
+    let mut relocations: Relocations = PrimaryMap::with_capacity(function_body_inputs.len());
+    let mut address_transforms: AddressTransforms =
+        PrimaryMap::with_capacity(function_body_inputs.len());
+    let mut frame_sizes: FrameSizes = PrimaryMap::with_capacity(function_body_inputs.len());
+    let mut traps: TrapInformation = PrimaryMap::with_capacity(function_body_inputs.len());
+    let mut ir: PrimaryMap<DefinedFuncIndex, String> =
+        PrimaryMap::with_capacity(function_body_inputs.len());
+    let mut stats = PrimaryMap::with_capacity(function_body_inputs.len());
+    let mut failed = Vec::new();
+    let CompileOptions {
+        capture_ir,
+        allow_failures,
+        max_code_size,
+        cache,
+        collect_stats,
+        pool: _,
+        bounds_check_hoisting,
+    } = *options;
 
-    function_body_inputs
+    let inputs = function_body_inputs
         .into_iter()
-        .collect::<Vec<(DefinedFuncIndex, &FunctionBodyData<'data>)>>()
-        .par_iter()
-        .map(|(i, input)| {
+        .collect::<Vec<(DefinedFuncIndex, &FunctionBodyData<'data>)>>();
+
+    let selected = |i: &DefinedFuncIndex| only.map_or(true, |only| only.contains(i));
+
+    // Functions with the same signature and body compile to the same code,
+    // so only the first of them is compiled and the others copy its result.
+    // Failures are kept per function, so with `allow_failures` every
+    // function is compiled, as is every selected function with `only`.
+    let mut duplicate_of = BTreeMap::new();
+    if !allow_failures && only.is_none() {
+        let mut originals = BTreeMap::new();
+        for (i, input) in &inputs {
+            let signature = module.functions[module.func_index(*i)];
+            match originals.entry((signature, input.data)) {
+                Entry::Occupied(original) => {
+                    duplicate_of.insert(*i, *original.get());
+                }
+                Entry::Vacant(original) => {
+                    original.insert(*i);
+                }
+            }
+        }
+    }
+    let unique_inputs = inputs
+        .iter()
+        .filter(|(i, _)| !duplicate_of.contains_key(i) && selected(i))
+        .collect::<Vec<_>>();
+
+    let compile_input = |(i, input): &&(DefinedFuncIndex, &FunctionBodyData<'data>)| {
+        let compile = || -> Result<_, CompileError> {
             let func_index = module.func_index(*i);
-            let mut context = Context::new();
-            context.func.name = get_func_name(func_index);
-            context.func.signature = module.signatures[module.functions[func_index]].clone();
-
-            let mut trans = FuncTranslator::new();
-            trans
-                .translate(
-                    input.data,
-                    input.module_offset,
-                    &mut context.func,
-                    &mut FuncEnvironment::new(isa.frontend_config(), module),
-                )
-                .map_err(CompileError::Wasm)?;
-
-            let mut code_buf: Vec<u8> = Vec::new();
-            let mut reloc_sink = RelocSink::new();
-            let mut trap_sink = binemit::NullTrapSink {};
-            context
-                .compile_and_emit(isa, &mut code_buf, &mut reloc_sink, &mut trap_sink)
-                .map_err(CompileError::Codegen)?;
+
+            // The IR isn't cached, so capturing it always compiles.
+            let key = match cache {
+                Some(_) if !capture_ir => Some(cache_key(
+                    module,
+                    isa,
+                    bounds_check_hoisting,
+                    func_index,
+                    input,
+                )),
+                _ => None,
+            };
+            let cached = match (cache, &key) {
+                (Some(cache), Some(key)) => cache
+                    .get(key)
+                    .and_then(|value| decode_cached_function(&value)),
+                _ => None,
+            };
+
+            let (function, func_ir, translation_time, codegen_time) = match cached {
+                Some(function) => (function, None, Duration::default(), Duration::default()),
+                None => {
+                    let (context, code_buf, relocs, func_traps, times) =
+                        compile_function(module, func_index, input, isa, bounds_check_hoisting)?;
+                    let (translation_time, codegen_time) = times;
+
+                    // Cached entries always hold the address transform,
+                    // so that turning on debug info still hits.
+                    let locations = if generate_debug_info || key.is_some() {
+                        get_address_transform(&context, isa)
+                    } else {
+                        Vec::new()
+                    };
+
+                    let func_ir = if capture_ir {
+                        Some(context.func.display(isa).to_string())
+                    } else {
+                        None
+                    };
+
+                    let function = CachedFunction {
+                        code: code_buf,
+                        jt_offsets: context.func.jt_offsets,
+                        relocs,
+                        locations,
+                        frame_size: context.func.stack_slots.frame_size.unwrap_or(0),
+                        traps: func_traps,
+                    };
+                    if let (Some(cache), Some(key)) = (cache, &key) {
+                        if let Some(value) = encode_cached_function(&function) {
+                            cache.put(key, &value);
+                        }
+                    }
+                    (function, func_ir, translation_time, codegen_time)
+                }
+            };
+
+            if let Some(limit) = max_code_size {
+                if function.code.len() > limit {
+                    return Err(CompileError::FunctionTooLarge {
+                        func: func_index,
+                        actual: Some(function.code.len()),
+                        limit,
+                        size_estimate: input.data.len(),
+                    });
+                }
+            }
 
             let address_transform = if generate_debug_info {
-                let body_len = code_buf.len();
-                let at = get_address_transform(&context, isa);
                 Some(FunctionAddressTransform {
-                    locations: at,
+                    body_len: function.code.len(),
+                    locations: function.locations,
                     body_offset: 0,
-                    body_len,
                 })
             } else {
                 None
             };
 
-            Ok((code_buf, reloc_sink.func_relocs, address_transform))
-        })
-        .collect::<Result<Vec<_>, CompileError>>()?
-        .into_iter()
-        .for_each(|(function, relocs, address_transform)| {
-            functions.push(function);
-            relocations.push(relocs);
-            if let Some(address_transform) = address_transform {
-                address_transforms.push(address_transform);
+            let stats = FunctionStats {
+                translation_time,
+                codegen_time,
+                code_size: function.code.len(),
+            };
+
+            Ok((
+                function.code,
+                function.jt_offsets,
+                function.relocs,
+                address_transform,
+                function.frame_size,
+                function.traps,
+                func_ir,
+                stats,
+            ))
+        };
+        (*i, compile())
+    };
+    let results = if single_threaded() {
+        unique_inputs.iter().map(compile_input).collect::<Vec<_>>()
+    } else {
+        unique_inputs
+            .par_iter()
+            .map(compile_input)
+            .collect::<Vec<_>>()
+    };
+
+    // The result of a function that isn't compiled, because it failed or
+    // wasn't selected: an empty body.
+    let placeholder = || {
+        let address_transform = if generate_debug_info {
+            Some(FunctionAddressTransform {
+                locations: Vec::new(),
+                body_offset: 0,
+                body_len: 0,
+            })
+        } else {
+            None
+        };
+        let func_ir = if capture_ir {
+            Some(String::new())
+        } else {
+            None
+        };
+        (
+            Vec::new(),
+            ir::JumpTableOffsets::new(),
+            Vec::new(),
+            address_transform,
+            0,
+            Vec::new(),
+            func_ir,
+            FunctionStats::default(),
+        )
+    };
+
+    // Each result carries its index, so that a change in the order results
+    // are collected in fails loudly instead of mixing up the functions.
+    let mut results = results.into_iter();
+    for (i, input) in &inputs {
+        if let Some(&original) = duplicate_of.get(i) {
+            // Source locations are offsets in the wasm file, so they move by
+            // as much as the body does.
+            let delta = input
+                .module_offset
+                .wrapping_sub(inputs[original.index()].1.module_offset)
+                as u32;
+            let relocate = |srcloc: ir::SourceLoc| {
+                if srcloc.is_default() {
+                    srcloc
+                } else {
+                    ir::SourceLoc::new(srcloc.bits().wrapping_add(delta))
+                }
+            };
+            assert_eq!(functions.push(functions[original].clone()), *i);
+            assert_eq!(jt_offsets.push(jt_offsets[original].clone()), *i);
+            assert_eq!(relocations.push(relocations[original].clone()), *i);
+            if generate_debug_info {
+                let transform: &FunctionAddressTransform = &address_transforms[original];
+                let address_transform = FunctionAddressTransform {
+                    locations: transform
+                        .locations
+                        .iter()
+                        .map(|location| InstructionAddressTransform {
+                            srcloc: relocate(location.srcloc),
+                            code_offset: location.code_offset,
+                            code_len: location.code_len,
+                        })
+                        .collect(),
+                    body_offset: transform.body_offset,
+                    body_len: transform.body_len,
+                };
+                assert_eq!(address_transforms.push(address_transform), *i);
             }
-        });
+            assert_eq!(frame_sizes.push(frame_sizes[original]), *i);
+            let func_traps = traps[original]
+                .iter()
+                .map(|trap| TrapSite {
+                    source_loc: relocate(trap.source_loc),
+                    ..trap.clone()
+                })
+                .collect();
+            assert_eq!(traps.push(func_traps), *i);
+            // Nothing was generated for the duplicate itself.
+            if capture_ir {
+                assert_eq!(ir.push(String::new()), *i);
+            }
+            assert_eq!(stats.push(FunctionStats::default()), *i);
+            continue;
+        }
+
+        let result = if selected(i) {
+            let (index, result) = results.next().unwrap();
+            assert_eq!(*i, index);
+            result
+        } else {
+            Ok(placeholder())
+        };
+        let (
+            function,
+            func_jt_offsets,
+            relocs,
+            address_transform,
+            frame_size,
+            func_traps,
+            func_ir,
+            func_stats,
+        ) = match result {
+            Ok(compiled) => compiled,
+            Err(error) if allow_failures => {
+                failed.push(FailedFunction {
+                    index: *i,
+                    error,
+                    wasm_body: input.data.to_vec(),
+                });
+                placeholder()
+            }
+            Err(error) => return Err(error),
+        };
+        assert_eq!(functions.push(function), *i);
+        assert_eq!(jt_offsets.push(func_jt_offsets), *i);
+        assert_eq!(relocations.push(relocs), *i);
+        if let Some(address_transform) = address_transform {
+            assert_eq!(address_transforms.push(address_transform), *i);
+        }
+        assert_eq!(frame_sizes.push(frame_size), *i);
+        assert_eq!(traps.push(func_traps), *i);
+        if let Some(func_ir) = func_ir {
+            assert_eq!(ir.push(func_ir), *i);
+        }
+        assert_eq!(stats.push(func_stats), *i);
+    }
+
+    let mut compilation = Compilation::new(functions);
+    if capture_ir {
+        compilation.ir = Some(ir);
+    }
+    if collect_stats {
+        compilation.stats = Some(stats);
+    }
+    compilation.failed = failed;
+    compilation.jt_offsets = jt_offsets;
+    compilation.duplicates = duplicate_of.into_iter().collect();
 
     // TODO: Reorganize where we create the Vec for the resolved imports.
-    Ok((Compilation::new(functions), relocations, address_transforms))
+    Ok((
+        compilation,
+        relocations,
+        address_transforms,
+        frame_sizes,
+        traps,
+    ))
+}
+
+/// Like `compile_module`, but compiles the functions in parallel on `pool`
+/// instead of rayon's global thread pool, such as to cap the number of
+/// threads used when embedded in a service that manages its own.
+pub fn compile_module_in_pool<'data, 'module>(
+    module: &'module Module,
+    function_body_inputs: PrimaryMap<DefinedFuncIndex, FunctionBodyData<'data>>,
+    isa: &dyn isa::TargetIsa,
+    generate_debug_info: bool,
+    pool: &rayon::ThreadPool,
+) -> Result<
+    (
+        Compilation,
+        Relocations,
+        AddressTransforms,
+        FrameSizes,
+        TrapInformation,
+    ),
+    CompileError,
+> {
+    compile_module_with_options(
+        module,
+        function_body_inputs,
+        isa,
+        generate_debug_info,
+        &CompileOptions {
+            pool: Some(pool),
+            ..CompileOptions::default()
+        },
+    )
+}
+
+/// Compiles the module's functions one at a time, in order, passing each to
+/// `emit` as it is compiled instead of collecting them, so that only one
+/// function's code is held in memory at a time.
+///
+/// `emit` is given the function's index, its code, its relocations, its trap
+/// sites, its address transform if `generate_debug_info` is set, and the size
+/// of its stack frame. Of `options`, `bounds_check_hoisting` and
+/// `max_code_size` apply as they do to `compile_module_with_options`; the
+/// others have nothing to act on here, as duplicate bodies, IR and stats
+/// aren't kept, functions aren't compiled in parallel or cached, and the
+/// first function that fails to compile fails the module.
+pub fn compile_module_streaming<'data, F>(
+    module: &Module,
+    function_body_inputs: PrimaryMap<DefinedFuncIndex, FunctionBodyData<'data>>,
+    isa: &dyn isa::TargetIsa,
+    generate_debug_info: bool,
+    options: &CompileOptions,
+    mut emit: F,
+) -> Result<(), CompileError>
+where
+    F: FnMut(
+        DefinedFuncIndex,
+        Vec<u8>,
+        Vec<Relocation>,
+        Vec<TrapSite>,
+        Option<FunctionAddressTransform>,
+        u32,
+    ),
+{
+    for (i, input) in function_body_inputs.iter() {
+        let func_index = module.func_index(i);
+        let (context, code, relocs, func_traps, _) = compile_function(
+            module,
+            func_index,
+            input,
+            isa,
+            options.bounds_check_hoisting,
+        )?;
+        if let Some(limit) = options.max_code_size {
+            if code.len() > limit {
+                return Err(CompileError::FunctionTooLarge {
+                    func: func_index,
+                    actual: Some(code.len()),
+                    limit,
+                    size_estimate: input.data.len(),
+                });
+            }
+        }
+        let address_transform = if generate_debug_info {
+            Some(FunctionAddressTransform {
+                body_len: code.len(),
+                locations: get_address_transform(&context, isa),
+                body_offset: 0,
+            })
+        } else {
+            None
+        };
+        let frame_size = context.func.stack_slots.frame_size.unwrap_or(0);
+        emit(i, code, relocs, func_traps, address_transform, frame_size);
+    }
+    Ok(())
+}
+
+/// Check that the compiled code only calls libcalls from `available`,
+/// reporting the first function that needs anything else.
+pub fn check_libcalls(
+    module: &Module,
+    relocations: &Relocations,
+    available: &[ir::LibCall],
+) -> Result<(), CompileError> {
+    for (i, function_relocs) in relocations.iter() {
+        for r in function_relocs {
+            if let RelocationTarget::LibCall(libcall) = r.reloc_target {
+                if !available.contains(&libcall) {
+                    return Err(CompileError::UnavailableLibCall {
+                        func: module.func_index(i),
+                        libcall,
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that the compiled code is position-independent, that is, contains
+/// no absolute relocations, as required to link it into a PIE.
+pub fn check_position_independent(
+    module: &Module,
+    relocations: &Relocations,
+) -> Result<(), CompileError> {
+    for (i, function_relocs) in relocations.iter() {
+        for r in function_relocs {
+            match r.reloc {
+                binemit::Reloc::Abs4 | binemit::Reloc::Abs8 => {
+                    return Err(CompileError::NotPositionIndependent {
+                        func: module.func_index(i),
+                        reloc: r.reloc,
+                        offset: r.offset,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether Cranelift's prologue for `call_conv` links the frame into the
+/// frame-pointer chain. This version of Cranelift has no setting to keep
+/// frame pointers: its native x86 prologue always pushes the caller's frame
+/// pointer and points the frame pointer at it, while the Baldrdash prologue
+/// is left to the embedder and other targets don't set one up at all.
+fn chains_frame_pointers(isa: &dyn isa::TargetIsa, call_conv: isa::CallConv) -> bool {
+    isa.name() == "x86" && call_conv != isa::CallConv::Baldrdash
+}
+
+/// Check that every defined function will link its frame into the
+/// frame-pointer chain, so that profilers can unwind through wasm frames
+/// without DWARF.
+pub fn check_frame_pointer_chain(
+    module: &Module,
+    isa: &dyn isa::TargetIsa,
+) -> Result<(), CompileError> {
+    for (func, &sig) in module.functions.iter().skip(module.imported_funcs.len()) {
+        if !chains_frame_pointers(isa, module.signatures[sig].call_conv) {
+            return Err(CompileError::NoFramePointer { func });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::binemit::RelocSink as BinemitRelocSink;
+
+    #[test]
+    fn test_reloc_ebb() {
+        let mut sink = RelocSink::new(FuncIndex::new(0));
+        sink.reloc_ebb(12, binemit::Reloc::Abs8, 40);
+        assert_eq!(sink.func_relocs.len(), 1);
+        let r = &sink.func_relocs[0];
+        assert_eq!(r.offset, 12);
+        match r.reloc {
+            binemit::Reloc::Abs8 => {}
+            reloc => panic!("unexpected relocation {:?}", reloc),
+        }
+        assert_eq!(r.addend, 0);
+        match r.reloc_target {
+            RelocationTarget::Ebb(ebb_offset) => assert_eq!(ebb_offset, 40),
+            target => panic!("unexpected relocation target {:?}", target),
+        }
+    }
+
+    #[test]
+    fn test_reloc_within_body_resolved() {
+        let mut sink = RelocSink::new(FuncIndex::new(0));
+        sink.reloc_ebb(12, binemit::Reloc::X86PCRel4, 40);
+        sink.reloc_jt(20, binemit::Reloc::X86PCRel4, ir::JumpTable::new(0));
+        assert!(sink.finish().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reloc_unrecognized_external_name() {
+        let mut sink = RelocSink::new(FuncIndex::new(3));
+        sink.reloc_external(
+            4,
+            binemit::Reloc::Abs8,
+            &ExternalName::testcase("unexpected"),
+            0,
+        );
+        sink.reloc_ebb(12, binemit::Reloc::X86PCRel4, 40);
+        match sink.finish() {
+            Err(CompileError::UnrecognizedExternalName { func, name }) => {
+                assert_eq!(func, FuncIndex::new(3));
+                assert!(name.contains("unexpected"));
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_address_transform_ranges() {
+        let mut transforms = Vec::new();
+        let a = ir::SourceLoc::new(10);
+        let b = ir::SourceLoc::new(12);
+        push_address_transform(&mut transforms, a, 0, 3);
+        push_address_transform(&mut transforms, a, 3, 2);
+        push_address_transform(&mut transforms, a, 5, 4);
+        push_address_transform(&mut transforms, b, 9, 1);
+        // Not contiguous with the previous range, such as after padding.
+        push_address_transform(&mut transforms, b, 12, 2);
+        let ranges = transforms
+            .iter()
+            .map(|t| (t.srcloc, t.code_offset, t.code_len))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec![(a, 0, 9), (b, 9, 1), (b, 12, 2)]);
+    }
+
+    #[test]
+    fn test_codegen_error_function_too_large() {
+        let input = FunctionBodyData {
+            data: &[0; 100],
+            module_offset: 0,
+        };
+        match codegen_error(FuncIndex::new(7), &input, CodegenError::CodeTooLarge) {
+            CompileError::FunctionTooLarge {
+                func,
+                actual,
+                limit,
+                size_estimate,
+            } => {
+                assert_eq!(func, FuncIndex::new(7));
+                assert_eq!(actual, None);
+                assert_eq!(limit, CRANELIFT_MAX_CODE_SIZE);
+                assert_eq!(size_estimate, 100);
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+        let message =
+            codegen_error(FuncIndex::new(7), &input, CodegenError::CodeTooLarge).to_string();
+        assert!(message.contains(&CRANELIFT_MAX_CODE_SIZE.to_string()));
+        assert!(message.contains("100 bytes of wasm"));
+        match codegen_error(FuncIndex::new(7), &input, CodegenError::ImplLimitExceeded) {
+            CompileError::InFunction { func, source } => {
+                assert_eq!(func, FuncIndex::new(7));
+                match *source {
+                    CompileError::Codegen(CodegenError::ImplLimitExceeded) => {}
+                    error => panic!("unexpected error {:?}", error),
+                }
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn test_check_libcalls() {
+        let mut module = Module::new();
+        module
+            .imported_funcs
+            .push((String::from("env"), String::from("log")));
+        let mut relocations = PrimaryMap::new();
+        relocations.push(Vec::new());
+        relocations.push(vec![Relocation {
+            reloc: binemit::Reloc::Abs8,
+            reloc_target: RelocationTarget::LibCall(ir::LibCall::FloorF32),
+            offset: 4,
+            addend: 0,
+        }]);
+
+        assert!(check_libcalls(&module, &relocations, &[ir::LibCall::FloorF32]).is_ok());
+        match check_libcalls(&module, &relocations, &[ir::LibCall::CeilF32]) {
+            Err(CompileError::UnavailableLibCall { func, libcall }) => {
+                // The import comes first in the function index space.
+                assert_eq!(func, FuncIndex::new(2));
+                assert_eq!(libcall, ir::LibCall::FloorF32);
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
 }
@@ -6,7 +6,7 @@ use core::clone::Clone;
 use cranelift_codegen::cursor::FuncCursor;
 use cranelift_codegen::ir;
 use cranelift_codegen::ir::condcodes::*;
-use cranelift_codegen::ir::immediates::{Offset32, Uimm64};
+use cranelift_codegen::ir::immediates::{Imm64, Offset32, Uimm64};
 use cranelift_codegen::ir::types::*;
 use cranelift_codegen::ir::{
     AbiParam, ArgumentPurpose, ExtFuncData, FuncRef, Function, InstBuilder, Signature,
@@ -48,6 +48,18 @@ pub fn get_imported_memory32_size_name() -> ir::ExternalName {
     ir::ExternalName::user(1, 3)
 }
 
+/// Compute an `ir::ExternalName` for the base of the signature table used
+/// with `TableStyle::CallerChecksRelocatedSignature`.
+pub fn get_signature_table_name() -> ir::ExternalName {
+    ir::ExternalName::user(2, 0)
+}
+
+/// Compute an `ir::ExternalName` for the base of a locally-defined memory
+/// whose plan has a `relocated_base`.
+pub fn get_memory_base_name(index: MemoryIndex) -> ir::ExternalName {
+    ir::ExternalName::user(3, index.as_u32())
+}
+
 /// The `FuncEnvironment` implementation for use by the `ModuleEnvironment`.
 pub struct FuncEnvironment<'module_environment> {
     /// Target-specified configuration.
@@ -255,7 +267,7 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         });
 
         let element_size = match self.module.table_plans[index].style {
-            TableStyle::CallerChecksSignature => {
+            TableStyle::CallerChecksSignature | TableStyle::CallerChecksRelocatedSignature => {
                 u64::from(self.offsets.size_of_vmcaller_checked_anyfunc())
             }
         };
@@ -305,6 +317,7 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
                 memory: _,
                 style: MemoryStyle::Dynamic,
                 offset_guard_size,
+                relocated_base: _,
             } => {
                 let heap_bound = func.create_global_value(ir::GlobalValueData::Load {
                     base: ptr,
@@ -324,6 +337,7 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
                 memory: _,
                 style: MemoryStyle::Static { bound },
                 offset_guard_size,
+                relocated_base: _,
             } => (
                 Uimm64::new(offset_guard_size),
                 ir::HeapStyle::Static {
@@ -333,12 +347,22 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
             ),
         };
 
-        let heap_base = func.create_global_value(ir::GlobalValueData::Load {
-            base: ptr,
-            offset: Offset32::new(base_offset),
-            global_type: pointer_type,
-            readonly: readonly_base,
-        });
+        let relocated_base = self.module.memory_plans[index].relocated_base
+            && !self.module.is_imported_memory(index);
+        let heap_base = if relocated_base {
+            func.create_global_value(ir::GlobalValueData::Symbol {
+                name: get_memory_base_name(index),
+                offset: Imm64::new(0),
+                colocated: false,
+            })
+        } else {
+            func.create_global_value(ir::GlobalValueData::Load {
+                base: ptr,
+                offset: Offset32::new(base_offset),
+                global_type: pointer_type,
+                readonly: readonly_base,
+            })
+        };
         func.create_heap(ir::HeapData {
             base: heap_base,
             min_size: 0.into(),
@@ -357,6 +381,9 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
                 let offset = cast::i32(self.offsets.vmctx_vmglobal_definition(def_index)).unwrap();
                 (vmctx, offset)
             } else {
+                // The runtime stores a pointer to the imported global's
+                // definition in the vmctx at instantiation, so accessing it
+                // needs no relocation.
                 let from_offset = self.offsets.vmctx_vmglobal_import_from(index);
                 let global = func.create_global_value(ir::GlobalValueData::Load {
                     base: vmctx,
@@ -407,33 +434,47 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         let table_entry_addr = pos.ins().table_addr(pointer_type, table, callee, 0);
 
         // If necessary, check the signature.
-        match self.module.table_plans[table_index].style {
+        let caller_sig_id_location = match self.module.table_plans[table_index].style {
             TableStyle::CallerChecksSignature => {
-                let sig_id_size = self.offsets.size_of_vmshared_signature_index();
-                let sig_id_type = Type::int(u16::from(sig_id_size) * 8).unwrap();
                 let vmctx = self.vmctx(pos.func);
                 let base = pos.ins().global_value(pointer_type, vmctx);
                 let offset =
                     cast::i32(self.offsets.vmctx_vmshared_signature_id(sig_index)).unwrap();
-
-                // Load the caller ID.
-                let mut mem_flags = ir::MemFlags::trusted();
-                mem_flags.set_readonly();
-                let caller_sig_id = pos.ins().load(sig_id_type, mem_flags, base, offset);
-
-                // Load the callee ID.
-                let mem_flags = ir::MemFlags::trusted();
-                let callee_sig_id = pos.ins().load(
-                    sig_id_type,
-                    mem_flags,
-                    table_entry_addr,
-                    i32::from(self.offsets.vmcaller_checked_anyfunc_type_index()),
-                );
-
-                // Check that they match.
-                let cmp = pos.ins().icmp(IntCC::Equal, callee_sig_id, caller_sig_id);
-                pos.ins().trapz(cmp, ir::TrapCode::BadSignature);
+                Some((base, offset))
             }
+            TableStyle::CallerChecksRelocatedSignature => {
+                let table = pos.func.create_global_value(ir::GlobalValueData::Symbol {
+                    name: get_signature_table_name(),
+                    offset: Imm64::new(0),
+                    colocated: false,
+                });
+                let base = pos.ins().global_value(pointer_type, table);
+                let sig_id_size = self.offsets.size_of_vmshared_signature_index();
+                let offset = cast::i32(sig_index.as_u32() * u32::from(sig_id_size)).unwrap();
+                Some((base, offset))
+            }
+        };
+        if let Some((base, offset)) = caller_sig_id_location {
+            let sig_id_size = self.offsets.size_of_vmshared_signature_index();
+            let sig_id_type = Type::int(u16::from(sig_id_size) * 8).unwrap();
+
+            // Load the caller ID.
+            let mut mem_flags = ir::MemFlags::trusted();
+            mem_flags.set_readonly();
+            let caller_sig_id = pos.ins().load(sig_id_type, mem_flags, base, offset);
+
+            // Load the callee ID.
+            let mem_flags = ir::MemFlags::trusted();
+            let callee_sig_id = pos.ins().load(
+                sig_id_type,
+                mem_flags,
+                table_entry_addr,
+                i32::from(self.offsets.vmcaller_checked_anyfunc_type_index()),
+            );
+
+            // Check that they match.
+            let cmp = pos.ins().icmp(IntCC::Equal, callee_sig_id, caller_sig_id);
+            pos.ins().trapz(cmp, ir::TrapCode::BadSignature);
         }
 
         // Dereference table_entry_addr to get the function address.
@@ -0,0 +1,164 @@
+//! Checking a module's imports against the interface a host provides.
+
+use crate::module::Module;
+use cranelift_codegen::ir;
+use std::string::String;
+use std::vec::Vec;
+
+/// A function provided by the host.
+#[derive(Debug, Clone)]
+pub struct HostFunction {
+    /// The module name of the import.
+    pub module: String,
+    /// The field name of the import.
+    pub field: String,
+    /// The types of the parameters.
+    pub params: Vec<ir::Type>,
+    /// The types of the results.
+    pub returns: Vec<ir::Type>,
+}
+
+/// A global provided by the host.
+#[derive(Debug, Clone)]
+pub struct HostGlobal {
+    /// The module name of the import.
+    pub module: String,
+    /// The field name of the import.
+    pub field: String,
+    /// The type of the global.
+    pub ty: ir::Type,
+    /// Whether the global is mutable.
+    pub mutable: bool,
+}
+
+/// The entities a host provides for modules to import.
+#[derive(Debug, Clone, Default)]
+pub struct HostInterface {
+    /// The functions the host provides.
+    pub functions: Vec<HostFunction>,
+    /// The globals the host provides.
+    pub globals: Vec<HostGlobal>,
+    /// The names of the memories the host provides.
+    pub memories: Vec<(String, String)>,
+    /// The names of the tables the host provides.
+    pub tables: Vec<(String, String)>,
+}
+
+/// An import that doesn't match the host interface.
+#[derive(Fail, Debug)]
+pub enum ImportMismatch {
+    /// The host provides no entity of the right kind with this name.
+    #[fail(display = "Import {}::{} is not provided by the host", module, field)]
+    Missing {
+        /// The module name of the import.
+        module: String,
+        /// The field name of the import.
+        field: String,
+    },
+
+    /// The host provides the entity with a different type.
+    #[fail(
+        display = "Import {}::{} has type {} but the host provides {}",
+        module, field, actual, expected
+    )]
+    Type {
+        /// The module name of the import.
+        module: String,
+        /// The field name of the import.
+        field: String,
+        /// The type the host provides.
+        expected: String,
+        /// The type the module imports.
+        actual: String,
+    },
+}
+
+fn describe_function(params: &[ir::Type], returns: &[ir::Type]) -> String {
+    let list = |types: &[ir::Type]| {
+        types
+            .iter()
+            .map(|ty| format!("{}", ty))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!("({}) -> ({})", list(params), list(returns))
+}
+
+fn describe_global(ty: ir::Type, mutable: bool) -> String {
+    if mutable {
+        format!("mut {}", ty)
+    } else {
+        format!("{}", ty)
+    }
+}
+
+fn missing(name: &(String, String)) -> ImportMismatch {
+    ImportMismatch::Missing {
+        module: name.0.clone(),
+        field: name.1.clone(),
+    }
+}
+
+/// Check that every function, global, memory and table the module imports
+/// is provided by `interface`, with the same type for functions and
+/// globals, reporting the first import that isn't.
+pub fn validate_imports(module: &Module, interface: &HostInterface) -> Result<(), ImportMismatch> {
+    for (func_index, name) in module.imported_funcs.iter() {
+        let host = interface
+            .functions
+            .iter()
+            .find(|f| f.module == name.0 && f.field == name.1)
+            .ok_or_else(|| missing(name))?;
+        let signature = &module.signatures[module.functions[func_index]];
+        let params = signature
+            .params
+            .iter()
+            .filter(|p| p.purpose == ir::ArgumentPurpose::Normal)
+            .map(|p| p.value_type)
+            .collect::<Vec<_>>();
+        let returns = signature
+            .returns
+            .iter()
+            .map(|r| r.value_type)
+            .collect::<Vec<_>>();
+        if params != host.params || returns != host.returns {
+            return Err(ImportMismatch::Type {
+                module: name.0.clone(),
+                field: name.1.clone(),
+                expected: describe_function(&host.params, &host.returns),
+                actual: describe_function(&params, &returns),
+            });
+        }
+    }
+
+    for (global_index, name) in module.imported_globals.iter() {
+        let host = interface
+            .globals
+            .iter()
+            .find(|g| g.module == name.0 && g.field == name.1)
+            .ok_or_else(|| missing(name))?;
+        let global = &module.globals[global_index];
+        if global.ty != host.ty || global.mutability != host.mutable {
+            return Err(ImportMismatch::Type {
+                module: name.0.clone(),
+                field: name.1.clone(),
+                expected: describe_global(host.ty, host.mutable),
+                actual: describe_global(global.ty, global.mutability),
+            });
+        }
+    }
+
+    for name in module.imported_memories.values() {
+        if !interface.memories.contains(name) {
+            return Err(missing(name));
+        }
+    }
+
+    for name in module.imported_tables.values() {
+        if !interface.tables.contains(name) {
+            return Err(missing(name));
+        }
+    }
+
+    Ok(())
+}
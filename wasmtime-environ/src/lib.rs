@@ -37,18 +37,32 @@ extern crate std;
 #[macro_use]
 extern crate failure_derive;
 
+mod cache;
+mod call_graph;
 mod compilation;
 mod func_environ;
+mod host_interface;
 mod module;
 mod module_environ;
+#[cfg(feature = "serde")]
+mod serialize;
 mod tunables;
 mod vmoffsets;
 
 pub mod cranelift;
 
+pub use crate::cache::CompilationCache;
+#[cfg(feature = "std")]
+pub use crate::cache::FileSystemCache;
+pub use crate::call_graph::{call_sites, direct_callees, worst_case_stack_depths};
 pub use crate::compilation::{
-    AddressTransforms, Compilation, CompileError, InstructionAddressTransform, Relocation,
-    RelocationTarget, Relocations,
+    rebase_transforms, summarize_relocations, AddressTransforms, Compilation, CompileError,
+    CompileStats, FailedFunction, FrameSizes, FunctionAddressTransform, FunctionStats,
+    InstructionAddressTransform, Relocation, RelocationSummary, RelocationTarget, Relocations,
+    TrapInformation, TrapSite,
+};
+pub use crate::host_interface::{
+    validate_imports, HostFunction, HostGlobal, HostInterface, ImportMismatch,
 };
 pub use crate::module::{
     Export, MemoryPlan, MemoryStyle, Module, TableElements, TablePlan, TableStyle,
@@ -57,6 +71,8 @@ pub use crate::module_environ::{
     translate_signature, DataInitializer, DataInitializerLocation, FunctionBodyData,
     ModuleEnvironment, ModuleTranslation,
 };
+#[cfg(feature = "serde")]
+pub use crate::serialize::SerializedCompilation;
 pub use crate::tunables::Tunables;
 pub use crate::vmoffsets::{TargetSharedSignatureIndex, VMOffsets};
 
@@ -85,16 +85,25 @@ pub struct MemoryPlan {
     pub style: MemoryStyle,
     /// Our chosen offset-guard size.
     pub offset_guard_size: u64,
+    /// Whether the base of the memory, if defined by the module, is resolved
+    /// by a `RelocationTarget::MemoryBase` relocation. Only static memories,
+    /// which never move, are relocated.
+    pub relocated_base: bool,
 }
 
 impl MemoryPlan {
     /// Draw up a plan for implementing a `Memory`.
     pub fn for_memory(memory: Memory, tunables: &Tunables) -> Self {
         let (style, offset_guard_size) = MemoryStyle::for_memory(memory, tunables);
+        let relocated_base = match style {
+            MemoryStyle::Static { .. } => tunables.relocatable_memory_base,
+            MemoryStyle::Dynamic => false,
+        };
         Self {
             memory,
             style,
             offset_guard_size,
+            relocated_base,
         }
     }
 }
@@ -104,12 +113,20 @@ impl MemoryPlan {
 pub enum TableStyle {
     /// Signatures are stored in the table and checked in the caller.
     CallerChecksSignature,
+    /// Signatures are stored in the table and checked in the caller, which
+    /// finds its own signature ids in a table whose base address is
+    /// resolved by a `RelocationTarget::SignatureTableBase` relocation.
+    CallerChecksRelocatedSignature,
 }
 
 impl TableStyle {
     /// Decide on an implementation style for the given `Table`.
-    pub fn for_table(_table: Table, _tunables: &Tunables) -> Self {
-        TableStyle::CallerChecksSignature
+    pub fn for_table(_table: Table, tunables: &Tunables) -> Self {
+        if tunables.relocatable_signature_table {
+            TableStyle::CallerChecksRelocatedSignature
+        } else {
+            TableStyle::CallerChecksSignature
+        }
     }
 }
 
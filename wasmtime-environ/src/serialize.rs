@@ -0,0 +1,294 @@
+//! Serialization of the results of `compile_module`, with the `serde`
+//! feature, so that a build system can keep them across processes and emit
+//! the object later without compiling again.
+//!
+//! Cranelift's types don't implement `Serialize`, so the fields holding
+//! them name one of the modules here with `#[serde(with = "...")]`.
+
+use crate::compilation::{
+    AddressTransforms, Compilation, FrameSizes, Relocations, TrapInformation,
+};
+use cranelift_codegen::binemit;
+use cranelift_entity::{EntityRef, PrimaryMap};
+use serde::{Deserialize, Serialize};
+use std::string::String;
+use std::vec::Vec;
+
+/// The results of `compile_module`, tagged with the version of this crate
+/// that produced them.
+#[derive(Serialize, Deserialize)]
+pub struct SerializedCompilation {
+    version: String,
+    compilation: Compilation,
+    #[serde(with = "primary_map")]
+    relocations: Relocations,
+    #[serde(with = "primary_map")]
+    address_transforms: AddressTransforms,
+    #[serde(with = "primary_map")]
+    frame_sizes: FrameSizes,
+    #[serde(with = "primary_map")]
+    traps: TrapInformation,
+}
+
+impl SerializedCompilation {
+    /// Collects the results of `compile_module` to be serialized. Fails if
+    /// functions failed to compile, as their errors can't be serialized.
+    pub fn new(
+        compilation: Compilation,
+        relocations: Relocations,
+        address_transforms: AddressTransforms,
+        frame_sizes: FrameSizes,
+        traps: TrapInformation,
+    ) -> Result<Self, String> {
+        if !compilation.failed.is_empty() {
+            return Err(format!(
+                "{} functions failed to compile",
+                compilation.failed.len()
+            ));
+        }
+        Ok(Self {
+            version: String::from(crate::VERSION),
+            compilation,
+            relocations,
+            address_transforms,
+            frame_sizes,
+            traps,
+        })
+    }
+
+    /// Returns the results of `compile_module` this was created from. Fails
+    /// if they were serialized by another version of this crate, whose code
+    /// may expect a different runtime.
+    pub fn into_parts(
+        self,
+    ) -> Result<
+        (
+            Compilation,
+            Relocations,
+            AddressTransforms,
+            FrameSizes,
+            TrapInformation,
+        ),
+        String,
+    > {
+        if self.version != crate::VERSION {
+            return Err(format!(
+                "compiled by wasmtime-environ {}, not {}",
+                self.version,
+                crate::VERSION
+            ));
+        }
+        Ok((
+            self.compilation,
+            self.relocations,
+            self.address_transforms,
+            self.frame_sizes,
+            self.traps,
+        ))
+    }
+}
+
+/// The variants of `binemit::Reloc`. Deriving from the remote type makes
+/// adding a relocation kind to Cranelift a compile error here, rather than
+/// a relocation that can't be serialized.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "binemit::Reloc")]
+pub(crate) enum RelocDef {
+    Abs4,
+    Abs8,
+    X86PCRel4,
+    X86CallPCRel4,
+    X86CallPLTRel4,
+    X86GOTPCRel4,
+    Arm32Call,
+    Arm64Call,
+    RiscvCall,
+}
+
+/// Collects `values` into a map with consecutive keys.
+fn map_from_values<K: EntityRef, V>(values: Vec<V>) -> PrimaryMap<K, V> {
+    let mut map = PrimaryMap::with_capacity(values.len());
+    for value in values {
+        map.push(value);
+    }
+    map
+}
+
+/// A `PrimaryMap`, as the sequence of its values.
+pub(crate) mod primary_map {
+    use cranelift_entity::{EntityRef, PrimaryMap};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::vec::Vec;
+
+    pub fn serialize<K, V, S>(map: &PrimaryMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: EntityRef,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.values())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<PrimaryMap<K, V>, D::Error>
+    where
+        K: EntityRef,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(super::map_from_values(Vec::deserialize(deserializer)?))
+    }
+}
+
+/// An optional `PrimaryMap`, as the sequence of its values.
+pub(crate) mod option_primary_map {
+    use cranelift_entity::{EntityRef, PrimaryMap};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::vec::Vec;
+
+    pub fn serialize<K, V, S>(
+        map: &Option<PrimaryMap<K, V>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        K: EntityRef,
+        V: Serialize,
+        S: Serializer,
+    {
+        map.as_ref()
+            .map(|map| map.values().collect::<Vec<_>>())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<Option<PrimaryMap<K, V>>, D::Error>
+    where
+        K: EntityRef,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<Vec<V>>::deserialize(deserializer)?.map(super::map_from_values))
+    }
+}
+
+/// The jump table offsets of each function, as a sequence of offsets per
+/// function.
+pub(crate) mod jt_offsets {
+    use cranelift_codegen::ir;
+    use cranelift_entity::{EntityRef, PrimaryMap};
+    use cranelift_wasm::DefinedFuncIndex;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::vec::Vec;
+
+    pub fn serialize<S: Serializer>(
+        jt_offsets: &PrimaryMap<DefinedFuncIndex, ir::JumpTableOffsets>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(jt_offsets.values().map(|offsets| {
+            offsets
+                .iter()
+                .map(|(_, &offset)| offset)
+                .collect::<Vec<_>>()
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<PrimaryMap<DefinedFuncIndex, ir::JumpTableOffsets>, D::Error> {
+        let functions = Vec::<Vec<u32>>::deserialize(deserializer)?;
+        let mut jt_offsets = PrimaryMap::with_capacity(functions.len());
+        for offsets in functions {
+            let mut function_offsets = ir::JumpTableOffsets::new();
+            for (jt, offset) in offsets.into_iter().enumerate() {
+                function_offsets[ir::JumpTable::new(jt)] = offset;
+            }
+            jt_offsets.push(function_offsets);
+        }
+        Ok(jt_offsets)
+    }
+}
+
+/// An entity index, as its `u32` value.
+pub(crate) mod entity {
+    use cranelift_entity::EntityRef;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<K: EntityRef, S: Serializer>(
+        index: &K,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(index.index() as u32)
+    }
+
+    pub fn deserialize<'de, K: EntityRef, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<K, D::Error> {
+        Ok(K::new(u32::deserialize(deserializer)? as usize))
+    }
+}
+
+/// Pairs of entity indices, as pairs of their `u32` values.
+pub(crate) mod entity_pairs {
+    use cranelift_entity::EntityRef;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::vec::Vec;
+
+    pub fn serialize<K: EntityRef, S: Serializer>(
+        pairs: &[(K, K)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            pairs
+                .iter()
+                .map(|&(a, b)| (a.index() as u32, b.index() as u32)),
+        )
+    }
+
+    pub fn deserialize<'de, K: EntityRef, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(K, K)>, D::Error> {
+        Ok(Vec::<(u32, u32)>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(a, b)| (K::new(a as usize), K::new(b as usize)))
+            .collect())
+    }
+}
+
+/// An `ir::SourceLoc`, as its bits.
+pub(crate) mod source_loc {
+    use cranelift_codegen::ir;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(loc: &ir::SourceLoc, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(loc.bits())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<ir::SourceLoc, D::Error> {
+        Ok(ir::SourceLoc::new(u32::deserialize(deserializer)?))
+    }
+}
+
+/// A value with a textual form that `FromStr` parses back, such as
+/// `ir::TrapCode` and `ir::LibCall`, as that text.
+pub(crate) mod display_from_str {
+    use core::fmt::Display;
+    use core::str::FromStr;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::string::String;
+
+    pub fn serialize<T: Display, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T: FromStr, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse()
+            .map_err(|_| D::Error::custom(format!("unknown value '{}'", text)))
+    }
+}
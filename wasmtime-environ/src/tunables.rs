@@ -9,6 +9,15 @@ pub struct Tunables {
 
     /// The size of the offset guard for dynamic heaps.
     pub dynamic_memory_offset_guard_size: u64,
+
+    /// Whether `call_indirect` reads the caller's signature id from a
+    /// signature table located by relocation, rather than from the vmctx.
+    pub relocatable_signature_table: bool,
+
+    /// Whether static memories defined by the module are located by a
+    /// relocation against their base, rather than by loading the base from
+    /// the vmctx.
+    pub relocatable_memory_base: bool,
 }
 
 impl Default for Tunables {
@@ -39,6 +48,9 @@ impl Default for Tunables {
             /// Allocate a small guard to optimize common cases but without
             /// wasting too much memor.
             dynamic_memory_offset_guard_size: 0x1_0000,
+
+            relocatable_signature_table: false,
+            relocatable_memory_base: false,
         }
     }
 }
@@ -17,7 +17,7 @@ use std::vec::Vec;
 use wasmtime_debug::{emit_debugsections_image, DebugInfoData};
 use wasmtime_environ::cranelift;
 use wasmtime_environ::{
-    Compilation, CompileError, FunctionBodyData, Module, Relocations, Tunables,
+    Compilation, CompileError, FunctionBodyData, Module, RelocationTarget, Relocations, Tunables,
 };
 use wasmtime_runtime::{InstantiationError, SignatureRegistry, VMFunctionBody};
 
@@ -78,13 +78,24 @@ impl Compiler {
         ),
         SetupError,
     > {
-        let (compilation, relocations, address_transform) = cranelift::compile_module(
-            module,
-            function_body_inputs,
-            &*self.isa,
-            debug_data.is_some(),
-        )
-        .map_err(SetupError::Compile)?;
+        let (compilation, mut relocations, address_transform, _frame_sizes, _traps) =
+            cranelift::compile_module(
+                module,
+                function_body_inputs,
+                &*self.isa,
+                debug_data.is_some(),
+            )
+            .map_err(SetupError::Compile)?;
+
+        // Jump tables are part of their function's body, so absolute
+        // relocations against them are relocations within the body.
+        for (i, function_relocs) in relocations.iter_mut() {
+            for r in function_relocs {
+                if let RelocationTarget::JumpTable(jt) = r.reloc_target {
+                    r.reloc_target = RelocationTarget::Ebb(compilation.jt_offsets[i][jt]);
+                }
+            }
+        }
 
         let allocated_functions =
             allocate_functions(&mut self.code_memory, &compilation).map_err(|message| {
@@ -277,6 +277,7 @@ fn is_memory_compatible(exported: &MemoryPlan, imported: &MemoryPlan) -> bool {
             },
         style: _exported_style,
         offset_guard_size: _exported_offset_guard_size,
+        relocated_base: _exported_relocated_base,
     } = exported;
     let MemoryPlan {
         memory:
@@ -287,6 +288,7 @@ fn is_memory_compatible(exported: &MemoryPlan, imported: &MemoryPlan) -> bool {
             },
         style: _imported_style,
         offset_guard_size: _imported_offset_guard_size,
+        relocated_base: _imported_relocated_base,
     } = imported;
 
     imported_minimum <= exported_minimum
@@ -303,6 +305,8 @@ fn relocate(
     module: &Module,
 ) {
     for (i, function_relocs) in relocations.into_iter() {
+        let fatptr: *const [VMFunctionBody] = allocated_functions[i];
+        let body = fatptr as *const VMFunctionBody;
         for r in function_relocs {
             use self::libcalls::*;
             let target_func_address: usize = match r.reloc_target {
@@ -317,6 +321,16 @@ fn relocate(
                 RelocationTarget::Memory32Size => wasmtime_memory32_size as usize,
                 RelocationTarget::ImportedMemory32Grow => wasmtime_imported_memory32_grow as usize,
                 RelocationTarget::ImportedMemory32Size => wasmtime_imported_memory32_size as usize,
+                RelocationTarget::SignatureTableBase => {
+                    panic!("relocatable signature tables are not supported by the JIT")
+                }
+                RelocationTarget::MemoryBase { .. } => {
+                    panic!("relocatable memory bases are not supported by the JIT")
+                }
+                RelocationTarget::Ebb(offset) => body as usize + offset as usize,
+                RelocationTarget::JumpTable(_) => {
+                    panic!("jump table relocations are resolved by the compiler")
+                }
                 RelocationTarget::LibCall(libcall) => {
                     use cranelift_codegen::ir::LibCall::*;
                     match libcall {
@@ -337,8 +351,6 @@ fn relocate(
                 }
             };
 
-            let fatptr: *const [VMFunctionBody] = allocated_functions[i];
-            let body = fatptr as *const VMFunctionBody;
             match r.reloc {
                 #[cfg(target_pointer_width = "64")]
                 Reloc::Abs8 => unsafe {
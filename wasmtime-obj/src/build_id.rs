@@ -0,0 +1,179 @@
+use crate::elf::{patch_elf_section_headers, SectionPatch, SHF_ALLOC, SHT_NOTE};
+use faerie::{Artifact, Decl};
+use std::str::FromStr;
+use target_lexicon::BinaryFormat;
+use wasmtime_environ::{Compilation, Relocations};
+
+/// Name of the ELF section holding the build-id note.
+pub const BUILD_ID_SECTION: &str = ".note.gnu.build-id";
+
+/// `NT_GNU_BUILD_ID` note type.
+const NT_GNU_BUILD_ID: u32 = 3;
+
+/// How to compute the build-id of an object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuildId {
+    /// Don't emit a build-id.
+    None,
+    /// A 20-byte SHA-1 digest.
+    Sha1,
+    /// An 8-byte FNV-1a hash, cheaper to compute but more collision-prone.
+    Hash,
+}
+
+impl Default for BuildId {
+    fn default() -> Self {
+        BuildId::None
+    }
+}
+
+impl FromStr for BuildId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(BuildId::None),
+            "sha1" => Ok(BuildId::Sha1),
+            "hash" => Ok(BuildId::Hash),
+            _ => Err(format!(
+                "unknown build-id style '{}'; expected none, sha1 or hash",
+                s
+            )),
+        }
+    }
+}
+
+/// Feeds everything that determines the generated code to `sink`: the
+/// function bodies, their relocations and a description of the settings.
+fn hash_input(
+    compilation: &Compilation,
+    relocations: &Relocations,
+    settings: &str,
+    sink: &mut dyn FnMut(&[u8]),
+) {
+    sink(settings.as_bytes());
+    for (i, body) in compilation.functions.iter() {
+        sink(&(body.len() as u64).to_le_bytes());
+        sink(body);
+        for r in &relocations[i] {
+            sink(&r.offset.to_le_bytes());
+            sink(&r.addend.to_le_bytes());
+            sink(format!("{} {:?}", r.reloc, r.reloc_target).as_bytes());
+        }
+    }
+}
+
+/// Computes a deterministic build-id for the compiled module.
+pub fn compute_build_id(
+    style: BuildId,
+    compilation: &Compilation,
+    relocations: &Relocations,
+    settings: &str,
+) -> Option<Vec<u8>> {
+    match style {
+        BuildId::None => None,
+        BuildId::Sha1 => {
+            let mut hasher = sha1::Sha1::new();
+            hash_input(compilation, relocations, settings, &mut |bytes: &[u8]| {
+                hasher.update(bytes)
+            });
+            Some(hasher.digest().bytes().to_vec())
+        }
+        BuildId::Hash => {
+            let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+            hash_input(compilation, relocations, settings, &mut |bytes: &[u8]| {
+                for byte in bytes {
+                    hash ^= u64::from(*byte);
+                    hash = hash.wrapping_mul(0x0100_0000_01b3);
+                }
+            });
+            Some(hash.to_le_bytes().to_vec())
+        }
+    }
+}
+
+/// Declares the `.note.gnu.build-id` section. faerie writes it as a plain
+/// section; `patch_elf_note_sections` must be run on the emitted bytes to
+/// turn it into a proper note.
+pub fn emit_build_id(obj: &mut Artifact, build_id: &[u8]) -> Result<(), String> {
+    if obj.target.binary_format != BinaryFormat::Elf {
+        return Err(format!(
+            "build-ids are only supported for ELF output, not {}",
+            obj.target.binary_format
+        ));
+    }
+    let mut note = Vec::with_capacity(16 + build_id.len());
+    note.extend_from_slice(&4u32.to_le_bytes());
+    note.extend_from_slice(&(build_id.len() as u32).to_le_bytes());
+    note.extend_from_slice(&NT_GNU_BUILD_ID.to_le_bytes());
+    note.extend_from_slice(b"GNU\0");
+    note.extend_from_slice(build_id);
+    obj.declare_with(BUILD_ID_SECTION, Decl::debug_section(), note)
+        .map_err(|err| format!("{}", err))?;
+    Ok(())
+}
+
+/// Rewrites the headers of `.note.*` sections in a little-endian ELF image
+/// emitted by faerie so that they are `SHT_NOTE` and `SHF_ALLOC`, as tools
+/// such as `readelf -n` expect.
+pub fn patch_elf_note_sections(bytes: &mut [u8]) -> Result<(), String> {
+    patch_elf_section_headers(bytes, |name| {
+        if name.starts_with(b".note.") {
+            Some(SectionPatch {
+                sh_type: SHT_NOTE,
+                sh_flags: SHF_ALLOC,
+                sh_addralign: 4,
+            })
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_entity::PrimaryMap;
+
+    fn compilation(body: &[u8]) -> (Compilation, Relocations) {
+        let mut functions = PrimaryMap::new();
+        functions.push(body.to_vec());
+        let mut relocations = PrimaryMap::new();
+        relocations.push(Vec::new());
+        (Compilation::new(functions), relocations)
+    }
+
+    #[test]
+    fn test_compute_build_id_stable() {
+        for (style, len) in [(BuildId::Sha1, 20), (BuildId::Hash, 8)].iter() {
+            let (a, a_relocs) = compilation(&[0x90, 0xc3]);
+            let (b, b_relocs) = compilation(&[0x90, 0xc3]);
+            let (c, c_relocs) = compilation(&[0xcc, 0xc3]);
+
+            let id = compute_build_id(*style, &a, &a_relocs, "opt_level=speed").unwrap();
+            assert_eq!(id.len(), *len);
+            // The same input gives the same id, and a different body or
+            // different settings a different one.
+            assert_eq!(
+                compute_build_id(*style, &b, &b_relocs, "opt_level=speed"),
+                Some(id.clone())
+            );
+            assert_ne!(
+                compute_build_id(*style, &c, &c_relocs, "opt_level=speed"),
+                Some(id.clone())
+            );
+            assert_ne!(
+                compute_build_id(*style, &a, &a_relocs, "opt_level=none"),
+                Some(id)
+            );
+        }
+        let (a, a_relocs) = compilation(&[0xc3]);
+        assert_eq!(compute_build_id(BuildId::None, &a, &a_relocs, ""), None);
+    }
+
+    #[test]
+    fn test_patch_elf_note_sections_truncated() {
+        assert!(patch_elf_note_sections(&mut b"\x7fELF\x02\x01".to_vec()).is_err());
+        assert!(patch_elf_note_sections(&mut Vec::new()).is_err());
+    }
+}
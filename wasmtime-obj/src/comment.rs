@@ -0,0 +1,56 @@
+//! The `.comment` section, recording the compiler and settings an object
+//! was produced with, for reproducibility audits.
+//!
+//! As in the objects of C compilers, the section is a sequence of
+//! NUL-terminated strings, starting with an empty one, so that
+//! `readelf -p .comment` lists them:
+//!
+//! ```text
+//! wasmtime-obj 0.1.0 (cranelift 0.30.0)
+//! target: x86_64-unknown-linux-gnu
+//! opt_level: best
+//! ```
+//!
+//! followed by each line of the shared and ISA-specific flags, as printed by
+//! the `TargetIsa`.
+
+use crate::macho::check_debug_section_name;
+use cranelift_codegen::isa::TargetIsa;
+use faerie::{Artifact, Decl};
+
+/// Name of the section holding the producer note.
+pub const COMMENT_SECTION: &str = ".comment";
+
+/// Returns the strings of the producer note for code compiled by `isa`.
+pub fn producer_note(isa: &dyn TargetIsa) -> Vec<String> {
+    let mut note = vec![
+        format!(
+            "wasmtime-obj {} (cranelift {})",
+            env!("CARGO_PKG_VERSION"),
+            cranelift_codegen::VERSION
+        ),
+        format!("target: {}", isa.triple()),
+        format!("opt_level: {}", isa.flags().opt_level()),
+    ];
+    note.extend(
+        isa.to_string()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from),
+    );
+    note
+}
+
+/// Emits the `.comment` section with the producer note for `isa`, once the
+/// rest of the module has been emitted.
+pub fn emit_comment(obj: &mut Artifact, isa: &dyn TargetIsa) -> Result<(), String> {
+    check_debug_section_name(obj, COMMENT_SECTION)?;
+    let mut section = vec![0];
+    for line in producer_note(isa) {
+        section.extend_from_slice(line.as_bytes());
+        section.push(0);
+    }
+    obj.declare_with(COMMENT_SECTION, Decl::debug_section(), section)
+        .map_err(|err| format!("{}", err))?;
+    Ok(())
+}
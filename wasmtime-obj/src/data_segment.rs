@@ -1,4 +1,8 @@
+use crate::elf::{patch_elf_section_headers, SectionPatch, SHF_ALLOC, SHF_WRITE, SHT_PROGBITS};
+use cranelift_entity::EntityRef;
 use faerie::{Artifact, Decl};
+use std::collections::HashMap;
+use target_lexicon::BinaryFormat;
 use wasmtime_environ::DataInitializer;
 
 /// Declares data segment symbol
@@ -24,3 +28,172 @@ pub fn emit_data_segment(
         .map_err(|err| format!("{}", err))?;
     Ok(())
 }
+
+/// Emits a segment's data as a section of its own named `section` rather
+/// than as a `_memory_N` symbol in `.data`, so that a linker script can place
+/// it in a particular memory region. The section symbol carries the name of
+/// the section, which is what relocations against the segment must target.
+///
+/// faerie writes the section as non-allocated; `patch_elf_data_sections`
+/// must be run on the emitted bytes to make it writable, loaded data.
+pub fn emit_data_segment_in_section(
+    obj: &mut Artifact,
+    data_initaliazer: &DataInitializer,
+    section: &str,
+) -> Result<(), String> {
+    if obj.target.binary_format != BinaryFormat::Elf {
+        return Err(format!(
+            "custom data sections are only supported for ELF output, not {}",
+            obj.target.binary_format
+        ));
+    }
+    obj.declare_with(
+        section,
+        Decl::debug_section(),
+        Vec::from(data_initaliazer.data),
+    )
+    .map_err(|err| format!("{}", err))?;
+    Ok(())
+}
+
+/// Rewrites the headers of the sections named in `data_sections`, which
+/// maps segment indices to section names, in a little-endian ELF image
+/// emitted by faerie so that they are `SHF_ALLOC | SHF_WRITE` data.
+pub fn patch_elf_data_sections(
+    bytes: &mut [u8],
+    data_sections: &HashMap<usize, String>,
+) -> Result<(), String> {
+    patch_elf_section_headers(bytes, |name| {
+        if data_sections.values().any(|s| s.as_bytes() == name) {
+            Some(SectionPatch {
+                sh_type: SHT_PROGBITS,
+                sh_flags: SHF_ALLOC | SHF_WRITE,
+                sh_addralign: 1,
+            })
+        } else {
+            None
+        }
+    })
+}
+
+/// Name of the data symbol holding the deferred data initializer table.
+pub const DATA_INITIALIZERS_SYMBOL: &str = "_data_initializers";
+
+/// Flag set in an initializer entry whose offset is relative to a global.
+pub const DATA_INITIALIZER_HAS_BASE: u32 = 1 << 0;
+
+/// Flag reserved for passive segments. This translator doesn't support the
+/// bulk-memory proposal yet, so it is never set.
+pub const DATA_INITIALIZER_PASSIVE: u32 = 1 << 1;
+
+/// Encodes the data initializers as a table the runtime applies at
+/// instantiation instead of baking them into per-segment data symbols.
+///
+/// All fields are little-endian `u32`s:
+///
+/// ```text
+/// count
+/// count times:
+///     memory_index
+///     flags         DATA_INITIALIZER_HAS_BASE | DATA_INITIALIZER_PASSIVE
+///     base          global index, meaningful only with DATA_INITIALIZER_HAS_BASE
+///     offset        constant offset, added to the global's value if present
+///     length        number of data bytes that follow
+///     data          `length` bytes, zero-padded to a multiple of 4
+/// ```
+pub fn encode_data_initializers(data_initializers: &[DataInitializer]) -> Vec<u8> {
+    fn push_u32(out: &mut Vec<u8>, value: u32) {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut out = Vec::new();
+    push_u32(&mut out, data_initializers.len() as u32);
+    for initializer in data_initializers {
+        let location = &initializer.location;
+        let (flags, base) = match location.base {
+            Some(base) => (DATA_INITIALIZER_HAS_BASE, base.index() as u32),
+            None => (0, 0),
+        };
+        push_u32(&mut out, location.memory_index.index() as u32);
+        push_u32(&mut out, flags);
+        push_u32(&mut out, base);
+        push_u32(&mut out, location.offset as u32);
+        push_u32(&mut out, initializer.data.len() as u32);
+        out.extend_from_slice(initializer.data);
+        while out.len() % 4 != 0 {
+            out.push(0);
+        }
+    }
+    out
+}
+
+/// Emits the deferred data initializer table as a global data symbol.
+pub fn emit_data_initializers_table(
+    obj: &mut Artifact,
+    data_initializers: &[DataInitializer],
+) -> Result<(), String> {
+    obj.declare_with(
+        DATA_INITIALIZERS_SYMBOL,
+        Decl::data().global(),
+        encode_data_initializers(data_initializers),
+    )
+    .map_err(|err| format!("{}", err))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_wasm::{GlobalIndex, MemoryIndex};
+    use wasmtime_environ::DataInitializerLocation;
+
+    fn read_u32(bytes: &[u8], at: usize) -> u32 {
+        let mut word = [0; 4];
+        word.copy_from_slice(&bytes[at..at + 4]);
+        u32::from_le_bytes(word)
+    }
+
+    #[test]
+    fn test_encode_data_initializers() {
+        let data_initializers = [
+            DataInitializer {
+                location: DataInitializerLocation {
+                    memory_index: MemoryIndex::new(0),
+                    base: None,
+                    offset: 16,
+                },
+                data: b"hello",
+            },
+            DataInitializer {
+                location: DataInitializerLocation {
+                    memory_index: MemoryIndex::new(1),
+                    base: Some(GlobalIndex::new(2)),
+                    offset: 4,
+                },
+                data: b"wasm",
+            },
+        ];
+        let table = encode_data_initializers(&data_initializers);
+        assert_eq!(read_u32(&table, 0), 2);
+
+        // The first entry's 5 bytes of data are padded to 8.
+        assert_eq!(read_u32(&table, 4), 0);
+        assert_eq!(read_u32(&table, 8), 0);
+        assert_eq!(read_u32(&table, 12), 0);
+        assert_eq!(read_u32(&table, 16), 16);
+        assert_eq!(read_u32(&table, 20), 5);
+        assert_eq!(&table[24..32], b"hello\0\0\0");
+
+        assert_eq!(read_u32(&table, 32), 1);
+        assert_eq!(read_u32(&table, 36), DATA_INITIALIZER_HAS_BASE);
+        assert_eq!(read_u32(&table, 40), 2);
+        assert_eq!(read_u32(&table, 44), 4);
+        assert_eq!(read_u32(&table, 48), 4);
+        assert_eq!(&table[52..], b"wasm");
+    }
+
+    #[test]
+    fn test_encode_no_data_initializers() {
+        assert_eq!(encode_data_initializers(&[]), [0, 0, 0, 0]);
+    }
+}
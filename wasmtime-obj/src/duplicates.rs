@@ -0,0 +1,57 @@
+use cranelift_codegen::binemit::Reloc;
+use cranelift_codegen::isa::TargetIsa;
+use target_lexicon::{Architecture, BinaryFormat};
+use wasmtime_environ::{
+    AddressTransforms, Compilation, FrameSizes, Module, Relocation, RelocationTarget, Relocations,
+    TrapInformation,
+};
+
+/// `jmp rel32`, with the displacement left for the relocation.
+const X86_64_THUNK: [u8; 5] = [0xe9, 0, 0, 0, 0];
+
+/// Replaces the body of each function listed in `compilation.duplicates`
+/// with a thunk jumping to the function it duplicates, so that the object
+/// holds the shared code once. Everything describing the copied body, its
+/// relocations, trap sites, jump tables and line information, is replaced
+/// with that of the thunk, which has no frame of its own.
+///
+/// Only ELF objects for x86-64 are supported; for other targets the copies
+/// are left in place.
+pub fn thunk_duplicate_functions(
+    module: &Module,
+    compilation: &mut Compilation,
+    relocations: &mut Relocations,
+    traps: &mut TrapInformation,
+    frame_sizes: &mut FrameSizes,
+    address_transforms: &mut AddressTransforms,
+    isa: &dyn TargetIsa,
+) {
+    let triple = isa.triple();
+    if triple.architecture != Architecture::X86_64 || triple.binary_format != BinaryFormat::Elf {
+        return;
+    }
+    // In PIC the original may be preempted, so the jump goes through the
+    // PLT like calls do.
+    let reloc = if isa.flags().is_pic() {
+        Reloc::X86CallPLTRel4
+    } else {
+        Reloc::X86CallPCRel4
+    };
+    for &(duplicate, original) in &compilation.duplicates {
+        compilation.functions[duplicate] = X86_64_THUNK.to_vec();
+        compilation.jt_offsets[duplicate].clear();
+        relocations[duplicate] = vec![Relocation {
+            reloc,
+            reloc_target: RelocationTarget::UserFunc(module.func_index(original)),
+            offset: 1,
+            // The displacement is relative to the end of the instruction.
+            addend: -4,
+        }];
+        traps[duplicate].clear();
+        frame_sizes[duplicate] = 0;
+        if let Some(transform) = address_transforms.get_mut(duplicate) {
+            transform.locations.clear();
+            transform.body_len = X86_64_THUNK.len();
+        }
+    }
+}
@@ -0,0 +1,647 @@
+//! Post-processing of the ELF images emitted by faerie, for section and
+//! symbol attributes faerie has no way to express.
+
+/// `SHT_PROGBITS` section type.
+pub const SHT_PROGBITS: u32 = 1;
+
+/// `SHT_NOTE` section type.
+pub const SHT_NOTE: u32 = 7;
+
+/// `SHF_WRITE` section flag.
+pub const SHF_WRITE: u64 = 0x1;
+
+/// `SHF_ALLOC` section flag.
+pub const SHF_ALLOC: u64 = 0x2;
+
+/// New attributes for a section header.
+pub struct SectionPatch {
+    /// The section type.
+    pub sh_type: u32,
+    /// Flags to set in addition to the existing ones.
+    pub sh_flags: u64,
+    /// The section alignment.
+    pub sh_addralign: u64,
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from(bytes[at]) | u16::from(bytes[at + 1]) << 8
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from(read_u16(bytes, at)) | u32::from(read_u16(bytes, at + 2)) << 16
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from(read_u32(bytes, at)) | u64::from(read_u32(bytes, at + 4)) << 32
+}
+
+/// Returns the `len` bytes at `at`, failing if the image is too short, as
+/// a malformed or unexpected image must not panic.
+fn get(bytes: &[u8], at: usize, len: usize) -> Result<&[u8], String> {
+    at.checked_add(len)
+        .and_then(|end| bytes.get(at..end))
+        .ok_or_else(|| format!("ELF image truncated at offset {:#x}", at))
+}
+
+/// Overwrites the bytes at `at` with `value`, failing if the image is too
+/// short.
+fn put(bytes: &mut [u8], at: usize, value: &[u8]) -> Result<(), String> {
+    at.checked_add(value.len())
+        .and_then(move |end| bytes.get_mut(at..end))
+        .ok_or_else(|| format!("ELF image truncated at offset {:#x}", at))?
+        .copy_from_slice(value);
+    Ok(())
+}
+
+fn get_u8(bytes: &[u8], at: usize) -> Result<u8, String> {
+    Ok(get(bytes, at, 1)?[0])
+}
+
+fn get_u16(bytes: &[u8], at: usize) -> Result<u16, String> {
+    get(bytes, at, 2).map(|b| read_u16(b, 0))
+}
+
+fn get_u32(bytes: &[u8], at: usize) -> Result<u32, String> {
+    get(bytes, at, 4).map(|b| read_u32(b, 0))
+}
+
+fn get_u64(bytes: &[u8], at: usize) -> Result<u64, String> {
+    get(bytes, at, 8).map(|b| read_u64(b, 0))
+}
+
+/// The fields of the ELF header locating the section headers, and the
+/// offsets of section header fields that differ between classes.
+struct SectionHeaders {
+    is_64: bool,
+    shoff: usize,
+    shentsize: usize,
+    shnum: u16,
+    shstrndx: u16,
+    sh_offset: usize,
+    sh_size: usize,
+    sh_link: usize,
+    sh_addralign: usize,
+}
+
+impl SectionHeaders {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if get(bytes, 0, 4)? != b"\x7fELF" {
+            return Err(String::from("not an ELF image"));
+        }
+        if get_u8(bytes, 0x5)? != 1 {
+            return Err(String::from("big-endian ELF images are not supported"));
+        }
+        let headers = match get_u8(bytes, 0x4)? {
+            1 => Self {
+                is_64: false,
+                shoff: get_u32(bytes, 0x20)? as usize,
+                shentsize: get_u16(bytes, 0x2E)? as usize,
+                shnum: get_u16(bytes, 0x30)?,
+                shstrndx: get_u16(bytes, 0x32)?,
+                sh_offset: 0x10,
+                sh_size: 0x14,
+                sh_link: 0x18,
+                sh_addralign: 0x20,
+            },
+            2 => Self {
+                is_64: true,
+                shoff: get_u64(bytes, 0x28)? as usize,
+                shentsize: get_u16(bytes, 0x3A)? as usize,
+                shnum: get_u16(bytes, 0x3C)?,
+                shstrndx: get_u16(bytes, 0x3E)?,
+                sh_offset: 0x18,
+                sh_size: 0x20,
+                sh_link: 0x28,
+                sh_addralign: 0x30,
+            },
+            _ => return Err(String::from("unknown ELF class")),
+        };
+        // Every header must lie within the image, and hold the fields read.
+        if headers.shentsize < headers.sh_addralign + headers.word_size() {
+            return Err(format!(
+                "ELF section header size {} is too small",
+                headers.shentsize
+            ));
+        }
+        if headers.shnum > 0 {
+            get(bytes, headers.header(headers.shnum - 1)?, headers.shentsize)?;
+        }
+        Ok(headers)
+    }
+
+    /// The size of an address-sized field.
+    fn word_size(&self) -> usize {
+        if self.is_64 {
+            8
+        } else {
+            4
+        }
+    }
+
+    /// Offset of the header of section `index`.
+    fn header(&self, index: u16) -> Result<usize, String> {
+        if index >= self.shnum {
+            return Err(format!("ELF section index {} is out of range", index));
+        }
+        (index as usize)
+            .checked_mul(self.shentsize)
+            .and_then(|offset| offset.checked_add(self.shoff))
+            .ok_or_else(|| format!("ELF section header {} is out of range", index))
+    }
+
+    /// Reads an address-sized field.
+    fn read_word(&self, bytes: &[u8], at: usize) -> Result<usize, String> {
+        if self.is_64 {
+            Ok(get_u64(bytes, at)? as usize)
+        } else {
+            Ok(get_u32(bytes, at)? as usize)
+        }
+    }
+
+    /// Writes an address-sized field.
+    fn write_word(&self, bytes: &mut [u8], at: usize, value: u64) -> Result<(), String> {
+        if self.is_64 {
+            put(bytes, at, &value.to_le_bytes())
+        } else {
+            put(bytes, at, &(value as u32).to_le_bytes())
+        }
+    }
+}
+
+/// Reads the NUL-terminated string at `start`.
+fn read_str(bytes: &[u8], start: usize) -> Result<&[u8], String> {
+    let tail = bytes
+        .get(start..)
+        .ok_or_else(|| format!("ELF string at offset {:#x} is out of range", start))?;
+    let len = tail
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or_else(|| String::from("unterminated string"))?;
+    Ok(&tail[..len])
+}
+
+/// Rewrites the header of each section in a little-endian ELF image for
+/// which `patch` returns new attributes, given the section's name.
+pub fn patch_elf_section_headers<F>(bytes: &mut [u8], patch: F) -> Result<(), String>
+where
+    F: Fn(&[u8]) -> Option<SectionPatch>,
+{
+    // `sh_flags` is at the same offset in both classes.
+    const SH_FLAGS: usize = 0x8;
+
+    let headers = SectionHeaders::parse(bytes)?;
+    let strtab = headers.read_word(bytes, headers.header(headers.shstrndx)? + headers.sh_offset)?;
+    for index in 0..headers.shnum {
+        let off = headers.header(index)?;
+        let name_start = strtab
+            .checked_add(get_u32(bytes, off)? as usize)
+            .ok_or_else(|| format!("ELF section name {} is out of range", index))?;
+        let section = match patch(read_str(bytes, name_start)?) {
+            Some(section) => section,
+            None => continue,
+        };
+        put(bytes, off + 4, &section.sh_type.to_le_bytes())?;
+        let sh_flags = headers.read_word(bytes, off + SH_FLAGS)? as u64;
+        headers.write_word(bytes, off + SH_FLAGS, sh_flags | section.sh_flags)?;
+        headers.write_word(bytes, off + headers.sh_addralign, section.sh_addralign)?;
+    }
+    Ok(())
+}
+
+/// `SHT_SYMTAB` section type.
+const SHT_SYMTAB: u32 = 2;
+
+/// `STB_GLOBAL` symbol binding.
+const STB_GLOBAL: u8 = 1;
+
+/// A symbol table, and the string table holding its names.
+struct SymbolTable {
+    /// Offset of the first symbol.
+    offset: usize,
+    /// Offset past the last symbol.
+    end: usize,
+    /// Offset of the section header of the string table.
+    strtab_header: usize,
+    /// Offset of the string table.
+    strtab: usize,
+}
+
+/// Returns the symbol table described by the section header at `off`, if
+/// it is one, after checking that it lies within the image.
+fn symbol_table(
+    bytes: &[u8],
+    headers: &SectionHeaders,
+    off: usize,
+    entsize: usize,
+) -> Result<Option<SymbolTable>, String> {
+    if get_u32(bytes, off + 4)? != SHT_SYMTAB {
+        return Ok(None);
+    }
+    let offset = headers.read_word(bytes, off + headers.sh_offset)?;
+    let size = headers.read_word(bytes, off + headers.sh_size)?;
+    get(bytes, offset, size)?;
+    if size % entsize != 0 {
+        return Err(format!(
+            "ELF symbol table size {} is not a multiple of {}",
+            size, entsize
+        ));
+    }
+    let strtab_header = headers.header(get_u32(bytes, off + headers.sh_link)? as u16)?;
+    let strtab = headers.read_word(bytes, strtab_header + headers.sh_offset)?;
+    Ok(Some(SymbolTable {
+        offset,
+        end: offset + size,
+        strtab_header,
+        strtab,
+    }))
+}
+
+/// Reads the name of the symbol at `sym`.
+fn symbol_name<'a>(bytes: &'a [u8], table: &SymbolTable, sym: usize) -> Result<&'a [u8], String> {
+    let name_start = table
+        .strtab
+        .checked_add(get_u32(bytes, sym)? as usize)
+        .ok_or_else(|| String::from("ELF symbol name is out of range"))?;
+    read_str(bytes, name_start)
+}
+
+/// Sets the visibility, the low bits of `st_other`, of each defined global
+/// symbol in a little-endian ELF image for which `visibility` returns one,
+/// given the symbol's name.
+pub fn patch_elf_symbol_visibility<F>(bytes: &mut [u8], visibility: F) -> Result<(), String>
+where
+    F: Fn(&[u8]) -> Option<u8>,
+{
+    let headers = SectionHeaders::parse(bytes)?;
+    // Offsets of `st_info`, `st_other` and `st_shndx`, and the entry size.
+    let (st_info, st_other, st_shndx, entsize) = if headers.is_64 {
+        (4, 5, 6, 24)
+    } else {
+        (12, 13, 14, 16)
+    };
+    for index in 0..headers.shnum {
+        let table = match symbol_table(bytes, &headers, headers.header(index)?, entsize)? {
+            Some(table) => table,
+            None => continue,
+        };
+        for sym in (table.offset..table.end).step_by(entsize) {
+            if get_u8(bytes, sym + st_info)? >> 4 != STB_GLOBAL
+                || get_u16(bytes, sym + st_shndx)? == 0
+            {
+                continue;
+            }
+            if let Some(v) = visibility(symbol_name(bytes, &table, sym)?) {
+                let other = get_u8(bytes, sym + st_other)?;
+                put(bytes, sym + st_other, &[(other & !0x3) | v])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `STT_SECTION` symbol type.
+const STT_SECTION: u8 = 3;
+
+/// Appends a copy of the string table whose header is at `header`, extended
+/// with `names`, to the end of the image and points the header at it.
+/// Returns the offset of each of `names` in the new table.
+fn append_string_table(
+    bytes: &mut Vec<u8>,
+    headers: &SectionHeaders,
+    header: usize,
+    names: &[Vec<u8>],
+) -> Result<Vec<u32>, String> {
+    let offset = headers.read_word(bytes, header + headers.sh_offset)?;
+    let size = headers.read_word(bytes, header + headers.sh_size)?;
+    let mut table = get(bytes, offset, size)?.to_vec();
+    let mut name_offsets = Vec::with_capacity(names.len());
+    for name in names {
+        name_offsets.push(table.len() as u32);
+        table.extend_from_slice(name);
+        table.push(0);
+    }
+    let new_offset = bytes.len() as u64;
+    headers.write_word(bytes, header + headers.sh_offset, new_offset)?;
+    headers.write_word(bytes, header + headers.sh_size, table.len() as u64)?;
+    bytes.extend_from_slice(&table);
+    Ok(name_offsets)
+}
+
+/// Renames each section of a little-endian ELF image for which `rename`
+/// returns a new name, given the section's name, along with the section
+/// symbols named after it, which relocations against the section refer to.
+///
+/// The string tables can't grow in place, so copies holding the new names
+/// are appended to the image.
+pub fn patch_elf_section_names<F>(bytes: &mut Vec<u8>, rename: F) -> Result<(), String>
+where
+    F: Fn(&[u8]) -> Option<Vec<u8>>,
+{
+    let headers = SectionHeaders::parse(bytes)?;
+
+    let mut sections = Vec::new();
+    let mut renamed = Vec::new();
+    let strtab = headers.read_word(bytes, headers.header(headers.shstrndx)? + headers.sh_offset)?;
+    for index in 0..headers.shnum {
+        let name_start = strtab
+            .checked_add(get_u32(bytes, headers.header(index)?)? as usize)
+            .ok_or_else(|| format!("ELF section name {} is out of range", index))?;
+        let name = read_str(bytes, name_start)?;
+        if let Some(new_name) = rename(name) {
+            sections.push(index);
+            renamed.push((name.to_vec(), new_name));
+        }
+    }
+    if sections.is_empty() {
+        return Ok(());
+    }
+
+    // Section symbols first, as their string table may be the one holding
+    // the section names, which is then copied with the symbol names.
+    let (st_info, entsize) = if headers.is_64 { (4, 24) } else { (12, 16) };
+    for index in 0..headers.shnum {
+        let table = match symbol_table(bytes, &headers, headers.header(index)?, entsize)? {
+            Some(table) => table,
+            None => continue,
+        };
+        let mut symbols = Vec::new();
+        let mut names = Vec::new();
+        for sym in (table.offset..table.end).step_by(entsize) {
+            if get_u8(bytes, sym + st_info)? & 0xf != STT_SECTION || get_u32(bytes, sym)? == 0 {
+                continue;
+            }
+            let name = symbol_name(bytes, &table, sym)?;
+            if let Some((_, new_name)) = renamed.iter().find(|(old, _)| old.as_slice() == name) {
+                symbols.push(sym);
+                names.push(new_name.clone());
+            }
+        }
+        if symbols.is_empty() {
+            continue;
+        }
+        let name_offsets = append_string_table(bytes, &headers, table.strtab_header, &names)?;
+        for (sym, name_offset) in symbols.into_iter().zip(name_offsets) {
+            put(bytes, sym, &name_offset.to_le_bytes())?;
+        }
+    }
+
+    let names = renamed.into_iter().map(|(_, new)| new).collect::<Vec<_>>();
+    let shstrtab_header = headers.header(headers.shstrndx)?;
+    let name_offsets = append_string_table(bytes, &headers, shstrtab_header, &names)?;
+    for (index, name_offset) in sections.into_iter().zip(name_offsets) {
+        put(bytes, headers.header(index)?, &name_offset.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Adds a global symbol named `alias` for each `(target, alias)` of
+/// `aliases` to the symbol tables of a little-endian ELF image, with the
+/// section, value, size, type and visibility of the defined symbol `target`.
+///
+/// The symbol and string tables can't grow in place, so copies holding the
+/// new symbols are appended to the image. The new symbols are global, so
+/// they belong after the local ones anyway.
+pub fn patch_elf_symbol_aliases(
+    bytes: &mut Vec<u8>,
+    aliases: &[(Vec<u8>, Vec<u8>)],
+) -> Result<(), String> {
+    if aliases.is_empty() {
+        return Ok(());
+    }
+    let headers = SectionHeaders::parse(bytes)?;
+    // Offsets of `st_info` and `st_shndx`, and the entry size.
+    let (st_info, st_shndx, entsize) = if headers.is_64 {
+        (4, 6, 24)
+    } else {
+        (12, 14, 16)
+    };
+    for index in 0..headers.shnum {
+        let header = headers.header(index)?;
+        let table = match symbol_table(bytes, &headers, header, entsize)? {
+            Some(table) => table,
+            None => continue,
+        };
+        let mut entries = Vec::with_capacity(aliases.len());
+        for (target, alias) in aliases {
+            let mut defined = None;
+            for sym in (table.offset..table.end).step_by(entsize) {
+                let name = symbol_name(bytes, &table, sym)?;
+                if name == alias.as_slice() {
+                    return Err(format!(
+                        "'{}' collides with a symbol of the object",
+                        String::from_utf8_lossy(alias)
+                    ));
+                }
+                if name == target.as_slice() && get_u16(bytes, sym + st_shndx)? != 0 {
+                    defined = Some(sym);
+                }
+            }
+            let sym = defined.ok_or_else(|| {
+                format!(
+                    "'{}' would alias '{}', which isn't defined",
+                    String::from_utf8_lossy(alias),
+                    String::from_utf8_lossy(target)
+                )
+            })?;
+            let mut entry = get(bytes, sym, entsize)?.to_vec();
+            entry[st_info] = STB_GLOBAL << 4 | entry[st_info] & 0xf;
+            entries.push(entry);
+        }
+
+        let names = aliases
+            .iter()
+            .map(|(_, alias)| alias.clone())
+            .collect::<Vec<_>>();
+        let name_offsets = append_string_table(bytes, &headers, table.strtab_header, &names)?;
+        let mut symbols = get(bytes, table.offset, table.end - table.offset)?.to_vec();
+        for (mut entry, name_offset) in entries.into_iter().zip(name_offsets) {
+            entry[..4].copy_from_slice(&name_offset.to_le_bytes());
+            symbols.extend_from_slice(&entry);
+        }
+        while bytes.len() % headers.word_size() != 0 {
+            bytes.push(0);
+        }
+        let new_offset = bytes.len() as u64;
+        headers.write_word(bytes, header + headers.sh_offset, new_offset)?;
+        headers.write_word(bytes, header + headers.sh_size, symbols.len() as u64)?;
+        bytes.extend_from_slice(&symbols);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_u16(bytes: &mut [u8], at: usize, value: u16) {
+        bytes[at..at + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(bytes: &mut [u8], at: usize, value: u32) {
+        bytes[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u64(bytes: &mut [u8], at: usize, value: u64) {
+        bytes[at..at + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Builds an ELF64 image with a `.symtab` holding a local symbol and
+    /// the global symbols `a` (defined) and `b` (undefined).
+    fn elf_with_symbols() -> Vec<u8> {
+        const SHSTRTAB: usize = 0x40;
+        const SYMTAB: usize = 0x60;
+        const STRTAB: usize = 0xC0;
+        const SHOFF: usize = 0xD0;
+
+        let mut bytes = vec![0; SHOFF + 4 * 64];
+        bytes[0..4].copy_from_slice(b"\x7fELF");
+        bytes[4] = 2;
+        bytes[5] = 1;
+        put_u64(&mut bytes, 0x28, SHOFF as u64);
+        put_u16(&mut bytes, 0x3A, 64);
+        put_u16(&mut bytes, 0x3C, 4);
+        put_u16(&mut bytes, 0x3E, 1);
+
+        let shstrtab = b"\0.shstrtab\0.symtab\0.strtab\0";
+        bytes[SHSTRTAB..SHSTRTAB + shstrtab.len()].copy_from_slice(shstrtab);
+        let strtab = b"\0l\0a\0b\0";
+        bytes[STRTAB..STRTAB + strtab.len()].copy_from_slice(strtab);
+
+        // Symbol 0 is the null symbol.
+        for (i, (name, info, shndx)) in [(1, 0x02, 1), (3, 0x12, 1), (5, 0x12, 0)]
+            .iter()
+            .enumerate()
+        {
+            let sym = SYMTAB + (i + 1) * 24;
+            put_u32(&mut bytes, sym, *name);
+            bytes[sym + 4] = *info;
+            put_u16(&mut bytes, sym + 6, *shndx);
+        }
+
+        let sections = [
+            (1, 3, SHSTRTAB, shstrtab.len(), 0),
+            (11, SHT_SYMTAB, SYMTAB, 4 * 24, 3),
+            (19, 3, STRTAB, strtab.len(), 0),
+        ];
+        for (i, (name, sh_type, offset, size, link)) in sections.iter().enumerate() {
+            let header = SHOFF + (i + 1) * 64;
+            put_u32(&mut bytes, header, *name);
+            put_u32(&mut bytes, header + 4, *sh_type);
+            put_u64(&mut bytes, header + 0x18, *offset as u64);
+            put_u64(&mut bytes, header + 0x20, *size as u64);
+            put_u32(&mut bytes, header + 0x28, *link);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_patch_elf_symbol_visibility() {
+        let mut bytes = elf_with_symbols();
+        patch_elf_symbol_visibility(&mut bytes, |_| Some(2)).unwrap();
+        let st_other = |bytes: &[u8], i: usize| bytes[0x60 + i * 24 + 5];
+        // Only the defined global symbol is changed.
+        assert_eq!(st_other(&bytes, 1), 0);
+        assert_eq!(st_other(&bytes, 2), 2);
+        assert_eq!(st_other(&bytes, 3), 0);
+
+        patch_elf_symbol_visibility(&mut bytes, |name| if name == b"a" { Some(3) } else { None })
+            .unwrap();
+        assert_eq!(st_other(&bytes, 2), 3);
+    }
+
+    #[test]
+    fn test_patch_elf_symbol_aliases() {
+        let mut bytes = elf_with_symbols();
+        put_u64(&mut bytes, 0x60 + 2 * 24 + 8, 0x10);
+        patch_elf_symbol_aliases(&mut bytes, &[(b"a".to_vec(), b"c".to_vec())]).unwrap();
+        let symtab = get_u64(&bytes, 0xD0 + 2 * 64 + 0x18).unwrap() as usize;
+        assert_eq!(get_u64(&bytes, 0xD0 + 2 * 64 + 0x20), Ok(5 * 24));
+        let strtab = get_u64(&bytes, 0xD0 + 3 * 64 + 0x18).unwrap() as usize;
+        let alias = symtab + 4 * 24;
+        let name = get_u32(&bytes, alias).unwrap() as usize;
+        assert_eq!(read_str(&bytes, strtab + name), Ok(&b"c"[..]));
+        // The rest of the entry is that of `a`.
+        assert_eq!(
+            get(&bytes, alias + 4, 20),
+            get(&bytes, symtab + 2 * 24 + 4, 20)
+        );
+
+        // Names taken by any symbol, or undefined targets, are rejected.
+        let bytes = elf_with_symbols();
+        for &(target, alias) in &[(b"a", b"b"), (b"a", b"l"), (b"b", b"c")] {
+            let mut patched = bytes.clone();
+            let aliases = [(target.to_vec(), alias.to_vec())];
+            assert!(patch_elf_symbol_aliases(&mut patched, &aliases).is_err());
+        }
+    }
+
+    #[test]
+    fn test_patch_elf_section_headers() {
+        let mut bytes = elf_with_symbols();
+        // Set a stale high byte, which must be overwritten.
+        put_u64(&mut bytes, 0xD0 + 3 * 64 + 0x30, 1 << 40);
+        patch_elf_section_headers(&mut bytes, |name| {
+            if name == b".strtab" {
+                Some(SectionPatch {
+                    sh_type: SHT_PROGBITS,
+                    sh_flags: SHF_ALLOC | 0x100,
+                    sh_addralign: 0x1_0000,
+                })
+            } else {
+                None
+            }
+        })
+        .unwrap();
+        let header = 0xD0 + 3 * 64;
+        assert_eq!(get_u32(&bytes, header + 4), Ok(SHT_PROGBITS));
+        assert_eq!(get_u64(&bytes, header + 0x8), Ok(SHF_ALLOC | 0x100));
+        assert_eq!(get_u64(&bytes, header + 0x30), Ok(0x1_0000));
+    }
+
+    #[test]
+    fn test_patch_elf_truncated() {
+        let bytes = elf_with_symbols();
+        // Cut off the last section header, and then the string table the
+        // names are read from.
+        for len in &[bytes.len() - 1, 0xC0, 0x10, 0] {
+            let mut truncated = bytes[..*len].to_vec();
+            assert!(patch_elf_section_headers(&mut truncated, |_| None).is_err());
+            assert!(patch_elf_symbol_visibility(&mut truncated, |_| Some(2)).is_err());
+            assert!(patch_elf_section_names(&mut truncated, |_| Some(b"x".to_vec())).is_err());
+            let aliases = [(b"a".to_vec(), b"c".to_vec())];
+            assert!(patch_elf_symbol_aliases(&mut truncated, &aliases).is_err());
+        }
+    }
+
+    #[test]
+    fn test_patch_elf_section_names() {
+        let mut bytes = elf_with_symbols();
+        let len = bytes.len();
+        patch_elf_section_names(&mut bytes, |name| {
+            if name == b".symtab" {
+                Some(b"m.symtab".to_vec())
+            } else {
+                None
+            }
+        })
+        .unwrap();
+
+        // The new `.shstrtab` is appended, holding the old names too.
+        let headers = SectionHeaders::parse(&bytes).unwrap();
+        let shstrtab = headers
+            .read_word(&bytes, headers.header(1).unwrap() + headers.sh_offset)
+            .unwrap();
+        assert_eq!(shstrtab, len);
+        let name = |bytes: &[u8], index| {
+            read_str(
+                bytes,
+                shstrtab + get_u32(bytes, headers.header(index).unwrap()).unwrap() as usize,
+            )
+            .unwrap()
+            .to_vec()
+        };
+        assert_eq!(name(&bytes, 1), b".shstrtab");
+        assert_eq!(name(&bytes, 2), b"m.symtab");
+        assert_eq!(name(&bytes, 3), b".strtab");
+    }
+}
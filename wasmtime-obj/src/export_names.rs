@@ -0,0 +1,124 @@
+//! The `.wasm.exports` section, mapping the object's symbols back to the
+//! names the module exports them under, for debuggers and profilers.
+//!
+//! All integers are little-endian `u32`s:
+//!
+//! ```text
+//! version         EXPORTS_SECTION_VERSION
+//! count           number of entries
+//! count times:
+//!   symbol_len    length of the symbol name in bytes
+//!   symbol        UTF-8 symbol name, such as `_wasm_function_3`
+//!   name_len      length of the export name in bytes
+//!   name          UTF-8 export name
+//! ```
+//!
+//! Entries are in the module's export order, and a symbol exported under
+//! several names has an entry for each. Only exported functions defined by
+//! the module and exported tables have symbols; other exports are omitted.
+//! The section is not loaded at runtime.
+
+use cranelift_entity::EntityRef;
+use faerie::{Artifact, Decl};
+use wasmtime_environ::{Export, Module};
+
+/// Name of the section mapping symbols to export names.
+pub const EXPORTS_SECTION: &str = ".wasm.exports";
+
+/// Version of the `.wasm.exports` section layout.
+pub const EXPORTS_SECTION_VERSION: u32 = 1;
+
+/// Returns the symbol and export name of each export that has a symbol.
+fn export_symbols(module: &Module) -> Vec<(String, &str)> {
+    module
+        .exports
+        .iter()
+        .filter_map(|(name, export)| {
+            let symbol = match *export {
+                Export::Function(func_index) => {
+                    module.defined_func_index(func_index)?;
+                    format!("_wasm_function_{}", func_index.index())
+                }
+                Export::Table(table_index) => format!("_table_{}", table_index.index()),
+                Export::Memory(_) | Export::Global(_) => return None,
+            };
+            Some((symbol, name.as_str()))
+        })
+        .collect()
+}
+
+/// Encodes the `.wasm.exports` section for `module`.
+pub fn encode_export_names(module: &Module) -> Vec<u8> {
+    fn push_str(out: &mut Vec<u8>, s: &str) {
+        out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    let symbols = export_symbols(module);
+    let mut out = Vec::new();
+    out.extend_from_slice(&EXPORTS_SECTION_VERSION.to_le_bytes());
+    out.extend_from_slice(&(symbols.len() as u32).to_le_bytes());
+    for (symbol, name) in symbols {
+        push_str(&mut out, &symbol);
+        push_str(&mut out, name);
+    }
+    out
+}
+
+/// Emits the `.wasm.exports` section, if any export has a symbol.
+pub fn emit_export_names(obj: &mut Artifact, module: &Module) -> Result<(), String> {
+    if export_symbols(module).is_empty() {
+        return Ok(());
+    }
+    obj.declare_with(
+        EXPORTS_SECTION,
+        Decl::debug_section(),
+        encode_export_names(module),
+    )
+    .map_err(|err| format!("{}", err))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::ir;
+    use cranelift_codegen::isa::CallConv;
+    use cranelift_wasm::{FuncIndex, MemoryIndex};
+
+    #[test]
+    fn test_encode_export_names() {
+        let mut module = Module::new();
+        let sig = module
+            .signatures
+            .push(ir::Signature::new(CallConv::SystemV));
+        module
+            .imported_funcs
+            .push((String::from("env"), String::from("f")));
+        module.functions.push(sig);
+        module.functions.push(sig);
+        module.exports.insert(
+            String::from("imported"),
+            Export::Function(FuncIndex::new(0)),
+        );
+        module
+            .exports
+            .insert(String::from("run"), Export::Function(FuncIndex::new(1)));
+        module
+            .exports
+            .insert(String::from("memory"), Export::Memory(MemoryIndex::new(0)));
+        module
+            .exports
+            .insert(String::from("main"), Export::Function(FuncIndex::new(1)));
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&[1, 0, 0, 0, 2, 0, 0, 0]);
+        for name in &["run", "main"] {
+            expected.extend_from_slice(&[16, 0, 0, 0]);
+            expected.extend_from_slice(b"_wasm_function_1");
+            expected.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            expected.extend_from_slice(name.as_bytes());
+        }
+        assert_eq!(encode_export_names(&module), expected);
+    }
+}
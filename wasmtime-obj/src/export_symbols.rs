@@ -0,0 +1,30 @@
+//! Global symbols named after the module's function exports, so that C code
+//! can call them by name.
+//!
+//! Each export symbol is an alias of the exported function's
+//! `_wasm_function_N` symbol, at the same address, added to the emitted ELF
+//! image as faerie defines a single symbol per function. Like the functions
+//! themselves, they take the vmctx as their first argument. Exports of
+//! imported functions get no symbol, as there is no code for them to name.
+
+use crate::elf::patch_elf_symbol_aliases;
+use cranelift_entity::EntityRef;
+use wasmtime_environ::{Export, Module};
+
+/// Adds a global symbol for each exported function defined by `module` to
+/// an ELF image emitted by faerie for it. Fails if an export has the name of
+/// a symbol of the object, or is of a function without code.
+pub fn patch_elf_export_symbols(bytes: &mut Vec<u8>, module: &Module) -> Result<(), String> {
+    let aliases = module
+        .exports
+        .iter()
+        .filter_map(|(name, export)| match *export {
+            Export::Function(func_index) if !module.is_imported_function(func_index) => Some((
+                format!("_wasm_function_{}", func_index.index()).into_bytes(),
+                name.clone().into_bytes(),
+            )),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    patch_elf_symbol_aliases(bytes, &aliases).map_err(|err| format!("export {}", err))
+}
@@ -0,0 +1,94 @@
+use cranelift_entity::EntityRef;
+use faerie::{Artifact, Decl};
+use wasmtime_environ::{Compilation, Module};
+
+/// Name of the data symbol holding the interpreter fallback table.
+pub const INTERPRETER_FALLBACK_SYMBOL: &str = "_interpreter_fallback";
+
+/// Encodes the functions that failed to compile as a table telling the
+/// runtime which functions to interpret, along with their original bodies.
+///
+/// All fields are little-endian `u32`s:
+///
+/// ```text
+/// count
+/// count times:
+///     func_index    index in the module's function index space
+///     length        number of wasm body bytes that follow
+///     body          `length` bytes, zero-padded to a multiple of 4
+/// ```
+///
+/// The `_wasm_function_N` symbols of these functions are left undefined;
+/// the runtime is expected to resolve them to interpreter entry points.
+pub fn encode_interpreter_fallback(module: &Module, compilation: &Compilation) -> Vec<u8> {
+    fn push_u32(out: &mut Vec<u8>, value: u32) {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut out = Vec::new();
+    push_u32(&mut out, compilation.failed.len() as u32);
+    for failed in &compilation.failed {
+        push_u32(&mut out, module.func_index(failed.index).index() as u32);
+        push_u32(&mut out, failed.wasm_body.len() as u32);
+        out.extend_from_slice(&failed.wasm_body);
+        while out.len() % 4 != 0 {
+            out.push(0);
+        }
+    }
+    out
+}
+
+/// Emits the interpreter fallback table as a global data symbol, if any
+/// function failed to compile.
+pub fn emit_interpreter_fallback(
+    obj: &mut Artifact,
+    module: &Module,
+    compilation: &Compilation,
+) -> Result<(), String> {
+    if compilation.failed.is_empty() {
+        return Ok(());
+    }
+    obj.declare_with(
+        INTERPRETER_FALLBACK_SYMBOL,
+        Decl::data().global(),
+        encode_interpreter_fallback(module, compilation),
+    )
+    .map_err(|err| format!("{}", err))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::CodegenError;
+    use cranelift_entity::PrimaryMap;
+    use cranelift_wasm::DefinedFuncIndex;
+    use wasmtime_environ::{CompileError, FailedFunction};
+
+    #[test]
+    fn test_encode_interpreter_fallback() {
+        let mut module = Module::new();
+        module
+            .imported_funcs
+            .push((String::from("env"), String::from("log")));
+        let mut functions = PrimaryMap::new();
+        functions.push(vec![0xc3]);
+        functions.push(Vec::new());
+        let mut compilation = Compilation::new(functions);
+        assert_eq!(
+            encode_interpreter_fallback(&module, &compilation),
+            [0, 0, 0, 0]
+        );
+
+        compilation.failed.push(FailedFunction {
+            index: DefinedFuncIndex::new(1),
+            error: CompileError::Codegen(CodegenError::ImplLimitExceeded),
+            wasm_body: vec![0x00, 0x41, 0x01, 0x1a, 0x0b],
+        });
+        let mut expected = vec![1, 0, 0, 0];
+        // The import comes first in the function index space.
+        expected.extend_from_slice(&[2, 0, 0, 0, 5, 0, 0, 0]);
+        expected.extend_from_slice(&[0x00, 0x41, 0x01, 0x1a, 0x0b, 0, 0, 0]);
+        assert_eq!(encode_interpreter_fallback(&module, &compilation), expected);
+    }
+}
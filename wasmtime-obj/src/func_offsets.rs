@@ -0,0 +1,127 @@
+//! The `.wasm.func_offsets` section, giving the code range of each defined
+//! function, so that a loader can build its function pointer table without
+//! parsing symbols.
+//!
+//! The header is two little-endian `u32`s, followed by one entry per
+//! defined function, indexed by `DefinedFuncIndex`. Entry fields are
+//! pointer-sized, and start 8-byte aligned within the section:
+//!
+//! ```text
+//! version       FUNC_OFFSETS_SECTION_VERSION
+//! count         number of defined functions
+//! count times:
+//!   start       relocated to the function's `_wasm_function_N` symbol
+//!   length      little-endian bytes of machine code
+//! ```
+//!
+//! Each function is in its own section in the object, so its place in
+//! `.text` is only known once the object is linked or loaded; `start`
+//! holds the function's address after relocation, and subtracting the base
+//! of the mapped code gives its offset. Functions that failed to compile
+//! have zero `start` and `length`, and are found through the interpreter
+//! fallback table instead. The section is not loaded at runtime.
+
+use cranelift_entity::EntityRef;
+use faerie::{Artifact, Decl, Link, Reloc};
+use wasmtime_environ::{Compilation, Module};
+
+/// Name of the section giving the code range of each defined function.
+pub const FUNC_OFFSETS_SECTION: &str = ".wasm.func_offsets";
+
+/// Version of the `.wasm.func_offsets` section layout.
+pub const FUNC_OFFSETS_SECTION_VERSION: u32 = 1;
+
+const HEADER_BYTES: usize = 8;
+
+/// Encodes the `.wasm.func_offsets` section for `compilation`, with every
+/// `start` left zero for its relocation.
+pub fn encode_func_offsets(compilation: &Compilation, pointer_bytes: u8) -> Vec<u8> {
+    let pointer_bytes = usize::from(pointer_bytes);
+    let mut out = Vec::new();
+    out.extend_from_slice(&FUNC_OFFSETS_SECTION_VERSION.to_le_bytes());
+    out.extend_from_slice(&(compilation.functions.len() as u32).to_le_bytes());
+    for (i, body) in compilation.functions.iter() {
+        let length = if compilation.failed.iter().any(|failed| failed.index == i) {
+            0
+        } else {
+            body.len() as u64
+        };
+        out.resize(out.len() + pointer_bytes, 0);
+        out.extend_from_slice(&length.to_le_bytes()[..pointer_bytes]);
+    }
+    out
+}
+
+/// Emits the `.wasm.func_offsets` section, if the module defines any
+/// functions.
+pub fn emit_func_offsets(
+    obj: &mut Artifact,
+    module: &Module,
+    compilation: &Compilation,
+    pointer_bytes: u8,
+) -> Result<(), String> {
+    if compilation.functions.is_empty() {
+        return Ok(());
+    }
+    obj.declare_with(
+        FUNC_OFFSETS_SECTION,
+        Decl::debug_section(),
+        encode_func_offsets(compilation, pointer_bytes),
+    )
+    .map_err(|err| format!("{}", err))?;
+
+    for (n, i) in compilation.functions.keys().enumerate() {
+        if compilation.failed.iter().any(|failed| failed.index == i) {
+            continue;
+        }
+        let target_name = format!("_wasm_function_{}", module.func_index(i).index());
+        obj.link_with(
+            Link {
+                from: FUNC_OFFSETS_SECTION,
+                to: &target_name,
+                at: (HEADER_BYTES + n * 2 * usize::from(pointer_bytes)) as u64,
+            },
+            Reloc::Debug {
+                size: pointer_bytes,
+                addend: 0,
+            },
+        )
+        .map_err(|err| format!("{}", err))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::CodegenError;
+    use cranelift_entity::PrimaryMap;
+    use cranelift_wasm::DefinedFuncIndex;
+    use wasmtime_environ::{CompileError, FailedFunction};
+
+    #[test]
+    fn test_encode_func_offsets() {
+        let mut functions = PrimaryMap::new();
+        functions.push(vec![0xc3]);
+        functions.push(Vec::new());
+        functions.push(vec![0x90; 300]);
+        let mut compilation = Compilation::new(functions);
+        compilation.failed.push(FailedFunction {
+            index: DefinedFuncIndex::new(1),
+            error: CompileError::Codegen(CodegenError::ImplLimitExceeded),
+            wasm_body: Vec::new(),
+        });
+
+        let mut expected = vec![1, 0, 0, 0, 3, 0, 0, 0];
+        expected.extend_from_slice(&[0; 8]);
+        expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(&[0; 16]);
+        expected.extend_from_slice(&[0; 8]);
+        expected.extend_from_slice(&[0x2c, 0x01, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(encode_func_offsets(&compilation, 8), expected);
+
+        let encoded = encode_func_offsets(&compilation, 4);
+        assert_eq!(encoded.len(), 8 + 3 * 8);
+        assert_eq!(&encoded[8 + 2 * 8..], &[0, 0, 0, 0, 0x2c, 0x01, 0, 0]);
+    }
+}
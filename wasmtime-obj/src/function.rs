@@ -1,30 +1,179 @@
+use crate::profile::{is_profiled, profile_thunk_name};
+use crate::reloc_addend::{reloc_addend, write_inline_addend, RelocAddend};
+use cranelift_codegen::binemit;
+use cranelift_codegen::ir::LibCall;
 use cranelift_codegen::settings;
 use cranelift_codegen::settings::Configurable;
 use cranelift_entity::EntityRef;
+use cranelift_wasm::{DefinedFuncIndex, MemoryIndex};
 use faerie::{Artifact, Decl, Link};
-use wasmtime_environ::{Compilation, Module, RelocationTarget, Relocations};
+use std::collections::HashSet;
+use target_lexicon::{Architecture, BinaryFormat};
+use wasmtime_environ::{Compilation, Module, Relocation, RelocationTarget, Relocations};
 
-/// Defines module functions
+fn is_failed(compilation: &Compilation, i: DefinedFuncIndex) -> bool {
+    compilation.failed.iter().any(|failed| failed.index == i)
+}
+
+/// Name of the data symbol the runtime provides as the signature table for
+/// `TableStyle::CallerChecksRelocatedSignature`.
+pub const SIGNATURE_TABLE_SYMBOL: &str = "_signature_table";
+
+/// Returns the name of the data symbol the runtime provides as the base of
+/// a memory whose plan has a `relocated_base`.
+pub fn memory_base_symbol(memory_index: MemoryIndex) -> String {
+    format!("_memory_base_{}", memory_index.index())
+}
+
+/// Returns the name of the symbol a libcall links against. The float
+/// rounding functions are provided by `wasmtime_runtime::libcalls`, or by
+/// any C shim exporting the same names; the memory functions are the C
+/// library's.
+pub fn libcall_symbol(libcall: LibCall) -> &'static str {
+    match libcall {
+        LibCall::Probestack => "__rust_probestack",
+        LibCall::CeilF32 => "wasmtime_f32_ceil",
+        LibCall::FloorF32 => "wasmtime_f32_floor",
+        LibCall::TruncF32 => "wasmtime_f32_trunc",
+        LibCall::NearestF32 => "wasmtime_f32_nearest",
+        LibCall::CeilF64 => "wasmtime_f64_ceil",
+        LibCall::FloorF64 => "wasmtime_f64_floor",
+        LibCall::TruncF64 => "wasmtime_f64_trunc",
+        LibCall::NearestF64 => "wasmtime_f64_nearest",
+        LibCall::Memcpy => "memcpy",
+        LibCall::Memset => "memset",
+        LibCall::Memmove => "memmove",
+    }
+}
+
+/// Returns the name of the symbol the runtime provides for a libcall or
+/// builtin relocation target, matching the functions in
+/// `wasmtime_runtime::libcalls`.
+pub fn runtime_symbol(target: RelocationTarget) -> Option<&'static str> {
+    Some(match target {
+        RelocationTarget::Memory32Grow => "wasmtime_memory32_grow",
+        RelocationTarget::ImportedMemory32Grow => "wasmtime_imported_memory32_grow",
+        RelocationTarget::Memory32Size => "wasmtime_memory32_size",
+        RelocationTarget::ImportedMemory32Size => "wasmtime_imported_memory32_size",
+        RelocationTarget::LibCall(libcall) => libcall_symbol(libcall),
+        RelocationTarget::UserFunc(_)
+        | RelocationTarget::SignatureTableBase
+        | RelocationTarget::MemoryBase { .. }
+        | RelocationTarget::Ebb(_)
+        | RelocationTarget::JumpTable(_) => return None,
+    })
+}
+
+/// The ELF relocation type for `reloc` on x86-64, where faerie can't infer
+/// it from the kinds of the symbols involved.
+fn elf_x86_64_reloc(reloc: binemit::Reloc) -> Option<u32> {
+    match reloc {
+        binemit::Reloc::Abs4 => Some(10),          // R_X86_64_32
+        binemit::Reloc::Abs8 => Some(1),           // R_X86_64_64
+        binemit::Reloc::X86PCRel4 => Some(2),      // R_X86_64_PC32
+        binemit::Reloc::X86CallPCRel4 => Some(2),  // R_X86_64_PC32
+        binemit::Reloc::X86CallPLTRel4 => Some(4), // R_X86_64_PLT32
+        binemit::Reloc::X86GOTPCRel4 => Some(9),   // R_X86_64_GOTPCREL
+        _ => None,
+    }
+}
+
+fn is_elf_x86_64(obj: &Artifact) -> bool {
+    obj.target.binary_format == BinaryFormat::Elf && obj.target.architecture == Architecture::X86_64
+}
+
+/// Links `from` to `to` with the relocation Cranelift asked for. Calls to
+/// symbols that aren't colocated are emitted as absolute addresses outside
+/// PIC, which faerie's default relocation for function imports gets wrong.
+pub fn link_reloc(
+    obj: &mut Artifact,
+    from: &str,
+    to: &str,
+    offset: binemit::CodeOffset,
+    reloc: binemit::Reloc,
+    addend: binemit::Addend,
+) -> Result<(), String> {
+    let link = Link {
+        from,
+        to,
+        at: u64::from(offset),
+    };
+    let addend = match reloc_addend(&obj.target, reloc, addend)? {
+        RelocAddend::Explicit(addend) => addend,
+        // Already written into the code by `emit_functions`.
+        RelocAddend::Inline(_) => return obj.link(link).map_err(|err| format!("{}", err)),
+    };
+    let raw = if is_elf_x86_64(obj) {
+        elf_x86_64_reloc(reloc)
+    } else {
+        None
+    };
+    match raw {
+        Some(raw) => obj.link_with(
+            link,
+            faerie::Reloc::Raw {
+                reloc: raw,
+                addend: addend as i32,
+            },
+        ),
+        None => obj.link(link),
+    }
+    .map_err(|err| format!("{}", err))
+}
+
+/// Links `name` to the code at `body_offset` within its own body, such as
+/// an EBB header or a jump table. PC-relative references within a body are
+/// resolved by Cranelift and never recorded, so these are absolute, and the
+/// address is the symbol's plus `body_offset`.
+fn link_within_body(
+    obj: &mut Artifact,
+    name: &str,
+    r: &Relocation,
+    body_offset: binemit::CodeOffset,
+) -> Result<(), String> {
+    // The offset can only be expressed as an addend, which needs a raw
+    // relocation.
+    if !is_elf_x86_64(obj) || elf_x86_64_reloc(r.reloc).is_none() {
+        return Err(format!(
+            "{} relocations within a function are not supported for {}",
+            r.reloc, obj.target
+        ));
+    }
+    let addend = r.addend + i64::from(body_offset);
+    link_reloc(obj, name, name, r.offset, r.reloc, addend)
+}
+
+/// Defines module functions. Functions that failed to compile are declared
+/// as imports, to be provided by the runtime's interpreter.
 pub fn declare_functions(
     obj: &mut Artifact,
     module: &Module,
+    compilation: &Compilation,
     relocations: &Relocations,
 ) -> Result<(), String> {
     for (i, _function_relocs) in relocations.iter().rev() {
         let func_index = module.func_index(i);
         let string_name = format!("_wasm_function_{}", func_index.index());
-        obj.declare(string_name, Decl::function().global())
+        let decl: Decl = if is_failed(compilation, i) {
+            Decl::function_import().into()
+        } else {
+            Decl::function().global().into()
+        };
+        obj.declare(string_name, decl)
             .map_err(|err| format!("{}", err))?;
     }
     Ok(())
 }
 
-/// Emits module functions
+/// Emits module functions. Calls to libcalls and builtins go to the symbols
+/// named by `runtime_symbol`, or through the counting thunks emitted by
+/// `emit_builtin_profiling` if `profile_builtins` is set.
 pub fn emit_functions(
     obj: &mut Artifact,
     module: &Module,
     compilation: &Compilation,
     relocations: &Relocations,
+    profile_builtins: bool,
 ) -> Result<(), String> {
     debug_assert!(
         module.start_func.is_none()
@@ -37,23 +186,69 @@ pub fn emit_functions(
         .enable("enable_verifier")
         .expect("Missing enable_verifier setting");
 
-    for (i, _function_relocs) in relocations.iter() {
-        let body = &compilation.functions[i];
+    for (i, function_relocs) in relocations.iter() {
+        if is_failed(compilation, i) {
+            continue;
+        }
+        let mut body = compilation.functions[i].clone();
         let func_index = module.func_index(i);
         let string_name = format!("_wasm_function_{}", func_index.index());
 
-        obj.define(string_name, body.clone())
+        // Formats without explicit addends take them from the code, for the
+        // relocations `link_reloc` emits below.
+        for r in function_relocs {
+            if runtime_symbol(r.reloc_target).is_none() {
+                continue;
+            }
+            if let RelocAddend::Inline(addend) = reloc_addend(&obj.target, r.reloc, r.addend)? {
+                write_inline_addend(&mut body, r.offset, r.reloc, addend);
+            }
+        }
+
+        obj.define(string_name, body)
             .map_err(|err| format!("{}", err))?;
     }
 
+    let mut imports = HashSet::new();
     for (i, function_relocs) in relocations.iter() {
         let func_index = module.func_index(i);
         let string_name = format!("_wasm_function_{}", func_index.index());
         for r in function_relocs {
-            debug_assert_eq!(r.addend, 0);
             match r.reloc_target {
                 RelocationTarget::UserFunc(target_index) => {
                     let target_name = format!("_wasm_function_{}", target_index.index());
+                    if r.addend == 0 {
+                        obj.link(Link {
+                            from: &string_name,
+                            to: &target_name,
+                            at: r.offset as u64,
+                        })
+                        .map_err(|err| format!("{}", err))?;
+                    } else {
+                        // The jump of a thunk from `thunk_duplicate_functions`.
+                        link_reloc(obj, &string_name, &target_name, r.offset, r.reloc, r.addend)?;
+                    }
+                }
+                RelocationTarget::SignatureTableBase => {
+                    debug_assert_eq!(r.addend, 0);
+                    if imports.insert(String::from(SIGNATURE_TABLE_SYMBOL)) {
+                        obj.declare(SIGNATURE_TABLE_SYMBOL, Decl::data_import())
+                            .map_err(|err| format!("{}", err))?;
+                    }
+                    obj.link(Link {
+                        from: &string_name,
+                        to: SIGNATURE_TABLE_SYMBOL,
+                        at: r.offset as u64,
+                    })
+                    .map_err(|err| format!("{}", err))?;
+                }
+                RelocationTarget::MemoryBase { memory_index } => {
+                    debug_assert_eq!(r.addend, 0);
+                    let target_name = memory_base_symbol(memory_index);
+                    if imports.insert(target_name.clone()) {
+                        obj.declare(&target_name, Decl::data_import())
+                            .map_err(|err| format!("{}", err))?;
+                    }
                     obj.link(Link {
                         from: &string_name,
                         to: &target_name,
@@ -61,10 +256,79 @@ pub fn emit_functions(
                     })
                     .map_err(|err| format!("{}", err))?;
                 }
-                _ => panic!("relocations target not supported yet"),
+                RelocationTarget::Ebb(body_offset) => {
+                    link_within_body(obj, &string_name, r, body_offset)?;
+                }
+                RelocationTarget::JumpTable(jt) => {
+                    link_within_body(obj, &string_name, r, compilation.jt_offsets[i][jt])?;
+                }
+                target => {
+                    let symbol = runtime_symbol(target).ok_or_else(|| {
+                        format!("relocation target {:?} is not supported yet", target)
+                    })?;
+                    let target_name = if profile_builtins && is_profiled(target) {
+                        profile_thunk_name(symbol)
+                    } else {
+                        if imports.insert(String::from(symbol)) {
+                            obj.declare(symbol, Decl::function_import())
+                                .map_err(|err| format!("{}", err))?;
+                        }
+                        String::from(symbol)
+                    };
+                    link_reloc(obj, &string_name, &target_name, r.offset, r.reloc, r.addend)?;
+                }
             };
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_libcall_symbol_total() {
+        // No catch-all: a libcall added to Cranelift fails to compile here
+        // until it is given a symbol.
+        let all = |libcall| match libcall {
+            LibCall::Probestack
+            | LibCall::CeilF32
+            | LibCall::FloorF32
+            | LibCall::TruncF32
+            | LibCall::NearestF32
+            | LibCall::CeilF64
+            | LibCall::FloorF64
+            | LibCall::TruncF64
+            | LibCall::NearestF64
+            | LibCall::Memcpy
+            | LibCall::Memset
+            | LibCall::Memmove => libcall_symbol(libcall),
+        };
+        let libcalls = [
+            LibCall::Probestack,
+            LibCall::CeilF32,
+            LibCall::FloorF32,
+            LibCall::TruncF32,
+            LibCall::NearestF32,
+            LibCall::CeilF64,
+            LibCall::FloorF64,
+            LibCall::TruncF64,
+            LibCall::NearestF64,
+            LibCall::Memcpy,
+            LibCall::Memset,
+            LibCall::Memmove,
+        ];
+        let symbols = libcalls
+            .iter()
+            .map(|&libcall| all(libcall))
+            .collect::<HashSet<_>>();
+        assert_eq!(symbols.len(), libcalls.len());
+        assert!(symbols.iter().all(|symbol| !symbol.is_empty()));
+        assert_eq!(
+            runtime_symbol(RelocationTarget::LibCall(LibCall::CeilF32)),
+            Some("wasmtime_f32_ceil")
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use crate::elf::{patch_elf_section_headers, SectionPatch, SHF_ALLOC, SHF_WRITE};
+use crate::function::link_reloc;
+use cranelift_codegen::binemit;
+use cranelift_codegen::ir;
+use cranelift_codegen::ir::InstBuilder;
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::Context;
+use cranelift_entity::EntityRef;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_wasm::FuncIndex;
+use faerie::{Artifact, Decl, Link};
+use target_lexicon::BinaryFormat;
+use wasmtime_environ::{Export, Module};
+
+/// Name of the ELF section holding constructor pointers.
+pub const INIT_ARRAY_SECTION: &str = ".init_array";
+
+/// `SHT_INIT_ARRAY` section type.
+const SHT_INIT_ARRAY: u32 = 14;
+
+/// External name namespace of the callee in the trampoline.
+const CALLEE_NAMESPACE: u32 = 0;
+
+/// External name namespace of the vmctx in the trampoline.
+const VMCTX_NAMESPACE: u32 = 1;
+
+/// A relocation in the trampoline against the symbol `target`.
+struct TrampolineReloc {
+    offset: binemit::CodeOffset,
+    reloc: binemit::Reloc,
+    addend: binemit::Addend,
+    target: String,
+}
+
+/// Collects the relocations of the trampoline, which only refers to the
+/// callee and to `_vmcontext_init`.
+struct RelocSink {
+    relocs: Vec<TrampolineReloc>,
+}
+
+impl binemit::RelocSink for RelocSink {
+    fn reloc_ebb(&mut self, _: binemit::CodeOffset, _: binemit::Reloc, _: binemit::CodeOffset) {
+        panic!("trampoline compilation should not produce ebb relocs");
+    }
+    fn reloc_external(
+        &mut self,
+        offset: binemit::CodeOffset,
+        reloc: binemit::Reloc,
+        name: &ir::ExternalName,
+        addend: binemit::Addend,
+    ) {
+        let target = match *name {
+            ir::ExternalName::User {
+                namespace: CALLEE_NAMESPACE,
+                index,
+            } => format!("_wasm_function_{}", index),
+            ir::ExternalName::User {
+                namespace: VMCTX_NAMESPACE,
+                ..
+            } => String::from("_vmcontext_init"),
+            _ => panic!("unexpected trampoline relocation target {}", name),
+        };
+        self.relocs.push(TrampolineReloc {
+            offset,
+            reloc,
+            addend,
+            target,
+        });
+    }
+    fn reloc_jt(&mut self, _: binemit::CodeOffset, _: binemit::Reloc, _: ir::JumpTable) {
+        panic!("trampoline compilation should not produce jump table relocs");
+    }
+}
+
+/// Compiles a function taking no arguments, as called from `.init_array`,
+/// that calls `func_index` with `_vmcontext_init` as its vmctx.
+fn make_init_trampoline(
+    isa: &dyn TargetIsa,
+    module: &Module,
+    func_index: FuncIndex,
+) -> Result<(Vec<u8>, Vec<TrampolineReloc>), String> {
+    let pointer_type = isa.pointer_type();
+    let signature = &module.signatures[module.functions[func_index]];
+
+    let mut context = Context::new();
+    context.func = ir::Function::with_name_signature(
+        ir::ExternalName::user(0, 0),
+        ir::Signature::new(isa.frontend_config().default_call_conv),
+    );
+
+    {
+        let mut fn_builder_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut context.func, &mut fn_builder_ctx);
+        let block0 = builder.create_ebb();
+        builder.switch_to_block(block0);
+        builder.seal_block(block0);
+
+        let vmctx = builder.create_global_value(ir::GlobalValueData::Symbol {
+            name: ir::ExternalName::user(VMCTX_NAMESPACE, 0),
+            offset: ir::immediates::Imm64::new(0),
+            colocated: true,
+        });
+        let vmctx_ptr_val = builder.ins().global_value(pointer_type, vmctx);
+
+        let callee_sig = builder.import_signature(signature.clone());
+        let callee = builder.import_function(ir::ExtFuncData {
+            name: ir::ExternalName::user(CALLEE_NAMESPACE, func_index.index() as u32),
+            signature: callee_sig,
+            colocated: true,
+        });
+        builder.ins().call(callee, &[vmctx_ptr_val]);
+        builder.ins().return_(&[]);
+        builder.finalize()
+    }
+
+    let mut code_buf: Vec<u8> = Vec::new();
+    let mut reloc_sink = RelocSink { relocs: Vec::new() };
+    let mut trap_sink = binemit::NullTrapSink {};
+    context
+        .compile_and_emit(isa, &mut code_buf, &mut reloc_sink, &mut trap_sink)
+        .map_err(|error| format!("{}", error))?;
+    Ok((code_buf, reloc_sink.relocs))
+}
+
+/// Emits an `.init_array` entry that runs the exported function `export` at
+/// load time, through a trampoline named `_wasm_init_trampoline_N`.
+///
+/// The function is called with the object's `_vmcontext_init` as its vmctx,
+/// so it may only use state that is valid before the runtime sets anything
+/// up, and `_vmcontext_init` must be declared writable for it to set
+/// globals. It must take no parameters and return nothing.
+///
+/// faerie writes the section as non-allocated debug data;
+/// `patch_elf_init_array` must be run on the emitted bytes.
+pub fn emit_init_array(
+    obj: &mut Artifact,
+    module: &Module,
+    isa: &dyn TargetIsa,
+    export: &str,
+) -> Result<(), String> {
+    if obj.target.binary_format != BinaryFormat::Elf {
+        return Err(format!(
+            "init_array entries are only supported for ELF output, not {}",
+            obj.target.binary_format
+        ));
+    }
+    let func_index = match module.exports.get(export) {
+        Some(Export::Function(func_index)) => *func_index,
+        Some(_) => return Err(format!("export '{}' is not a function", export)),
+        None => return Err(format!("no export named '{}'", export)),
+    };
+    let signature = &module.signatures[module.functions[func_index]];
+    if signature
+        .params
+        .iter()
+        .any(|param| param.purpose != ir::ArgumentPurpose::VMContext)
+        || !signature.returns.is_empty()
+    {
+        return Err(format!(
+            "export '{}' must take no parameters and return nothing to run at load time",
+            export
+        ));
+    }
+
+    let (body, relocs) = make_init_trampoline(isa, module, func_index)?;
+    let trampoline_name = format!("_wasm_init_trampoline_{}", func_index.index());
+    obj.declare_with(&trampoline_name, Decl::function(), body)
+        .map_err(|err| format!("{}", err))?;
+    for r in relocs {
+        link_reloc(
+            obj,
+            &trampoline_name,
+            &r.target,
+            r.offset,
+            r.reloc,
+            r.addend,
+        )?;
+    }
+
+    let pointer_bytes = isa.pointer_bytes();
+    obj.declare_with(
+        INIT_ARRAY_SECTION,
+        Decl::debug_section(),
+        vec![0; pointer_bytes as usize],
+    )
+    .map_err(|err| format!("{}", err))?;
+    obj.link_with(
+        Link {
+            from: INIT_ARRAY_SECTION,
+            to: &trampoline_name,
+            at: 0,
+        },
+        faerie::Reloc::Debug {
+            size: pointer_bytes,
+            addend: 0,
+        },
+    )
+    .map_err(|err| format!("{}", err))?;
+    Ok(())
+}
+
+/// Rewrites the header of the `.init_array` section in a little-endian ELF
+/// image emitted by faerie so that it is an allocated `SHT_INIT_ARRAY`.
+pub fn patch_elf_init_array(bytes: &mut [u8], pointer_bytes: u8) -> Result<(), String> {
+    patch_elf_section_headers(bytes, |name| {
+        if name == INIT_ARRAY_SECTION.as_bytes() {
+            Some(SectionPatch {
+                sh_type: SHT_INIT_ARRAY,
+                sh_flags: SHF_ALLOC | SHF_WRITE,
+                sh_addralign: u64::from(pointer_bytes),
+            })
+        } else {
+            None
+        }
+    })
+}
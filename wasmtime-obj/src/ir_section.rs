@@ -0,0 +1,198 @@
+//! The `.clif` section, holding the textual Cranelift IR of each function
+//! so that a crash address can be traced back to the IR that produced it.
+//!
+//! All integers are little-endian `u32`s. The section starts with a header:
+//!
+//! ```text
+//! magic           b"CLIF"
+//! version         CLIF_SECTION_VERSION
+//! compression     0 = none, 1 = zlib
+//! payload_len     length of the payload once decompressed
+//! ```
+//!
+//! followed by the (possibly compressed) payload:
+//!
+//! ```text
+//! count           number of functions
+//! count times:
+//!   func_index    index of the function in the module's function index space
+//!   text_len      length of the text in bytes
+//!   text          UTF-8 IR, as printed by `Function::display`
+//! ```
+//!
+//! The text is typically an order of magnitude larger than the machine code
+//! it describes; zlib usually shrinks it by a factor of five to ten. The
+//! section is not loaded at runtime.
+
+use cranelift_entity::EntityRef;
+use faerie::{Artifact, Decl};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+use std::str::FromStr;
+use wasmtime_environ::{Compilation, Module};
+
+/// Name of the section holding the embedded IR.
+pub const CLIF_SECTION: &str = ".clif";
+
+/// Version of the `.clif` section layout.
+pub const CLIF_SECTION_VERSION: u32 = 1;
+
+/// How the payload of the `.clif` section is compressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IrCompression {
+    /// The payload is stored as-is.
+    None,
+    /// The payload is a zlib stream.
+    Zlib,
+}
+
+impl FromStr for IrCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(IrCompression::None),
+            "zlib" => Ok(IrCompression::Zlib),
+            _ => Err(format!(
+                "unknown IR compression '{}'; expected none or zlib",
+                s
+            )),
+        }
+    }
+}
+
+fn encode_ir_payload(module: &Module, compilation: &Compilation) -> Vec<u8> {
+    let mut payload = Vec::new();
+    let ir = match compilation.ir {
+        Some(ref ir) => ir,
+        None => return payload,
+    };
+    payload.extend_from_slice(&(ir.len() as u32).to_le_bytes());
+    for (i, text) in ir.iter() {
+        payload.extend_from_slice(&(module.func_index(i).index() as u32).to_le_bytes());
+        payload.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        payload.extend_from_slice(text.as_bytes());
+    }
+    payload
+}
+
+/// Encodes the `.clif` section from the IR captured in `compilation`.
+fn encode_ir_section(
+    module: &Module,
+    compilation: &Compilation,
+    compression: IrCompression,
+) -> Result<Vec<u8>, String> {
+    if compilation.ir.is_none() {
+        return Err(String::from(
+            "embedding IR requires compiling with IR capture enabled",
+        ));
+    }
+    let payload = encode_ir_payload(module, compilation);
+
+    let mut section = Vec::new();
+    section.extend_from_slice(b"CLIF");
+    section.extend_from_slice(&CLIF_SECTION_VERSION.to_le_bytes());
+    match compression {
+        IrCompression::None => {
+            section.extend_from_slice(&0u32.to_le_bytes());
+            section.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            section.extend_from_slice(&payload);
+        }
+        IrCompression::Zlib => {
+            section.extend_from_slice(&1u32.to_le_bytes());
+            section.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            let mut encoder = ZlibEncoder::new(section, Compression::default());
+            encoder.write_all(&payload).map_err(|e| e.to_string())?;
+            section = encoder.finish().map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(section)
+}
+
+/// Emits the `.clif` section from the IR captured in `compilation`.
+pub fn emit_ir_section(
+    obj: &mut Artifact,
+    module: &Module,
+    compilation: &Compilation,
+    compression: IrCompression,
+) -> Result<(), String> {
+    let section = encode_ir_section(module, compilation, compression)?;
+    obj.declare_with(CLIF_SECTION, Decl::debug_section(), section)
+        .map_err(|err| format!("{}", err))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_entity::PrimaryMap;
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    fn read_u32(bytes: &[u8], at: usize) -> u32 {
+        let mut word = [0; 4];
+        word.copy_from_slice(&bytes[at..at + 4]);
+        u32::from_le_bytes(word)
+    }
+
+    /// A module with one import and two defined functions, and their IR.
+    fn module_and_compilation() -> (Module, Compilation) {
+        let mut module = Module::new();
+        module
+            .imported_funcs
+            .push((String::from("env"), String::from("log")));
+        let mut functions = PrimaryMap::new();
+        functions.push(vec![0xc3]);
+        functions.push(vec![0xc3]);
+        let mut compilation = Compilation::new(functions);
+        let mut ir = PrimaryMap::new();
+        ir.push(String::from("function u0:1() {}"));
+        ir.push(String::from("function u0:2() {}"));
+        compilation.ir = Some(ir);
+        (module, compilation)
+    }
+
+    fn check_payload(payload: &[u8]) {
+        assert_eq!(read_u32(payload, 0), 2);
+        let mut at = 4;
+        for (func_index, text) in &[(1, "function u0:1() {}"), (2, "function u0:2() {}")] {
+            assert_eq!(read_u32(payload, at), *func_index);
+            let len = read_u32(payload, at + 4) as usize;
+            assert_eq!(&payload[at + 8..at + 8 + len], text.as_bytes());
+            at += 8 + len;
+        }
+        assert_eq!(at, payload.len());
+    }
+
+    #[test]
+    fn test_encode_ir_section() {
+        let (module, compilation) = module_and_compilation();
+        let section = encode_ir_section(&module, &compilation, IrCompression::None).unwrap();
+        assert_eq!(&section[0..4], b"CLIF");
+        assert_eq!(read_u32(&section, 4), CLIF_SECTION_VERSION);
+        assert_eq!(read_u32(&section, 8), 0);
+        assert_eq!(read_u32(&section, 12) as usize, section.len() - 16);
+        check_payload(&section[16..]);
+    }
+
+    #[test]
+    fn test_encode_ir_section_zlib() {
+        let (module, compilation) = module_and_compilation();
+        let section = encode_ir_section(&module, &compilation, IrCompression::Zlib).unwrap();
+        assert_eq!(read_u32(&section, 8), 1);
+        let mut payload = Vec::new();
+        ZlibDecoder::new(&section[16..])
+            .read_to_end(&mut payload)
+            .unwrap();
+        assert_eq!(read_u32(&section, 12) as usize, payload.len());
+        check_payload(&payload);
+    }
+
+    #[test]
+    fn test_encode_ir_section_without_ir() {
+        let (module, mut compilation) = module_and_compilation();
+        compilation.ir = None;
+        assert!(encode_ir_section(&module, &compilation, IrCompression::None).is_err());
+    }
+}
@@ -26,13 +26,61 @@
     )
 )]
 
+mod build_id;
+mod comment;
 mod context;
 mod data_segment;
+mod duplicates;
+mod elf;
+mod export_names;
+mod export_symbols;
+mod fallback;
+mod func_offsets;
 mod function;
+mod init_array;
+mod ir_section;
+mod macho;
 mod module;
+mod patchable;
+mod profile;
+mod reloc_addend;
+mod section_prefix;
 mod table;
+mod tunables;
+mod visibility;
 
-pub use crate::module::emit_module;
+pub use crate::build_id::{compute_build_id, patch_elf_note_sections, BuildId, BUILD_ID_SECTION};
+pub use crate::comment::{emit_comment, producer_note, COMMENT_SECTION};
+pub use crate::data_segment::{
+    encode_data_initializers, patch_elf_data_sections, DATA_INITIALIZERS_SYMBOL,
+    DATA_INITIALIZER_HAS_BASE, DATA_INITIALIZER_PASSIVE,
+};
+pub use crate::duplicates::thunk_duplicate_functions;
+pub use crate::export_names::{encode_export_names, EXPORTS_SECTION, EXPORTS_SECTION_VERSION};
+pub use crate::export_symbols::patch_elf_export_symbols;
+pub use crate::fallback::{encode_interpreter_fallback, INTERPRETER_FALLBACK_SYMBOL};
+pub use crate::func_offsets::{
+    encode_func_offsets, FUNC_OFFSETS_SECTION, FUNC_OFFSETS_SECTION_VERSION,
+};
+pub use crate::function::{
+    libcall_symbol, memory_base_symbol, runtime_symbol, SIGNATURE_TABLE_SYMBOL,
+};
+pub use crate::init_array::{emit_init_array, patch_elf_init_array, INIT_ARRAY_SECTION};
+pub use crate::ir_section::{IrCompression, CLIF_SECTION, CLIF_SECTION_VERSION};
+pub use crate::module::{emit_module, EmitOptions, START_FUNC_SYMBOL};
+pub use crate::patchable::{pad_function_entries, PatchableEntry, PATCHABLE_ENTRIES_SYMBOL};
+pub use crate::profile::{
+    emit_builtin_profiling, BUILTIN_PROFILE_COUNTERS_SYMBOL, BUILTIN_PROFILE_NAMES_SYMBOL,
+};
+pub use crate::reloc_addend::{reloc_addend, write_inline_addend, RelocAddend};
+pub use crate::section_prefix::{
+    check_section_prefix, patch_elf_section_prefix, prefixed_section_name,
+};
+pub use crate::tunables::{
+    check_tunables, decode_tunables, emit_tunables, encode_tunables, TUNABLES_SECTION,
+    TUNABLES_SECTION_VERSION,
+};
+pub use crate::visibility::{patch_elf_visibility, Visibility};
 
 /// Version number of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
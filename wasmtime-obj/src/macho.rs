@@ -0,0 +1,53 @@
+//! Limits of Mach-O objects that faerie doesn't check before writing them.
+
+use faerie::Artifact;
+use target_lexicon::BinaryFormat;
+
+/// The most bytes of a Mach-O section name.
+const SECTNAME_BYTES: usize = 16;
+
+/// Checks that the debug section `name` can be declared in `obj`. faerie
+/// writes a debug section `.name` to a Mach-O object as `__name` in the
+/// `__DWARF` segment, and panics if that doesn't fit in the 16 bytes of a
+/// section name.
+pub fn check_debug_section_name(obj: &Artifact, name: &str) -> Result<(), String> {
+    if obj.target.binary_format != BinaryFormat::Macho {
+        return Ok(());
+    }
+    let sectname_len = if name.starts_with('.') {
+        name.len() + 1
+    } else {
+        name.len()
+    };
+    if sectname_len > SECTNAME_BYTES {
+        return Err(format!(
+            "the {} section's name is too long for a Mach-O object",
+            name
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use target_lexicon::Triple;
+
+    #[test]
+    fn test_check_debug_section_name() {
+        let macho = Artifact::new(
+            Triple::from_str("x86_64-apple-darwin").unwrap(),
+            String::from("wasm.o"),
+        );
+        assert!(check_debug_section_name(&macho, ".comment").is_ok());
+        assert!(check_debug_section_name(&macho, ".fifteen_bytes_").is_ok());
+        assert!(check_debug_section_name(&macho, ".sixteen_bytes__").is_err());
+
+        let elf = Artifact::new(
+            Triple::from_str("x86_64-unknown-linux-gnu").unwrap(),
+            String::from("wasm.o"),
+        );
+        assert!(check_debug_section_name(&elf, ".sixteen_bytes__").is_ok());
+    }
+}
@@ -1,19 +1,35 @@
+use crate::build_id::{compute_build_id, emit_build_id, BuildId};
 use crate::context::layout_vmcontext;
-use crate::data_segment::{declare_data_segment, emit_data_segment};
+use crate::data_segment::{
+    declare_data_segment, emit_data_initializers_table, emit_data_segment,
+    emit_data_segment_in_section,
+};
+use crate::export_names::emit_export_names;
+use crate::fallback::emit_interpreter_fallback;
+use crate::func_offsets::emit_func_offsets;
 use crate::function::{declare_functions, emit_functions};
+use crate::ir_section::{emit_ir_section, IrCompression};
+use crate::patchable::{emit_patchable_entries, PatchableEntry};
 use crate::table::{declare_table, emit_table};
 use cranelift_codegen::isa::TargetFrontendConfig;
+use cranelift_entity::EntityRef;
 use faerie::{Artifact, Decl, Link};
+use std::collections::HashMap;
 use wasmtime_environ::{Compilation, DataInitializer, Module, Relocations};
 
 fn emit_vmcontext_init(
     obj: &mut Artifact,
     module: &Module,
     target_config: &TargetFrontendConfig,
+    writable: bool,
 ) -> Result<(), String> {
     let (data, table_relocs) = layout_vmcontext(module, target_config);
-    obj.declare_with("_vmcontext_init", Decl::data().global(), data.to_vec())
-        .map_err(|err| format!("{}", err))?;
+    obj.declare_with(
+        "_vmcontext_init",
+        Decl::data().global().with_writable(writable),
+        data.to_vec(),
+    )
+    .map_err(|err| format!("{}", err))?;
     for reloc in table_relocs.iter() {
         let target_name = format!("_table_{}", reloc.index);
         obj.link(Link {
@@ -26,6 +42,77 @@ fn emit_vmcontext_init(
     Ok(())
 }
 
+/// Name of the data symbol pointing at the module's start function.
+pub const START_FUNC_SYMBOL: &str = "_start_func";
+
+/// For modules with a start function, emits a pointer-sized `_start_func`
+/// slot relocated to the start function's body, so that a loader knows what
+/// to call after instantiation. The symbol is absent otherwise.
+fn emit_start_func(
+    obj: &mut Artifact,
+    module: &Module,
+    target_config: &TargetFrontendConfig,
+) -> Result<(), String> {
+    let start_func = match module.start_func {
+        Some(start_func) => start_func,
+        None => return Ok(()),
+    };
+    let slot = vec![0; target_config.pointer_bytes() as usize];
+    obj.declare_with(START_FUNC_SYMBOL, Decl::data().global(), slot)
+        .map_err(|err| format!("{}", err))?;
+    let target_name = format!("_wasm_function_{}", start_func.index());
+    obj.link(Link {
+        from: START_FUNC_SYMBOL,
+        to: &target_name,
+        at: 0,
+    })
+    .map_err(|err| format!("{}", err))?;
+    Ok(())
+}
+
+/// Options controlling what `emit_module` writes to the object file.
+#[derive(Clone, Debug, Default)]
+pub struct EmitOptions {
+    /// Emit the data initializers as a table for the runtime to apply at
+    /// instantiation, rather than as one data symbol per segment.
+    pub defer_data_init: bool,
+
+    /// How to compute the `.note.gnu.build-id` of ELF objects, if at all.
+    pub build_id: BuildId,
+
+    /// Description of the compilation settings, hashed into the build-id so
+    /// that the same code compiled differently gets a different id.
+    pub build_id_settings: String,
+
+    /// Embed the IR captured in the compilation as a `.clif` section,
+    /// compressed as given.
+    pub embed_ir: Option<IrCompression>,
+
+    /// Sections to place data segments in, by segment index, instead of
+    /// `.data`. Each section may hold only one segment.
+    pub data_sections: HashMap<usize, String>,
+
+    /// Call libcalls and builtins through the counting thunks emitted by
+    /// `emit_builtin_profiling`, which must have been called first.
+    pub profile_builtins: bool,
+
+    /// Functions padded by `pad_function_entries`, whose patch points are
+    /// recorded in `_patchable_function_entries`.
+    pub patchable_entry: PatchableEntry,
+
+    /// Emit the `.wasm.func_offsets` section giving the code range of each
+    /// defined function.
+    pub func_offsets: bool,
+
+    /// Emit the `.wasm.exports` section mapping symbols to the names they
+    /// are exported as.
+    pub export_names: bool,
+
+    /// Make `_vmcontext_init` writable, for code that runs with it as its
+    /// vmctx before the runtime copies it, such as an `.init_array` entry.
+    pub writable_vmcontext: bool,
+}
+
 /// Emits a module that has been emitted with the `wasmtime-environ` environment
 /// implementation to a native object file.
 pub fn emit_module(
@@ -35,28 +122,96 @@ pub fn emit_module(
     relocations: &Relocations,
     data_initializers: &[DataInitializer],
     target_config: &TargetFrontendConfig,
+    options: &EmitOptions,
 ) -> Result<(), String> {
-    declare_functions(obj, module, relocations)?;
+    if options.defer_data_init && !options.data_sections.is_empty() {
+        return Err(String::from(
+            "deferred data initializers can't be placed in custom sections",
+        ));
+    }
+    for (&i, section) in options.data_sections.iter() {
+        if i >= data_initializers.len() {
+            return Err(format!("no data segment {} to place in {}", i, section));
+        }
+        if options
+            .data_sections
+            .iter()
+            .any(|(&j, other)| j != i && other == section)
+        {
+            return Err(format!("more than one data segment placed in {}", section));
+        }
+    }
 
-    for i in 0..data_initializers.len() {
-        declare_data_segment(obj, &data_initializers[i], i)?;
+    declare_functions(obj, module, compilation, relocations)?;
+
+    if !options.defer_data_init {
+        for i in 0..data_initializers.len() {
+            if !options.data_sections.contains_key(&i) {
+                declare_data_segment(obj, &data_initializers[i], i)?;
+            }
+        }
     }
 
     for i in 0..module.table_plans.len() {
         declare_table(obj, i)?;
     }
 
-    emit_functions(obj, module, compilation, relocations)?;
+    emit_functions(
+        obj,
+        module,
+        compilation,
+        relocations,
+        options.profile_builtins,
+    )?;
+
+    emit_interpreter_fallback(obj, module, compilation)?;
 
-    for i in 0..data_initializers.len() {
-        emit_data_segment(obj, &data_initializers[i], i)?;
+    if options.defer_data_init {
+        emit_data_initializers_table(obj, data_initializers)?;
+    } else {
+        for i in 0..data_initializers.len() {
+            match options.data_sections.get(&i) {
+                Some(section) => emit_data_segment_in_section(obj, &data_initializers[i], section)?,
+                None => emit_data_segment(obj, &data_initializers[i], i)?,
+            }
+        }
     }
 
     for i in 0..module.table_plans.len() {
         emit_table(obj, i)?;
     }
 
-    emit_vmcontext_init(obj, module, target_config)?;
+    emit_vmcontext_init(obj, module, target_config, options.writable_vmcontext)?;
+
+    emit_start_func(obj, module, target_config)?;
+
+    emit_patchable_entries(
+        obj,
+        module,
+        &options.patchable_entry,
+        target_config.pointer_bytes(),
+    )?;
+
+    if let Some(build_id) = compute_build_id(
+        options.build_id,
+        compilation,
+        relocations,
+        &options.build_id_settings,
+    ) {
+        emit_build_id(obj, &build_id)?;
+    }
+
+    if options.func_offsets {
+        emit_func_offsets(obj, module, compilation, target_config.pointer_bytes())?;
+    }
+
+    if options.export_names {
+        emit_export_names(obj, module)?;
+    }
+
+    if let Some(compression) = options.embed_ir {
+        emit_ir_section(obj, module, compilation, compression)?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,236 @@
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_entity::{EntityRef, PrimaryMap};
+use cranelift_wasm::DefinedFuncIndex;
+use faerie::{Artifact, Decl, Link};
+use wasmtime_environ::{Compilation, Module, RelocationTarget, Relocations, TrapInformation};
+
+/// Name of the data symbol recording the patch points of functions with
+/// patchable entries.
+pub const PATCHABLE_ENTRIES_SYMBOL: &str = "_patchable_function_entries";
+
+/// Functions to emit with nop padding at their entry, like
+/// `-fpatchable-function-entry`, so that a runtime can redirect calls to
+/// them by overwriting the padding.
+///
+/// The padding is the first thing each function executes, at its symbol, so
+/// the patch point has the function's own alignment. To patch it safely
+/// while other threads may be calling the function, the runtime must either
+/// stop them or replace the padding with a single atomic store that doesn't
+/// cross a cache line, such as a two-byte short jump over a longer sequence
+/// written beforehand. On targets without coherent instruction caches the
+/// patched range must also be flushed.
+#[derive(Clone, Debug, Default)]
+pub struct PatchableEntry {
+    /// Bytes of nop padding at each entry.
+    pub size: usize,
+    /// The functions to pad.
+    pub functions: Vec<DefinedFuncIndex>,
+}
+
+/// The nop instruction of the target, which the padding is made of.
+fn nop(isa: &dyn TargetIsa) -> Result<&'static [u8], String> {
+    match isa.name() {
+        "x86" => Ok(&[0x90]),
+        "arm64" => Ok(&[0x1f, 0x20, 0x03, 0xd5]),
+        "riscv" => Ok(&[0x13, 0x00, 0x00, 0x00]),
+        name => Err(format!("patchable entries are not supported for {}", name)),
+    }
+}
+
+/// Inserts the padding at the start of the bodies of the functions in
+/// `entry`, moving their relocations and trap sites to match. Returns each
+/// function's new body offset, for `rebase_transforms`.
+pub fn pad_function_entries(
+    compilation: &mut Compilation,
+    relocations: &mut Relocations,
+    traps: &mut TrapInformation,
+    isa: &dyn TargetIsa,
+    entry: &PatchableEntry,
+) -> Result<PrimaryMap<DefinedFuncIndex, usize>, String> {
+    let nop = nop(isa)?;
+    if entry.size % nop.len() != 0 {
+        return Err(format!(
+            "patchable entry size {} is not a multiple of the {}-byte nop",
+            entry.size,
+            nop.len()
+        ));
+    }
+
+    let mut layout = PrimaryMap::new();
+    for _ in compilation.functions.keys() {
+        layout.push(0);
+    }
+    for &i in &entry.functions {
+        if compilation.failed.iter().any(|failed| failed.index == i) {
+            return Err(format!(
+                "function {} failed to compile and can't have a patchable entry",
+                i.index()
+            ));
+        }
+        if entry.functions.iter().filter(|&&j| j == i).count() > 1 {
+            return Err(format!(
+                "function {} is given a patchable entry more than once",
+                i.index()
+            ));
+        }
+        layout[i] = entry.size;
+
+        let body = &mut compilation.functions[i];
+        let mut padded = Vec::with_capacity(entry.size + body.len());
+        for _ in 0..entry.size / nop.len() {
+            padded.extend_from_slice(nop);
+        }
+        padded.extend_from_slice(body);
+        *body = padded;
+
+        for r in &mut relocations[i] {
+            r.offset += entry.size as u32;
+            if let RelocationTarget::Ebb(ref mut ebb_offset) = r.reloc_target {
+                *ebb_offset += entry.size as u32;
+            }
+        }
+        for site in &mut traps[i] {
+            site.code_offset += entry.size as u32;
+        }
+        for jt_offset in compilation.jt_offsets[i].values_mut() {
+            *jt_offset += entry.size as u32;
+        }
+    }
+    Ok(layout)
+}
+
+/// Emits the patch point table, if any function has a patchable entry.
+///
+/// ```text
+/// count         little-endian u32
+/// size          little-endian u32, bytes of padding at each patch point
+/// count times:
+///     pointer   address of the patch point, the function's start
+/// ```
+pub fn emit_patchable_entries(
+    obj: &mut Artifact,
+    module: &Module,
+    entry: &PatchableEntry,
+    pointer_bytes: u8,
+) -> Result<(), String> {
+    if entry.functions.is_empty() {
+        return Ok(());
+    }
+    let pointer_bytes = usize::from(pointer_bytes);
+    let header = 8;
+    let mut table = Vec::with_capacity(header + entry.functions.len() * pointer_bytes);
+    table.extend_from_slice(&(entry.functions.len() as u32).to_le_bytes());
+    table.extend_from_slice(&(entry.size as u32).to_le_bytes());
+    table.resize(header + entry.functions.len() * pointer_bytes, 0);
+    obj.declare_with(PATCHABLE_ENTRIES_SYMBOL, Decl::data().global(), table)
+        .map_err(|err| format!("{}", err))?;
+
+    for (n, &i) in entry.functions.iter().enumerate() {
+        let target_name = format!("_wasm_function_{}", module.func_index(i).index());
+        obj.link(Link {
+            from: PATCHABLE_ENTRIES_SYMBOL,
+            to: &target_name,
+            at: (header + n * pointer_bytes) as u64,
+        })
+        .map_err(|err| format!("{}", err))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::binemit::Reloc;
+    use cranelift_codegen::{ir, isa, settings};
+    use std::str::FromStr;
+    use target_lexicon::Triple;
+    use wasmtime_environ::{Relocation, TrapSite};
+
+    fn x86_64_isa() -> Box<dyn TargetIsa> {
+        isa::lookup(Triple::from_str("x86_64-unknown-linux-gnu").unwrap())
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()))
+    }
+
+    /// Two functions, each with a relocation at offset 1 and a trap site at
+    /// offset 5.
+    fn compiled() -> (Compilation, Relocations, TrapInformation) {
+        let mut functions = PrimaryMap::new();
+        let mut relocations = PrimaryMap::new();
+        let mut traps = PrimaryMap::new();
+        for _ in 0..2 {
+            functions.push(vec![0xe8, 0, 0, 0, 0, 0x0f, 0x0b, 0xc3]);
+            relocations.push(vec![Relocation {
+                reloc: Reloc::X86CallPCRel4,
+                reloc_target: RelocationTarget::Ebb(7),
+                offset: 1,
+                addend: -4,
+            }]);
+            traps.push(vec![TrapSite {
+                code_offset: 5,
+                trap_code: ir::TrapCode::UnreachableCodeReached,
+                source_loc: ir::SourceLoc::default(),
+            }]);
+        }
+        (Compilation::new(functions), relocations, traps)
+    }
+
+    #[test]
+    fn test_pad_function_entries() {
+        let (mut compilation, mut relocations, mut traps) = compiled();
+        let entry = PatchableEntry {
+            size: 5,
+            functions: vec![DefinedFuncIndex::new(1)],
+        };
+        let layout = pad_function_entries(
+            &mut compilation,
+            &mut relocations,
+            &mut traps,
+            &*x86_64_isa(),
+            &entry,
+        )
+        .unwrap();
+        assert_eq!(layout.values().cloned().collect::<Vec<_>>(), [0, 5]);
+
+        let (unpadded, unpadded_relocations, unpadded_traps) = compiled();
+        let d = DefinedFuncIndex::new;
+        assert_eq!(compilation.functions[d(0)], unpadded.functions[d(0)]);
+        assert_eq!(compilation.functions[d(1)][..5], [0x90; 5]);
+        assert_eq!(
+            compilation.functions[d(1)][5..],
+            unpadded.functions[d(1)][..]
+        );
+
+        assert_eq!(
+            relocations[d(0)][0].offset,
+            unpadded_relocations[d(0)][0].offset
+        );
+        assert_eq!(relocations[d(1)][0].offset, 6);
+        match relocations[d(1)][0].reloc_target {
+            RelocationTarget::Ebb(offset) => assert_eq!(offset, 12),
+            target => panic!("unexpected relocation target {:?}", target),
+        }
+        assert_eq!(
+            traps[d(0)][0].code_offset,
+            unpadded_traps[d(0)][0].code_offset
+        );
+        assert_eq!(traps[d(1)][0].code_offset, 10);
+    }
+
+    #[test]
+    fn test_pad_function_entries_twice() {
+        let (mut compilation, mut relocations, mut traps) = compiled();
+        let entry = PatchableEntry {
+            size: 2,
+            functions: vec![DefinedFuncIndex::new(0), DefinedFuncIndex::new(0)],
+        };
+        assert!(pad_function_entries(
+            &mut compilation,
+            &mut relocations,
+            &mut traps,
+            &*x86_64_isa(),
+            &entry,
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,274 @@
+use crate::function::{link_reloc, runtime_symbol};
+use cranelift_codegen::binemit;
+use cranelift_codegen::ir;
+use cranelift_codegen::ir::InstBuilder;
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::Context;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use faerie::{Artifact, Decl};
+use std::collections::BTreeMap;
+use target_lexicon::{Architecture, BinaryFormat};
+use wasmtime_environ::{RelocationTarget, Relocations};
+
+/// Name of the data symbol holding one `u64` call counter per profiled
+/// libcall or builtin, in the order of `_builtin_profile_names`.
+pub const BUILTIN_PROFILE_COUNTERS_SYMBOL: &str = "_builtin_profile_counters";
+
+/// Name of the data symbol naming the profiled libcalls and builtins.
+pub const BUILTIN_PROFILE_NAMES_SYMBOL: &str = "_builtin_profile_names";
+
+/// External name namespace of the counter in a thunk.
+const COUNTERS_NAMESPACE: u32 = 0;
+
+/// External name namespace of the callee in a thunk.
+const CALLEE_NAMESPACE: u32 = 1;
+
+/// Returns the name of the thunk counting calls to the runtime symbol
+/// `symbol`.
+pub fn profile_thunk_name(symbol: &str) -> String {
+    format!("_profile_{}", symbol)
+}
+
+/// The signature of a libcall or builtin, if calls to it can be counted.
+/// The probestack has its own calling convention and is always called
+/// directly.
+fn target_signature(target: RelocationTarget, isa: &dyn TargetIsa) -> Option<ir::Signature> {
+    let vmctx = ir::AbiParam::special(isa.pointer_type(), ir::ArgumentPurpose::VMContext);
+    let i32 = ir::AbiParam::new(ir::types::I32);
+    let f32 = ir::AbiParam::new(ir::types::F32);
+    let f64 = ir::AbiParam::new(ir::types::F64);
+    let (params, returns) = match target {
+        RelocationTarget::Memory32Grow | RelocationTarget::ImportedMemory32Grow => {
+            (vec![vmctx, i32, i32], vec![i32])
+        }
+        RelocationTarget::Memory32Size | RelocationTarget::ImportedMemory32Size => {
+            (vec![vmctx, i32], vec![i32])
+        }
+        RelocationTarget::LibCall(libcall) => match libcall {
+            ir::LibCall::CeilF32
+            | ir::LibCall::FloorF32
+            | ir::LibCall::TruncF32
+            | ir::LibCall::NearestF32 => (vec![f32], vec![f32]),
+            ir::LibCall::CeilF64
+            | ir::LibCall::FloorF64
+            | ir::LibCall::TruncF64
+            | ir::LibCall::NearestF64 => (vec![f64], vec![f64]),
+            _ => return None,
+        },
+        RelocationTarget::UserFunc(_)
+        | RelocationTarget::SignatureTableBase
+        | RelocationTarget::MemoryBase { .. }
+        | RelocationTarget::Ebb(_)
+        | RelocationTarget::JumpTable(_) => return None,
+    };
+    Some(ir::Signature {
+        params,
+        returns,
+        call_conv: isa.frontend_config().default_call_conv,
+    })
+}
+
+/// Whether calls to `target` go through a counting thunk when profiling.
+pub fn is_profiled(target: RelocationTarget) -> bool {
+    match target {
+        RelocationTarget::UserFunc(_)
+        | RelocationTarget::SignatureTableBase
+        | RelocationTarget::MemoryBase { .. }
+        | RelocationTarget::Ebb(_)
+        | RelocationTarget::JumpTable(_)
+        | RelocationTarget::LibCall(ir::LibCall::Probestack) => false,
+        _ => runtime_symbol(target).is_some(),
+    }
+}
+
+/// Collects the relocations of a thunk, which only refers to its counter
+/// and to its callee.
+struct RelocSink {
+    callee: &'static str,
+    relocs: Vec<(binemit::CodeOffset, binemit::Reloc, String, binemit::Addend)>,
+}
+
+impl binemit::RelocSink for RelocSink {
+    fn reloc_ebb(&mut self, _: binemit::CodeOffset, _: binemit::Reloc, _: binemit::CodeOffset) {
+        panic!("thunk compilation should not produce ebb relocs");
+    }
+    fn reloc_external(
+        &mut self,
+        offset: binemit::CodeOffset,
+        reloc: binemit::Reloc,
+        name: &ir::ExternalName,
+        addend: binemit::Addend,
+    ) {
+        let target = match *name {
+            ir::ExternalName::User {
+                namespace: COUNTERS_NAMESPACE,
+                ..
+            } => String::from(BUILTIN_PROFILE_COUNTERS_SYMBOL),
+            ir::ExternalName::User {
+                namespace: CALLEE_NAMESPACE,
+                ..
+            } => String::from(self.callee),
+            _ => panic!("unexpected thunk relocation target {}", name),
+        };
+        self.relocs.push((offset, reloc, target, addend));
+    }
+    fn reloc_jt(&mut self, _: binemit::CodeOffset, _: binemit::Reloc, _: ir::JumpTable) {
+        panic!("thunk compilation should not produce jump table relocs");
+    }
+}
+
+/// Compiles a thunk that increments counter `counter` and then calls
+/// `callee` with its own arguments, returning its results.
+fn make_profile_thunk(
+    isa: &dyn TargetIsa,
+    signature: ir::Signature,
+    callee: &'static str,
+    counter: usize,
+) -> Result<(Vec<u8>, RelocSink), String> {
+    let pointer_type = isa.pointer_type();
+
+    let mut context = Context::new();
+    context.func =
+        ir::Function::with_name_signature(ir::ExternalName::user(0, 0), signature.clone());
+
+    {
+        let mut fn_builder_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut context.func, &mut fn_builder_ctx);
+        let block0 = builder.create_ebb();
+        for param in &signature.params {
+            builder.append_ebb_param(block0, param.value_type);
+        }
+        builder.switch_to_block(block0);
+        builder.seal_block(block0);
+
+        let counters = builder.create_global_value(ir::GlobalValueData::Symbol {
+            name: ir::ExternalName::user(COUNTERS_NAMESPACE, 0),
+            offset: ir::immediates::Imm64::new((counter * 8) as i64),
+            colocated: true,
+        });
+        let counter_addr = builder.ins().global_value(pointer_type, counters);
+        let mut flags = ir::MemFlags::new();
+        flags.set_notrap();
+        flags.set_aligned();
+        let count = builder.ins().load(ir::types::I64, flags, counter_addr, 0);
+        let count = builder.ins().iadd_imm(count, 1);
+        builder.ins().store(flags, count, counter_addr, 0);
+
+        let callee_sig = builder.import_signature(signature);
+        let callee_ref = builder.import_function(ir::ExtFuncData {
+            name: ir::ExternalName::user(CALLEE_NAMESPACE, 0),
+            signature: callee_sig,
+            colocated: false,
+        });
+        let args = builder.ebb_params(block0).to_vec();
+        let call = builder.ins().call(callee_ref, &args);
+        let results = builder.inst_results(call).to_vec();
+        builder.ins().return_(&results);
+        builder.finalize()
+    }
+
+    let mut code_buf: Vec<u8> = Vec::new();
+    let mut reloc_sink = RelocSink {
+        callee,
+        relocs: Vec::new(),
+    };
+    let mut trap_sink = binemit::NullTrapSink {};
+    context
+        .compile_and_emit(isa, &mut code_buf, &mut reloc_sink, &mut trap_sink)
+        .map_err(|error| format!("{}", error))?;
+    Ok((code_buf, reloc_sink))
+}
+
+/// Encodes the names of the profiled symbols, in counter order.
+///
+/// ```text
+/// count         little-endian u32
+/// count times:
+///     name      NUL-terminated runtime symbol name
+/// ```
+fn encode_profile_names(symbols: &[&str]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&(symbols.len() as u32).to_le_bytes());
+    for symbol in symbols {
+        out.extend_from_slice(symbol.as_bytes());
+        out.push(0);
+    }
+    out
+}
+
+/// Emits a counting thunk for every libcall and builtin the module calls,
+/// along with the `_builtin_profile_counters` and `_builtin_profile_names`
+/// tables. The counters are not updated atomically.
+///
+/// This must be called before `emit_module` with
+/// `EmitOptions::profile_builtins` set, which routes calls through the
+/// thunks. The counters can be reported by
+/// `wasmtime_runtime::libcalls::write_builtin_profile`.
+pub fn emit_builtin_profiling(
+    obj: &mut Artifact,
+    isa: &dyn TargetIsa,
+    relocations: &Relocations,
+) -> Result<(), String> {
+    // The thunks address their counters with an addend, which faerie can
+    // only express through the raw relocations `link_reloc` uses here.
+    if obj.target.binary_format != BinaryFormat::Elf
+        || obj.target.architecture != Architecture::X86_64
+    {
+        return Err(format!(
+            "builtin profiling is only supported for x86_64 ELF output, not {}",
+            obj.target
+        ));
+    }
+    let mut targets = BTreeMap::new();
+    for function_relocs in relocations.values() {
+        for r in function_relocs {
+            if is_profiled(r.reloc_target) {
+                let symbol = runtime_symbol(r.reloc_target).unwrap();
+                targets.insert(symbol, r.reloc_target);
+            }
+        }
+    }
+    let symbols = targets.keys().cloned().collect::<Vec<_>>();
+
+    obj.declare_with(
+        BUILTIN_PROFILE_COUNTERS_SYMBOL,
+        Decl::data().global(),
+        vec![0; symbols.len() * 8],
+    )
+    .map_err(|err| format!("{}", err))?;
+    obj.declare_with(
+        BUILTIN_PROFILE_NAMES_SYMBOL,
+        Decl::data().global(),
+        encode_profile_names(&symbols),
+    )
+    .map_err(|err| format!("{}", err))?;
+
+    for (counter, (symbol, target)) in targets.into_iter().enumerate() {
+        let signature = target_signature(target, isa).unwrap();
+        let (body, reloc_sink) = make_profile_thunk(isa, signature, symbol, counter)?;
+        let thunk_name = profile_thunk_name(symbol);
+        obj.declare(symbol, Decl::function_import())
+            .map_err(|err| format!("{}", err))?;
+        obj.declare_with(&thunk_name, Decl::function(), body)
+            .map_err(|err| format!("{}", err))?;
+        for (offset, reloc, target_name, addend) in reloc_sink.relocs {
+            link_reloc(obj, &thunk_name, &target_name, offset, reloc, addend)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_profile_names() {
+        let names = encode_profile_names(&["wasmtime_f32_ceil", "wasmtime_memory32_grow"]);
+        assert_eq!(&names[..4], &[2, 0, 0, 0]);
+        assert_eq!(
+            &names[4..],
+            &b"wasmtime_f32_ceil\0wasmtime_memory32_grow\0"[..]
+        );
+    }
+}
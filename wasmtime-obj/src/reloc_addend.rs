@@ -0,0 +1,103 @@
+//! Placement of relocation addends, which object formats disagree on.
+//!
+//! Cranelift's addends are relative to the start of the relocated field, so
+//! a PC-relative reference to a symbol carries an addend of -4 to account
+//! for the field itself. ELF keeps addends in its relocation entries, and
+//! ignores the bytes being relocated. Mach-O relocation entries have no
+//! addend: the linker adds whatever the relocated bytes hold, and measures
+//! PC-relative values from the end of the field. For x86-64:
+//!
+//! ```text
+//! binemit::Reloc    ELF          Mach-O
+//! Abs4              explicit     unsupported
+//! Abs8              explicit     inline, as is
+//! X86PCRel4         explicit     inline, plus 4
+//! X86CallPCRel4     explicit     inline, plus 4
+//! X86CallPLTRel4    explicit     inline, plus 4
+//! X86GOTPCRel4      explicit     inline, plus 4
+//! ```
+//!
+//! Other targets and relocation kinds keep their addends explicit.
+
+use cranelift_codegen::binemit;
+use target_lexicon::{Architecture, BinaryFormat, Triple};
+
+/// Where the addend of a relocation goes in an object.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RelocAddend {
+    /// In the relocation entry.
+    Explicit(binemit::Addend),
+    /// In the relocated bytes, which the linker adds to.
+    Inline(binemit::Addend),
+}
+
+/// Returns the addend of a `reloc` relocation with Cranelift's `addend`, in
+/// the form objects for `triple` expect.
+pub fn reloc_addend(
+    triple: &Triple,
+    reloc: binemit::Reloc,
+    addend: binemit::Addend,
+) -> Result<RelocAddend, String> {
+    if triple.binary_format != BinaryFormat::Macho || triple.architecture != Architecture::X86_64 {
+        return Ok(RelocAddend::Explicit(addend));
+    }
+    match reloc {
+        binemit::Reloc::Abs8 => Ok(RelocAddend::Inline(addend)),
+        binemit::Reloc::X86PCRel4
+        | binemit::Reloc::X86CallPCRel4
+        | binemit::Reloc::X86CallPLTRel4
+        | binemit::Reloc::X86GOTPCRel4 => Ok(RelocAddend::Inline(addend + 4)),
+        reloc => Err(format!(
+            "{} relocations are not supported for {}",
+            reloc, triple
+        )),
+    }
+}
+
+/// Writes an inline `addend` into the field of a `reloc` relocation at
+/// `offset` in the little-endian `body`.
+pub fn write_inline_addend(
+    body: &mut [u8],
+    offset: binemit::CodeOffset,
+    reloc: binemit::Reloc,
+    addend: binemit::Addend,
+) {
+    let offset = offset as usize;
+    match reloc {
+        binemit::Reloc::Abs8 => {
+            body[offset..offset + 8].copy_from_slice(&addend.to_le_bytes());
+        }
+        _ => body[offset..offset + 4].copy_from_slice(&(addend as i32).to_le_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_pc_rel4_addend_elf() {
+        let triple = Triple::from_str("x86_64-unknown-linux-gnu").unwrap();
+        assert_eq!(
+            reloc_addend(&triple, binemit::Reloc::X86PCRel4, -8),
+            Ok(RelocAddend::Explicit(-8))
+        );
+    }
+
+    #[test]
+    fn test_pc_rel4_addend_macho() {
+        let triple = Triple::from_str("x86_64-apple-darwin").unwrap();
+        let addend = reloc_addend(&triple, binemit::Reloc::X86PCRel4, -8).unwrap();
+        assert_eq!(addend, RelocAddend::Inline(-4));
+
+        // An instruction with the field followed by a 4-byte immediate.
+        let mut body = vec![0x90; 12];
+        write_inline_addend(&mut body, 2, binemit::Reloc::X86PCRel4, -4);
+        assert_eq!(&body[2..6], &[0xfc, 0xff, 0xff, 0xff]);
+        assert_eq!(&body[..2], &[0x90, 0x90]);
+        assert_eq!(&body[6..], &[0x90; 6]);
+
+        assert!(reloc_addend(&triple, binemit::Reloc::Abs4, 0).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Prefixed section names, so that the objects of several modules can be
+//! linked into one binary without their sections colliding.
+//!
+//! faerie names the sections after the symbols they hold, so the prefix is
+//! applied to the emitted ELF image: `.text._wasm_function_0` becomes
+//! `modA.text._wasm_function_0`, and its relocations' `.rela.text.*`
+//! section becomes `.relamodA.text.*`. Only code, data and debug sections
+//! are renamed; sections the linker or loader looks up by name, such as
+//! `.init_array` or `.note.gnu.build-id`, keep their names.
+
+use crate::elf::patch_elf_section_names;
+
+/// Names of the code and data sections that are prefixed, along with the
+/// `<name>.*` sections faerie gives each symbol. Debug sections, whose names
+/// start with `.debug_`, are prefixed too.
+const PREFIXED_SECTIONS: &[&str] = &[".text", ".data", ".rodata", ".bss"];
+
+/// Checks that `prefix` can start a section name.
+pub fn check_section_prefix(prefix: &str) -> Result<(), String> {
+    if prefix.is_empty() {
+        return Err(String::from("the section prefix is empty"));
+    }
+    if let Some(c) = prefix
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '_' && *c != '.' && *c != '$')
+    {
+        return Err(format!(
+            "the section prefix '{}' contains the invalid character {:?}",
+            prefix, c
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the name of the section `name` with `prefix`, or `None` if the
+/// section keeps its name.
+pub fn prefixed_section_name(prefix: &str, name: &[u8]) -> Option<Vec<u8>> {
+    for rel in &[&b".rela"[..], &b".rel"[..]] {
+        if name.starts_with(rel) {
+            let mut prefixed = rel.to_vec();
+            prefixed.extend(prefixed_section_name(prefix, &name[rel.len()..])?);
+            return Some(prefixed);
+        }
+    }
+    let is_prefixed = name.starts_with(b".debug_")
+        || PREFIXED_SECTIONS.iter().any(|section| {
+            name.starts_with(section.as_bytes())
+                && match name.get(section.len()) {
+                    None | Some(b'.') => true,
+                    Some(_) => false,
+                }
+        });
+    if !is_prefixed {
+        return None;
+    }
+    let mut prefixed = prefix.as_bytes().to_vec();
+    prefixed.extend_from_slice(name);
+    Some(prefixed)
+}
+
+/// Prefixes the names of the code, data and debug sections of a
+/// little-endian ELF image with `prefix`, along with the relocation
+/// sections referring to them. Section symbols have no names of their own,
+/// so they follow their sections.
+pub fn patch_elf_section_prefix(bytes: &mut Vec<u8>, prefix: &str) -> Result<(), String> {
+    check_section_prefix(prefix)?;
+    patch_elf_section_names(bytes, |name| prefixed_section_name(prefix, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefixed_section_name() {
+        let name = |name: &str| {
+            prefixed_section_name("modA", name.as_bytes()).map(|n| String::from_utf8(n).unwrap())
+        };
+        assert_eq!(name(".text"), Some(String::from("modA.text")));
+        assert_eq!(
+            name(".text._wasm_function_0"),
+            Some(String::from("modA.text._wasm_function_0"))
+        );
+        assert_eq!(
+            name(".rela.text._wasm_function_0"),
+            Some(String::from(".relamodA.text._wasm_function_0"))
+        );
+        assert_eq!(name(".debug_info"), Some(String::from("modA.debug_info")));
+        assert_eq!(
+            name(".rela.debug_info"),
+            Some(String::from(".relamodA.debug_info"))
+        );
+        assert_eq!(name(".textual"), None);
+        assert_eq!(name(".init_array"), None);
+        assert_eq!(name(".rela.init_array"), None);
+        assert_eq!(name(".symtab"), None);
+    }
+
+    #[test]
+    fn test_check_section_prefix() {
+        assert!(check_section_prefix("modA").is_ok());
+        assert!(check_section_prefix("mod_a.").is_ok());
+        assert!(check_section_prefix("").is_err());
+        assert!(check_section_prefix("mod a").is_err());
+    }
+
+    #[test]
+    fn test_patch_elf_section_prefix_malformed() {
+        assert!(patch_elf_section_prefix(&mut Vec::new(), "modA").is_err());
+        assert!(patch_elf_section_prefix(&mut b"\x7fELF\x02\x01".to_vec(), "modA").is_err());
+        let mut bytes = b"\x7fELF\x02\x01".to_vec();
+        bytes.resize(0x40, 0);
+        // The section headers are past the end of the image.
+        bytes[0x28] = 0x40;
+        bytes[0x3A] = 64;
+        bytes[0x3C] = 1;
+        assert!(patch_elf_section_prefix(&mut bytes, "modA").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! The `.wasm.tunables` section, recording the `Tunables` the code was
+//! compiled with, so that a loader can check that the memories it provides
+//! match the bounds and guard pages the code relies on.
+//!
+//! The section is little-endian:
+//!
+//! ```text
+//! version                            TUNABLES_SECTION_VERSION, u32
+//! static_memory_bound                u32, in wasm pages
+//! static_memory_offset_guard_size    u64, in bytes
+//! dynamic_memory_offset_guard_size   u64, in bytes
+//! flags                              u32
+//! ```
+//!
+//! Bit 0 of `flags` is `relocatable_signature_table`, and bit 1
+//! `relocatable_memory_base`. The section is not loaded at runtime.
+
+use crate::macho::check_debug_section_name;
+use faerie::{Artifact, Decl};
+use wasmtime_environ::Tunables;
+
+/// Name of the section recording the tunables.
+pub const TUNABLES_SECTION: &str = ".wasm.tunables";
+
+/// Version of the `.wasm.tunables` section layout.
+pub const TUNABLES_SECTION_VERSION: u32 = 1;
+
+const TUNABLES_SECTION_BYTES: usize = 28;
+
+const FLAG_RELOCATABLE_SIGNATURE_TABLE: u32 = 1 << 0;
+const FLAG_RELOCATABLE_MEMORY_BASE: u32 = 1 << 1;
+
+/// Encodes the `.wasm.tunables` section for `tunables`.
+pub fn encode_tunables(tunables: &Tunables) -> Vec<u8> {
+    let mut flags = 0;
+    if tunables.relocatable_signature_table {
+        flags |= FLAG_RELOCATABLE_SIGNATURE_TABLE;
+    }
+    if tunables.relocatable_memory_base {
+        flags |= FLAG_RELOCATABLE_MEMORY_BASE;
+    }
+    let mut out = Vec::with_capacity(TUNABLES_SECTION_BYTES);
+    out.extend_from_slice(&TUNABLES_SECTION_VERSION.to_le_bytes());
+    out.extend_from_slice(&tunables.static_memory_bound.to_le_bytes());
+    out.extend_from_slice(&tunables.static_memory_offset_guard_size.to_le_bytes());
+    out.extend_from_slice(&tunables.dynamic_memory_offset_guard_size.to_le_bytes());
+    out.extend_from_slice(&flags.to_le_bytes());
+    out
+}
+
+/// Decodes a `.wasm.tunables` section.
+pub fn decode_tunables(section: &[u8]) -> Result<Tunables, String> {
+    let u32_at = |at: usize| {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&section[at..at + 4]);
+        u32::from_le_bytes(bytes)
+    };
+    let u64_at = |at: usize| {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&section[at..at + 8]);
+        u64::from_le_bytes(bytes)
+    };
+    if section.len() < 4 {
+        return Err(String::from("truncated tunables section"));
+    }
+    let version = u32_at(0);
+    if version != TUNABLES_SECTION_VERSION {
+        return Err(format!(
+            "unsupported tunables section version {}; expected {}",
+            version, TUNABLES_SECTION_VERSION
+        ));
+    }
+    if section.len() != TUNABLES_SECTION_BYTES {
+        return Err(format!(
+            "tunables section has {} bytes; expected {}",
+            section.len(),
+            TUNABLES_SECTION_BYTES
+        ));
+    }
+    let flags = u32_at(24);
+    Ok(Tunables {
+        static_memory_bound: u32_at(4),
+        static_memory_offset_guard_size: u64_at(8),
+        dynamic_memory_offset_guard_size: u64_at(16),
+        relocatable_signature_table: flags & FLAG_RELOCATABLE_SIGNATURE_TABLE != 0,
+        relocatable_memory_base: flags & FLAG_RELOCATABLE_MEMORY_BASE != 0,
+    })
+}
+
+/// Checks that the code of an object with the `.wasm.tunables` section
+/// `section` was compiled with `expected`, the tunables of the loader.
+pub fn check_tunables(section: &[u8], expected: &Tunables) -> Result<(), String> {
+    let actual = decode_tunables(section)?;
+    let mismatch = |name: &str, actual: &dyn ToString, expected: &dyn ToString| {
+        Err(format!(
+            "the object was compiled with {} {}, but the loader expects {}",
+            name,
+            actual.to_string(),
+            expected.to_string()
+        ))
+    };
+    if actual.static_memory_bound != expected.static_memory_bound {
+        return mismatch(
+            "static_memory_bound",
+            &actual.static_memory_bound,
+            &expected.static_memory_bound,
+        );
+    }
+    if actual.static_memory_offset_guard_size != expected.static_memory_offset_guard_size {
+        return mismatch(
+            "static_memory_offset_guard_size",
+            &actual.static_memory_offset_guard_size,
+            &expected.static_memory_offset_guard_size,
+        );
+    }
+    if actual.dynamic_memory_offset_guard_size != expected.dynamic_memory_offset_guard_size {
+        return mismatch(
+            "dynamic_memory_offset_guard_size",
+            &actual.dynamic_memory_offset_guard_size,
+            &expected.dynamic_memory_offset_guard_size,
+        );
+    }
+    if actual.relocatable_signature_table != expected.relocatable_signature_table {
+        return mismatch(
+            "relocatable_signature_table",
+            &actual.relocatable_signature_table,
+            &expected.relocatable_signature_table,
+        );
+    }
+    if actual.relocatable_memory_base != expected.relocatable_memory_base {
+        return mismatch(
+            "relocatable_memory_base",
+            &actual.relocatable_memory_base,
+            &expected.relocatable_memory_base,
+        );
+    }
+    Ok(())
+}
+
+/// Emits the `.wasm.tunables` section recording `tunables`.
+pub fn emit_tunables(obj: &mut Artifact, tunables: &Tunables) -> Result<(), String> {
+    check_debug_section_name(obj, TUNABLES_SECTION)?;
+    obj.declare_with(
+        TUNABLES_SECTION,
+        Decl::debug_section(),
+        encode_tunables(tunables),
+    )
+    .map_err(|err| format!("{}", err))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tunables_round_trip() {
+        let tunables = Tunables {
+            static_memory_bound: 0x100,
+            static_memory_offset_guard_size: 0x2000,
+            dynamic_memory_offset_guard_size: 0x3000,
+            relocatable_signature_table: false,
+            relocatable_memory_base: true,
+        };
+        let section = encode_tunables(&tunables);
+        assert_eq!(section.len(), TUNABLES_SECTION_BYTES);
+        let decoded = decode_tunables(&section).unwrap();
+        assert_eq!(decoded.static_memory_bound, 0x100);
+        assert_eq!(decoded.static_memory_offset_guard_size, 0x2000);
+        assert_eq!(decoded.dynamic_memory_offset_guard_size, 0x3000);
+        assert!(!decoded.relocatable_signature_table);
+        assert!(decoded.relocatable_memory_base);
+
+        assert!(check_tunables(&section, &tunables).is_ok());
+        let error = check_tunables(&section, &Tunables::default()).unwrap_err();
+        assert!(error.contains("static_memory_bound"));
+        assert!(decode_tunables(&section[..12]).is_err());
+    }
+}
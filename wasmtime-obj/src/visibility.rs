@@ -0,0 +1,70 @@
+use crate::elf::patch_elf_symbol_visibility;
+use cranelift_entity::EntityRef;
+use std::collections::HashSet;
+use std::str::FromStr;
+use wasmtime_environ::{Export, Module};
+
+/// The ELF visibility of the symbols a module exports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    /// `STV_DEFAULT`: visible to other components, and preemptible.
+    Default,
+    /// `STV_HIDDEN`: not visible outside the component being linked.
+    Hidden,
+    /// `STV_PROTECTED`: visible to other components, but not preemptible.
+    Protected,
+}
+
+impl Visibility {
+    fn st_other(self) -> u8 {
+        match self {
+            Visibility::Default => 0,
+            Visibility::Hidden => 2,
+            Visibility::Protected => 3,
+        }
+    }
+}
+
+impl FromStr for Visibility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Visibility::Default),
+            "hidden" => Ok(Visibility::Hidden),
+            "protected" => Ok(Visibility::Protected),
+            _ => Err(format!(
+                "unknown visibility '{}'; expected default, hidden or protected",
+                s
+            )),
+        }
+    }
+}
+
+/// Sets the visibility of the symbols in an ELF image emitted by faerie for
+/// `module`: functions the module exports, and the other symbols a runtime
+/// looks up such as `_vmcontext_init`, get `visibility`, while functions
+/// that aren't exported are hidden.
+pub fn patch_elf_visibility(
+    bytes: &mut [u8],
+    module: &Module,
+    visibility: Visibility,
+) -> Result<(), String> {
+    let exported = module
+        .exports
+        .values()
+        .filter_map(|export| match *export {
+            Export::Function(func_index) => {
+                Some(format!("_wasm_function_{}", func_index.index()).into_bytes())
+            }
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    patch_elf_symbol_visibility(bytes, |name| {
+        if name.starts_with(b"_wasm_function_") && !exported.contains(name) {
+            Some(Visibility::Hidden.st_other())
+        } else {
+            Some(visibility.st_other())
+        }
+    })
+}
@@ -4,6 +4,10 @@
 
 use crate::vmcontext::VMContext;
 use cranelift_wasm::{DefinedMemoryIndex, MemoryIndex};
+use std::ffi::CStr;
+use std::io::{self, Write};
+use std::os::raw::c_char;
+use std::ptr;
 
 /// Implementation of f32.ceil
 pub extern "C" fn wasmtime_f32_ceil(x: f32) -> f32 {
@@ -137,3 +141,45 @@ pub unsafe extern "C" fn wasmtime_imported_memory32_size(
 
     instance.imported_memory_size(memory_index)
 }
+
+/// Writes the call counts gathered by an object compiled with
+/// `wasm2obj --profile-builtins` to `out`, one `name count` line per
+/// libcall or builtin. `names` and `counters` are the object's
+/// `_builtin_profile_names` and `_builtin_profile_counters`. This is meant
+/// to be called at process exit, e.g. from `atexit`.
+pub unsafe fn write_builtin_profile(
+    out: &mut dyn Write,
+    names: *const u8,
+    counters: *const u64,
+) -> io::Result<()> {
+    let mut count_bytes = [0; 4];
+    ptr::copy_nonoverlapping(names, count_bytes.as_mut_ptr(), count_bytes.len());
+    let count = u32::from_le_bytes(count_bytes) as usize;
+
+    let mut name = names.add(count_bytes.len());
+    for i in 0..count {
+        let symbol = CStr::from_ptr(name as *const c_char);
+        writeln!(out, "{} {}", symbol.to_string_lossy(), *counters.add(i))?;
+        name = name.add(symbol.to_bytes_with_nul().len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_builtin_profile() {
+        let names = b"\x02\x00\x00\x00wasmtime_f32_floor\0wasmtime_memory32_grow\0";
+        let counters = [3u64, 0];
+        let mut out = Vec::new();
+        unsafe {
+            write_builtin_profile(&mut out, names.as_ptr(), counters.as_ptr()).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "wasmtime_f32_floor 3\nwasmtime_memory32_grow 0\n"
+        );
+    }
+}
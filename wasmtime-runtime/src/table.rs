@@ -25,10 +25,12 @@ impl Table {
         };
 
         match plan.style {
-            TableStyle::CallerChecksSignature => Self {
-                vec: vec![VMCallerCheckedAnyfunc::default(); plan.table.minimum as usize],
-                maximum: plan.table.maximum,
-            },
+            TableStyle::CallerChecksSignature | TableStyle::CallerChecksRelocatedSignature => {
+                Self {
+                    vec: vec![VMCallerCheckedAnyfunc::default(); plan.table.minimum as usize],
+                    maximum: plan.table.maximum,
+                }
+            }
         }
     }
 